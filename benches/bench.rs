@@ -1,6 +1,11 @@
 #![allow(clippy::incompatible_msrv)] // false positive: https://github.com/rust-lang/rust-clippy/issues/12257#issuecomment-2093667187
 
-use std::{any::type_name, fmt::Debug, hint::black_box};
+use std::{
+    any::type_name,
+    fmt::Debug,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use concurrent_queue::{ConcurrentQueue, PopError};
 use criterion::{criterion_group, criterion_main, Criterion};
@@ -47,6 +52,28 @@ fn mpsc<T: Default + std::fmt::Debug + Send>(recv: &ConcurrentQueue<T>, send: &C
         .run();
 }
 
+fn mpmc<T: Default + std::fmt::Debug + Send>(recv: &ConcurrentQueue<T>, send: &ConcurrentQueue<T>) {
+    let producers = AtomicUsize::new(THREADS);
+
+    Parallel::new()
+        .each(0..THREADS, |_| loop {
+            match recv.pop() {
+                Ok(_) => (),
+                Err(PopError::Empty) => (),
+                Err(PopError::Closed) => break,
+            }
+        })
+        .each(0..THREADS, |_| {
+            for _ in 0..COUNT {
+                send.push(T::default()).unwrap();
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                send.close();
+            }
+        })
+        .run();
+}
+
 fn single_thread<T: Default + std::fmt::Debug>(
     recv: &ConcurrentQueue<T>,
     send: &ConcurrentQueue<T>,
@@ -77,6 +104,13 @@ macro_rules! bench_all(
                     let q = ConcurrentQueue::bounded(THREADS * COUNT);
                     $f::<T>(black_box(&q), black_box(&q));
                 }));
+
+                let name = format!("bounded_faa_{}_{}", stringify!($f), type_name::<T>());
+
+                c.bench_function(&name, |b| b.iter(|| {
+                    let q = ConcurrentQueue::bounded_faa(THREADS * COUNT);
+                    $f::<T>(black_box(&q), black_box(&q));
+                }));
             }
             helper::<u8>(c);
             helper::<u16>(c);
@@ -89,7 +123,14 @@ macro_rules! bench_all(
 
 bench_all!(bench_spsc, spsc);
 bench_all!(bench_mpsc, mpsc);
+bench_all!(bench_mpmc, mpmc);
 bench_all!(bench_single_thread, single_thread);
 
-criterion_group!(generic_group, bench_single_thread, bench_spsc, bench_mpsc);
+criterion_group!(
+    generic_group,
+    bench_single_thread,
+    bench_spsc,
+    bench_mpsc,
+    bench_mpmc
+);
 criterion_main!(generic_group);
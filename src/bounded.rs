@@ -1,30 +1,199 @@
 use alloc::{boxed::Box, vec::Vec};
-use core::mem::MaybeUninit;
+use core::mem::{self, MaybeUninit};
+#[cfg(feature = "latency")]
+use core::time::Duration;
 
 use crossbeam_utils::CachePadded;
 
+use crate::metrics::{Instrumented, Metrics, Spin, Timestamp};
+#[cfg(feature = "seq")]
+use crate::sync::atomic::AtomicU64;
 use crate::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::cell::UnsafeCell;
 #[allow(unused_imports)]
 use crate::sync::prelude::*;
-use crate::{busy_wait, ForcePushError, PopError, PushError};
+use crate::{PopError, PushError, TryPopError, TryPushError};
 
 /// A slot in a queue.
 struct Slot<T> {
     /// The current stamp.
     stamp: AtomicUsize,
 
+    /// The number of laps on which this slot was handed out at the tail.
+    #[cfg(feature = "seq")]
+    laps: UnsafeCell<u64>,
+
+    /// The position that `lost` belongs to.
+    #[cfg(feature = "seq")]
+    lost_at: AtomicUsize,
+
+    /// The number of items displaced right before the item at position `lost_at`.
+    #[cfg(feature = "seq")]
+    lost: AtomicU64,
+
+    /// The time the value was pushed.
+    pushed_at: Timestamp,
+
     /// The value in this slot.
     value: UnsafeCell<MaybeUninit<T>>,
 }
 
+/// A slot reserved for writing by [`Bounded::reserve`].
+///
+/// While this is alive, the slot's stamp is the position it was reserved at with the mark bit set,
+/// so that producers on the next lap see it as held instead of waiting for it to be published. If
+/// it is dropped without being committed, the mark bit is set in the slot's stamp so that consumers
+/// skip over it.
+pub struct WriteSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Bounded<T>,
+
+    /// The reserved slot.
+    slot: &'a Slot<T>,
+
+    /// The value of the tail when the slot was reserved.
+    tail: usize,
+}
+
+impl<'a, T> WriteSlot<'a, T> {
+    /// Takes over `slot`, which was handed out at position `tail`.
+    fn new(queue: &'a Bounded<T>, slot: &'a Slot<T>, tail: usize) -> Self {
+        // SAFETY: The slot was just handed out to us, so nobody else can access it.
+        #[cfg(feature = "seq")]
+        slot.laps.with_mut(|laps| unsafe { *laps += 1 });
+        WriteSlot { queue, slot, tail }
+    }
+
+    /// Returns the uninitialized value in the slot.
+    pub fn value_mut(&mut self) -> &mut MaybeUninit<T> {
+        // SAFETY: We have exclusive access to the slot until it is committed or dropped.
+        self.slot.value.with_mut(|slot| unsafe { &mut *slot })
+    }
+
+    /// Returns the sequence number of the value written into the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        self.queue.seq(self.slot, self.tail)
+    }
+
+    /// Writes a value into the slot and publishes it.
+    pub fn write(mut self, value: T) {
+        self.value_mut().write(value);
+        // SAFETY: The value was just initialized.
+        unsafe { self.commit() }
+    }
+
+    /// Marks the slot as held until it is committed or dropped.
+    fn hold(self) -> Self {
+        self.slot
+            .stamp
+            .store(self.tail | self.queue.mark_bit, Ordering::Relaxed);
+        self
+    }
+
+    /// Publishes the value in the slot.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    pub unsafe fn commit(self) {
+        self.queue.metrics.push();
+        #[cfg(feature = "metrics")]
+        self.queue.record_push(self.tail);
+        self.slot.pushed_at.set();
+        self.slot.stamp.store(self.tail + 1, Ordering::Release);
+        mem::forget(self);
+    }
+}
+
+impl<T> Drop for WriteSlot<'_, T> {
+    fn drop(&mut self) {
+        // Mark the slot as full, but with nothing in it.
+        self.slot
+            .stamp
+            .store((self.tail + 1) | self.queue.mark_bit, Ordering::Release);
+    }
+}
+
+/// A slot claimed for reading by [`Bounded::pop_ref`].
+///
+/// While this is alive, the slot's stamp is the position it was claimed at with the mark bit set,
+/// so that producers see it as occupied. The slot is released for producers once this is dropped.
+pub struct ReadSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Bounded<T>,
+
+    /// The claimed slot.
+    slot: &'a Slot<T>,
+
+    /// The value of the head when the slot was claimed.
+    head: usize,
+
+    /// The number of items displaced right before the item in the slot.
+    #[cfg(feature = "seq")]
+    lost: u64,
+}
+
+impl<T> ReadSlot<'_, T> {
+    /// Returns the sequence number of the value in the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        self.queue.seq(self.slot, self.head)
+    }
+
+    /// Returns the value in the slot.
+    pub fn value(&self) -> &T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_ref() })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_mut() })
+    }
+
+    /// Moves the value out of the slot and releases it.
+    pub fn into_inner(self) -> T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        let value = self
+            .slot
+            .value
+            .with_mut(|slot| unsafe { slot.read().assume_init() });
+        self.release();
+        mem::forget(self);
+        value
+    }
+
+    /// Lets producers write into the slot on the next lap.
+    fn release(&self) {
+        self.queue.release(self.slot, self.head);
+    }
+}
+
+impl<T> Drop for ReadSlot<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot.value.with_mut(|slot| unsafe {
+            let value = &mut *slot;
+            value.as_mut_ptr().drop_in_place();
+        });
+        self.release();
+    }
+}
+
 /// A bounded queue.
 pub struct Bounded<T> {
     /// The head of the queue.
     ///
     /// This value is a "stamp" consisting of an index into the buffer, a mark bit, and a lap, but
     /// packed into a single `usize`. The lower bits represent the index, while the upper bits
-    /// represent the lap. The mark bit in the head is always zero.
+    /// represent the lap. The mark bit is set while the queue is being inspected, which stops
+    /// consumers from moving the head.
     ///
     /// Values are popped from the head of the queue.
     head: CachePadded<AtomicUsize>,
@@ -45,7 +214,18 @@ pub struct Bounded<T> {
     one_lap: usize,
 
     /// If this bit is set in the tail, that means the queue is closed.
+    ///
+    /// If this bit is set in the head, that means the queue is frozen for inspection.
+    ///
+    /// If this bit is set in the stamp of a full slot, that means the slot was reserved but never
+    /// written into or its item was removed.
+    ///
+    /// If this bit is set in the stamp of an empty slot, that means the slot is held by a
+    /// [`ReadSlot`] or a [`WriteSlot`] and can't be written into until it is dropped.
     mark_bit: usize,
+
+    /// Counters for the operations on the queue.
+    metrics: Metrics,
 }
 
 impl<T> Bounded<T> {
@@ -64,6 +244,13 @@ impl<T> Bounded<T> {
             // Set the stamp to `{ lap: 0, mark: 0, index: i }`.
             buffer.push(Slot {
                 stamp: AtomicUsize::new(i),
+                #[cfg(feature = "seq")]
+                laps: UnsafeCell::new(0),
+                #[cfg(feature = "seq")]
+                lost_at: AtomicUsize::new(i),
+                #[cfg(feature = "seq")]
+                lost: AtomicU64::new(0),
+                pushed_at: Timestamp::new(),
                 value: UnsafeCell::new(MaybeUninit::uninit()),
             });
         }
@@ -78,85 +265,191 @@ impl<T> Bounded<T> {
             mark_bit,
             head: CachePadded::new(AtomicUsize::new(head)),
             tail: CachePadded::new(AtomicUsize::new(tail)),
+            metrics: Metrics::new(),
         }
     }
 
     /// Attempts to push an item into the queue.
     pub fn push(&self, value: T) -> Result<(), PushError<T>> {
-        self.push_or_else(value, |value, tail, _, _| {
-            let head = self.head.load(Ordering::Relaxed);
-
-            // If the head lags one lap behind the tail as well...
-            if head.wrapping_add(self.one_lap) == tail {
-                // ...then the queue is full.
-                Err(PushError::Full(value))
-            } else {
-                Ok(value)
+        // The slot is written right away, so it doesn't need to be marked as held.
+        match self.reserve_inner(false) {
+            Ok(slot) => {
+                slot.write(value);
+                Ok(())
             }
-        })
+            Err(TryPushError::Closed(())) => Err(PushError::Closed(value)),
+            Err(_) => Err(PushError::Full(value)),
+        }
     }
 
     /// Pushes an item into the queue, displacing another item if needed.
-    pub fn force_push(&self, value: T) -> Result<Option<T>, ForcePushError<T>> {
-        let result = self.push_or_else(value, |value, tail, new_tail, slot| {
+    ///
+    /// If the slot at the tail is held by a [`ReadSlot`] or a [`WriteSlot`], there is nowhere to
+    /// put the item, so `Busy` is returned instead of waiting for the guard to be dropped.
+    pub fn force_push(&self, value: T) -> Result<Option<T>, TryPushError<T>> {
+        let mut displaced = false;
+        let result = self.reserve_or_else(false, |tail, new_tail, slot| {
+            // Don't wait for the slot to be released.
+            if self.is_held(slot, tail) {
+                return Err(TryPushError::Full(()));
+            }
+
             let head = tail.wrapping_sub(self.one_lap);
             let new_head = new_tail.wrapping_sub(self.one_lap);
 
-            // Try to move the head.
-            if self
-                .head
-                .compare_exchange_weak(head, new_head, Ordering::SeqCst, Ordering::Relaxed)
-                .is_ok()
-            {
+            // Try to move the head, counting the old value as lost unless its reservation was
+            // abandoned.
+            let full = slot.stamp.load(Ordering::Relaxed) & self.mark_bit == 0;
+            if self.skip(head, new_head, full) {
                 // Move the tail.
                 self.tail.store(new_tail, Ordering::SeqCst);
 
-                // Swap out the old value.
-                // SAFETY: We know this is initialized, since it's covered by the current queue.
-                let old = unsafe {
-                    slot.value
-                        .with_mut(|slot| slot.replace(MaybeUninit::new(value)).assume_init())
-                };
+                // The slot is now ours, with the old value still in it.
+                displaced = true;
+                Ok(true)
+            } else {
+                self.metrics.cas_retry();
+                Ok(false)
+            }
+        });
+
+        let mut slot = match result {
+            Ok(slot) => slot,
+            Err(TryPushError::Full(())) => return Err(TryPushError::Busy(value)),
+            Err(_) => return Err(TryPushError::Closed(value)),
+        };
+
+        // Take out the old value, unless its reservation was abandoned.
+        let old = if displaced && slot.slot.stamp.load(Ordering::Relaxed) & self.mark_bit == 0 {
+            self.metrics.displace();
+            // SAFETY: We know this is initialized, since it was covered by the queue.
+            Some(unsafe { slot.value_mut().assume_init_read() })
+        } else {
+            None
+        };
+
+        slot.write(value);
+        Ok(old)
+    }
+
+    /// Reserves a slot at the tail of the queue.
+    pub fn reserve(&self) -> Result<WriteSlot<'_, T>, PushError<()>> {
+        self.reserve_inner(false)
+            .map(WriteSlot::hold)
+            .map_err(TryPushError::into_push_error)
+    }
+
+    /// Reserves a slot at the tail of the queue, reporting `Busy` instead of waiting.
+    pub fn reserve_nowait(&self) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        self.reserve_inner(true).map(WriteSlot::hold)
+    }
 
-                // Update the stamp.
-                slot.stamp.store(tail + 1, Ordering::Release);
+    /// Reserves a slot at the tail of the queue, failing if the queue is full.
+    fn reserve_inner(&self, nowait: bool) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        self.reserve_or_else(nowait, |tail, _, slot| {
+            let head = self.head.load(Ordering::Relaxed);
 
-                // Return a PushError.
-                Err(PushError::Full(old))
+            // If the head lags one lap behind the tail as well, or the slot is held by a
+            // `ReadSlot` or a `WriteSlot`...
+            if (head & !self.mark_bit).wrapping_add(self.one_lap) == tail
+                || self.is_held(slot, tail)
+            {
+                // ...then the queue is full.
+                self.metrics.full();
+                Err(TryPushError::Full(()))
+            } else if nowait {
+                // A consumer is in the middle of popping the item in the slot.
+                Err(TryPushError::Busy(()))
             } else {
-                Ok(value)
+                Ok(false)
+            }
+        })
+    }
+
+    /// Reserves up to `max` consecutive slots at the tail of the queue, moving the tail once.
+    ///
+    /// Stops at the first slot that is not free yet instead of waiting for it.
+    pub fn reserve_batch(&self, max: usize) -> Result<Vec<WriteSlot<'_, T>>, PushError<()>> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            // Check if the queue is closed.
+            if tail & self.mark_bit != 0 {
+                return Err(PushError::Closed(()));
+            }
+
+            // Count the free slots from the tail onwards.
+            let mut count = 0;
+            let mut new_tail = tail;
+            while count < max
+                && self.buffer[new_tail & (self.mark_bit - 1)]
+                    .stamp
+                    .load(Ordering::Acquire)
+                    == new_tail
+            {
+                count += 1;
+                new_tail = self.next(new_tail);
             }
-        });
 
-        match result {
-            Ok(()) => Ok(None),
-            Err(PushError::Full(old_value)) => Ok(Some(old_value)),
-            Err(PushError::Closed(value)) => Err(ForcePushError(value)),
+            if count == 0 {
+                self.metrics.full();
+                return Err(PushError::Full(()));
+            }
+
+            // Try moving the tail past all of them.
+            match self.tail.compare_exchange_weak(
+                tail,
+                new_tail,
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    let mut slots = Vec::with_capacity(count);
+                    while tail != new_tail {
+                        let slot = &self.buffer[tail & (self.mark_bit - 1)];
+                        slots.push(WriteSlot::new(self, slot, tail).hold());
+                        tail = self.next(tail);
+                    }
+                    return Ok(slots);
+                }
+                Err(t) => {
+                    self.metrics.cas_retry();
+                    tail = t;
+                }
+            }
         }
     }
 
-    /// Attempts to push an item into the queue, running a closure on failure.
+    /// Attempts to reserve a slot at the tail of the queue, running a closure on failure.
     ///
     /// `fail` is run when there is no more room left in the tail of the queue. The parameters of
     /// this function are as follows:
     ///
-    /// - The item that failed to push.
-    /// - The value of `self.tail` before the new value would be inserted.
-    /// - The value of `self.tail` after the new value would be inserted.
-    /// - The slot that we attempted to push into.
+    /// - The value of `self.tail` before the slot would be reserved.
+    /// - The value of `self.tail` after the slot would be reserved.
+    /// - The slot that we attempted to reserve, which is full or held by a [`ReadSlot`] or a
+    ///   [`WriteSlot`].
     ///
-    /// If `fail` returns `Ok(val)`, we will try pushing `val` to the head of the queue. Otherwise,
-    /// this function will return the error.
-    fn push_or_else<F>(&self, mut value: T, mut fail: F) -> Result<(), PushError<T>>
+    /// If `fail` returns `Ok(true)`, it has taken ownership of the slot and the tail has been moved
+    /// forward. If it returns `Ok(false)`, we will try again. Otherwise, this function will return
+    /// the error.
+    ///
+    /// If `nowait` is set, `Busy` is returned instead of waiting for a slot to be released.
+    fn reserve_or_else<F>(
+        &self,
+        nowait: bool,
+        mut fail: F,
+    ) -> Result<WriteSlot<'_, T>, TryPushError<()>>
     where
-        F: FnMut(T, usize, usize, &Slot<T>) -> Result<T, PushError<T>>,
+        F: FnMut(usize, usize, &Slot<T>) -> Result<bool, TryPushError<()>>,
     {
         let mut tail = self.tail.load(Ordering::Relaxed);
+        let mut spin = Spin::new(self);
 
         loop {
             // Check if the queue is closed.
             if tail & self.mark_bit != 0 {
-                return Err(PushError::Closed(value));
+                return Err(TryPushError::Closed(()));
             }
 
             // Deconstruct the tail.
@@ -188,31 +481,33 @@ impl<T> Bounded<T> {
                     Ordering::Relaxed,
                 ) {
                     Ok(_) => {
-                        // Write the value into the slot and update the stamp.
-                        slot.value.with_mut(|slot| unsafe {
-                            slot.write(MaybeUninit::new(value));
-                        });
-                        slot.stamp.store(tail + 1, Ordering::Release);
-                        return Ok(());
+                        return Ok(WriteSlot::new(self, slot, tail));
                     }
                     Err(t) => {
+                        self.metrics.cas_retry();
                         tail = t;
                     }
                 }
-            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+            } else if (stamp & !self.mark_bit).wrapping_add(self.one_lap) == tail + 1
+                || stamp == tail.wrapping_sub(self.one_lap) | self.mark_bit
+            {
                 crate::full_fence();
 
-                // We've failed to push; run our failure closure.
-                value = fail(value, tail, new_tail, slot)?;
+                // We've failed to reserve; run our failure closure.
+                if fail(tail, new_tail, slot)? {
+                    return Ok(WriteSlot::new(self, slot, tail));
+                }
 
                 // Loom complains if there isn't an explicit busy wait here.
                 #[cfg(loom)]
-                busy_wait();
+                spin.wait();
 
                 tail = self.tail.load(Ordering::Relaxed);
+            } else if nowait {
+                return Err(TryPushError::Busy(()));
             } else {
                 // Yield because we need to wait for the stamp to get updated.
-                busy_wait();
+                spin.wait();
                 tail = self.tail.load(Ordering::Relaxed);
             }
         }
@@ -220,9 +515,63 @@ impl<T> Bounded<T> {
 
     /// Attempts to pop an item from the queue.
     pub fn pop(&self) -> Result<T, PopError> {
+        self.claim(false)
+            .map(ReadSlot::into_inner)
+            .map_err(TryPopError::into_pop_error)
+    }
+
+    /// Attempts to pop an item from the queue, reporting `Busy` instead of waiting.
+    pub fn pop_nowait(&self) -> Result<T, TryPopError> {
+        self.claim(true).map(ReadSlot::into_inner)
+    }
+
+    /// Attempts to pop an item from the queue, leaving it in its slot until the guard is dropped.
+    pub fn pop_ref(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+
+        // Let producers know that the slot is still in use.
+        slot.slot
+            .stamp
+            .store(slot.head | self.mark_bit, Ordering::Relaxed);
+
+        Ok(slot)
+    }
+
+    /// Attempts to pop an item from the queue along with its sequence number.
+    #[cfg(feature = "seq")]
+    pub fn pop_seq(&self) -> Result<(u64, T), PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+        Ok((slot.seq(), slot.into_inner()))
+    }
+
+    /// Attempts to pop an item from the queue along with the number of items displaced right
+    /// before it.
+    #[cfg(feature = "seq")]
+    pub fn pop_with_loss(&self) -> Result<(T, u64), PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+        let lost = slot.lost;
+        Ok((slot.into_inner(), lost))
+    }
+
+    /// Claims the slot at the head of the queue.
+    ///
+    /// If `nowait` is set, `Busy` is returned instead of waiting for the head to be thawed or for a
+    /// slot to be written.
+    fn claim(&self, nowait: bool) -> Result<ReadSlot<'_, T>, TryPopError> {
         let mut head = self.head.load(Ordering::Relaxed);
+        let mut spin = Spin::new(self);
 
         loop {
+            // If the head is frozen, wait until it is thawed.
+            if head & self.mark_bit != 0 {
+                if nowait {
+                    return Err(TryPopError::Busy);
+                }
+                spin.wait();
+                head = self.head.load(Ordering::Relaxed);
+                continue;
+            }
+
             // Deconstruct the head.
             let index = head & (self.mark_bit - 1);
             let lap = head & !(self.one_lap - 1);
@@ -232,7 +581,7 @@ impl<T> Bounded<T> {
             let stamp = slot.stamp.load(Ordering::Acquire);
 
             // If the stamp is ahead of the head by 1, we may attempt to pop.
-            if head + 1 == stamp {
+            if head + 1 == stamp & !self.mark_bit {
                 let new = if index + 1 < self.buffer.len() {
                     // Same lap, incremented index.
                     // Set to `{ lap: lap, mark: 0, index: index + 1 }`.
@@ -243,6 +592,27 @@ impl<T> Bounded<T> {
                     lap.wrapping_add(self.one_lap)
                 };
 
+                // If the slot's reservation was abandoned or its item was removed, release it and
+                // move on.
+                if stamp & self.mark_bit != 0 {
+                    if self.skip(head, new, false) {
+                        self.release(slot, head);
+                        head = new;
+                    } else {
+                        self.metrics.cas_retry();
+                        head = self.head.load(Ordering::Relaxed);
+                    }
+                    continue;
+                }
+
+                // The count is final once the head has reached the slot, and we only keep it if
+                // the head doesn't move before we claim the slot.
+                #[cfg(feature = "seq")]
+                let lost = {
+                    crate::sync::atomic::fence(Ordering::Acquire);
+                    self.lost(slot, head)
+                };
+
                 // Try moving the head.
                 match self.head.compare_exchange_weak(
                     head,
@@ -251,19 +621,24 @@ impl<T> Bounded<T> {
                     Ordering::Relaxed,
                 ) {
                     Ok(_) => {
-                        // Read the value from the slot and update the stamp.
-                        let value = slot
-                            .value
-                            .with_mut(|slot| unsafe { slot.read().assume_init() });
-                        slot.stamp
-                            .store(head.wrapping_add(self.one_lap), Ordering::Release);
-                        return Ok(value);
+                        self.metrics.pop();
+                        self.metrics.pop_latency(&slot.pushed_at);
+                        return Ok(ReadSlot {
+                            queue: self,
+                            slot,
+                            head,
+                            #[cfg(feature = "seq")]
+                            lost,
+                        });
                     }
                     Err(h) => {
+                        self.metrics.cas_retry();
                         head = h;
                     }
                 }
-            } else if stamp == head {
+            } else if stamp == head || stamp == head.wrapping_sub(self.one_lap) | self.mark_bit {
+                // The slot is empty, or held by a `ReadSlot` since the previous lap, in which case
+                // producers can't get past it either.
                 crate::full_fence();
                 let tail = self.tail.load(Ordering::Relaxed);
 
@@ -271,31 +646,349 @@ impl<T> Bounded<T> {
                 if (tail & !self.mark_bit) == head {
                     // Check if the queue is closed.
                     if tail & self.mark_bit != 0 {
-                        return Err(PopError::Closed);
+                        return Err(TryPopError::Closed);
                     } else {
-                        return Err(PopError::Empty);
+                        return Err(TryPopError::Empty);
                     }
                 }
 
+                // Otherwise, the slot at the head is still being written.
+                if nowait {
+                    return Err(TryPopError::Busy);
+                }
+
                 // Loom complains if there isn't a busy-wait here.
                 #[cfg(loom)]
-                busy_wait();
+                spin.wait();
 
                 head = self.head.load(Ordering::Relaxed);
+            } else if nowait {
+                return Err(TryPopError::Busy);
             } else {
                 // Yield because we need to wait for the stamp to get updated.
-                busy_wait();
+                spin.wait();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops up to `max` items from the head of the queue, moving the head once.
+    ///
+    /// Stops at the first item that is still being written instead of waiting for it. Returns the
+    /// number of items passed to `f`.
+    pub fn pop_batch<F: FnMut(T)>(&self, max: usize, mut f: F) -> usize {
+        let frozen = self.freeze();
+        let start = frozen.head;
+        let tail = self.tail.load(Ordering::SeqCst) & !self.mark_bit;
+
+        // Count the full slots from the head onwards, including abandoned ones.
+        let mut count = 0;
+        let mut end = start;
+        #[cfg(feature = "seq")]
+        let mut lost = 0;
+        while end != tail && count < max {
+            let slot = &self.buffer[end & (self.mark_bit - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if stamp & !self.mark_bit != end + 1 {
+                break;
+            }
+            if stamp & self.mark_bit == 0 {
+                count += 1;
+            }
+
+            // Items lost before a popped item are dropped along with the count, but the ones lost
+            // before trailing abandoned slots are carried over to the next item.
+            #[cfg(feature = "seq")]
+            if stamp & self.mark_bit == 0 {
+                lost = 0;
+            } else {
+                lost += self.lost(slot, end);
+            }
+
+            end = self.next(end);
+        }
+        #[cfg(feature = "seq")]
+        self.add_lost(end, lost);
+
+        // Move the head past the claimed slots, which also thaws it.
+        self.head.store(end, Ordering::SeqCst);
+        mem::forget(frozen);
+
+        let mut head = start;
+        while head != end {
+            let slot = &self.buffer[head & (self.mark_bit - 1)];
+
+            // Abandoned slots are released without reading them.
+            if slot.stamp.load(Ordering::Relaxed) & self.mark_bit == 0 {
+                self.metrics.pop();
+                self.metrics.pop_latency(&slot.pushed_at);
+
+                // SAFETY: The slot is initialized and we have claimed it.
+                let value = slot
+                    .value
+                    .with_mut(|slot| unsafe { slot.read().assume_init() });
+                self.release(slot, head);
+                f(value);
+            } else {
+                self.release(slot, head);
+            }
+
+            head = self.next(head);
+        }
+
+        count
+    }
+
+    /// Returns `true` if `slot` is held by a [`ReadSlot`] or a [`WriteSlot`] from the lap before
+    /// `tail`.
+    fn is_held(&self, slot: &Slot<T>, tail: usize) -> bool {
+        slot.stamp.load(Ordering::Relaxed) == tail.wrapping_sub(self.one_lap) | self.mark_bit
+    }
+
+    /// Returns the position that follows `pos`.
+    fn next(&self, pos: usize) -> usize {
+        if (pos & (self.mark_bit - 1)) + 1 < self.buffer.len() {
+            pos + 1
+        } else {
+            (pos & !(self.one_lap - 1)).wrapping_add(self.one_lap)
+        }
+    }
+
+    /// Returns the sequence number of the item in `slot`, which was handed out at position `pos`.
+    ///
+    /// Every lap numbers as many items as there are slots. The laps are counted in the slot itself,
+    /// since `pos` wraps around sooner than the sequence numbers on 32-bit targets.
+    #[cfg(feature = "seq")]
+    fn seq(&self, slot: &Slot<T>, pos: usize) -> u64 {
+        // SAFETY: The slot is claimed, so it isn't handed out again in the meantime.
+        let laps = slot.laps.with_mut(|laps| unsafe { *laps });
+        (laps - 1) * self.buffer.len() as u64 + (pos & (self.mark_bit - 1)) as u64
+    }
+
+    /// Returns the number of items displaced right before position `pos`, whose slot is `slot`.
+    #[cfg(feature = "seq")]
+    fn lost(&self, slot: &Slot<T>, pos: usize) -> u64 {
+        // A count left over from an earlier lap belongs to another position.
+        if slot.lost_at.load(Ordering::Relaxed) == pos {
+            slot.lost.load(Ordering::Relaxed)
+        } else {
+            0
+        }
+    }
+
+    /// Adds `lost` to the number of items displaced right before position `pos`.
+    ///
+    /// Only the thread that moves the head to `pos` may call this, before it moves the head.
+    #[cfg(feature = "seq")]
+    fn add_lost(&self, pos: usize, lost: u64) {
+        if lost != 0 {
+            let slot = &self.buffer[pos & (self.mark_bit - 1)];
+            slot.lost
+                .store(self.lost(slot, pos) + lost, Ordering::Relaxed);
+            slot.lost_at.store(pos, Ordering::Relaxed);
+        }
+    }
+
+    /// Moves the head from `head` to `new` past a slot whose item isn't popped.
+    ///
+    /// If `displaced` is set, the item in the slot is counted as lost. The items lost before the
+    /// slot are carried over to the next one, so that whoever pops it learns about them.
+    ///
+    /// Returns `false` if the head was moved or frozen in the meantime.
+    #[cfg(feature = "seq")]
+    fn skip(&self, head: usize, new: usize, displaced: bool) -> bool {
+        // Freeze the head while carrying over the count, so that nobody claims the next slot
+        // before it is updated.
+        if self
+            .head
+            .compare_exchange_weak(
+                head,
+                head | self.mark_bit,
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return false;
+        }
+
+        let slot = &self.buffer[head & (self.mark_bit - 1)];
+        self.add_lost(new, self.lost(slot, head) + displaced as u64);
+        self.head.store(new, Ordering::SeqCst);
+        true
+    }
+
+    /// Moves the head from `head` to `new` past a slot whose item isn't popped.
+    ///
+    /// Returns `false` if the head was moved or frozen in the meantime.
+    #[cfg(not(feature = "seq"))]
+    fn skip(&self, head: usize, new: usize, _displaced: bool) -> bool {
+        self.head
+            .compare_exchange_weak(head, new, Ordering::SeqCst, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Releases a claimed slot to producers on the next lap.
+    fn release(&self, slot: &Slot<T>, head: usize) {
+        slot.stamp
+            .store(head.wrapping_add(self.one_lap), Ordering::Release);
+    }
+
+    /// Returns how long the oldest item in the queue has been in it.
+    ///
+    /// Slots that are still being written, or whose item was removed or abandoned, are skipped.
+    #[cfg(feature = "latency")]
+    pub fn oldest_item_age(&self) -> Option<Duration> {
+        'retry: loop {
+            let mut pos = self.head.load(Ordering::Acquire) & !self.mark_bit;
+            let tail = self.tail.load(Ordering::Acquire) & !self.mark_bit;
+
+            while pos != tail {
+                let slot = &self.buffer[pos & (self.mark_bit - 1)];
+                let stamp = slot.stamp.load(Ordering::Acquire);
+
+                if stamp == pos + 1 {
+                    let age = slot.pushed_at.age();
+
+                    // Make sure the item wasn't popped while we were looking at it.
+                    crate::sync::atomic::fence(Ordering::Acquire);
+                    if slot.stamp.load(Ordering::Relaxed) == stamp {
+                        return Some(age);
+                    }
+                    continue 'retry;
+                }
+
+                pos = self.next(pos);
+            }
+
+            return None;
+        }
+    }
+
+    /// Stops consumers from moving the head until the returned guard is dropped.
+    ///
+    /// While the head is frozen, the items between the head and the tail stay in place.
+    fn freeze(&self) -> Frozen<'_, T> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        let mut spin = Spin::new(self);
+
+        loop {
+            // Wait until other inspections are done.
+            if head & self.mark_bit != 0 {
+                spin.wait();
                 head = self.head.load(Ordering::Relaxed);
+                continue;
+            }
+
+            match self.head.compare_exchange_weak(
+                head,
+                head | self.mark_bit,
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Frozen { queue: self, head },
+                Err(h) => {
+                    self.metrics.cas_retry();
+                    head = h;
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on every full slot between the frozen head and the tail, along with its position.
+    ///
+    /// Slots that are still being written or whose reservation was abandoned are skipped.
+    fn for_each_full<F: FnMut(&Slot<T>, usize)>(&self, frozen: &Frozen<'_, T>, mut f: F) {
+        let tail = self.tail.load(Ordering::SeqCst) & !self.mark_bit;
+        let mut head = frozen.head;
+
+        while head != tail {
+            let index = head & (self.mark_bit - 1);
+            let lap = head & !(self.one_lap - 1);
+            let slot = &self.buffer[index];
+
+            if slot.stamp.load(Ordering::Acquire) == head + 1 {
+                f(slot, head);
             }
+
+            head = if index + 1 < self.buffer.len() {
+                head + 1
+            } else {
+                lap.wrapping_add(self.one_lap)
+            };
         }
     }
 
+    /// Calls `f` with the items in the queue, from the head to the tail, without popping them.
+    ///
+    /// Items that are still being written are left out. Consumers wait until this returns.
+    pub fn with_items<R, F: FnOnce(&[&T]) -> R>(&self, f: F) -> R {
+        let frozen = self.freeze();
+        let mut items = Vec::new();
+
+        self.for_each_full(&frozen, |slot, _| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            items.push(
+                slot.value
+                    .with_mut(|slot| unsafe { (*slot).assume_init_ref() }),
+            );
+        });
+
+        f(&items)
+    }
+
+    /// Removes the items for which `pred` returns `true` and returns them.
+    ///
+    /// The slots of removed items are skipped by consumers, like abandoned reservations. Items
+    /// that are still being written are kept. Consumers wait while `pred` runs.
+    pub fn remove_if<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Vec<T> {
+        let frozen = self.freeze();
+        let mut removed = Vec::new();
+
+        self.for_each_full(&frozen, |slot, head| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            let value = slot.value.with_mut(|value| unsafe {
+                if pred((*value).assume_init_ref()) {
+                    Some(value.read().assume_init())
+                } else {
+                    None
+                }
+            });
+
+            if let Some(value) = value {
+                // Mark the slot so that consumers skip over it.
+                slot.stamp
+                    .store((head + 1) | self.mark_bit, Ordering::Release);
+                removed.push(value);
+            }
+        });
+
+        drop(frozen);
+        removed
+    }
+
+    /// Records the number of items in the queue after pushing into the slot at `tail`.
+    #[cfg(feature = "metrics")]
+    fn record_push(&self, tail: usize) {
+        // Consumers can't get past the slot before it is committed, so the head is behind it.
+        let head = self.head.load(Ordering::Relaxed);
+        let hix = head & (self.mark_bit - 1);
+        let tix = tail & (self.mark_bit - 1);
+
+        let len = if hix <= tix {
+            tix - hix + 1
+        } else {
+            self.buffer.len() - hix + tix + 1
+        };
+        self.metrics.occupancy(len);
+    }
+
     /// Returns the number of items in the queue.
     pub fn len(&self) -> usize {
         loop {
             // Load the tail, then load the head.
             let tail = self.tail.load(Ordering::SeqCst);
-            let head = self.head.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst) & !self.mark_bit;
 
             // If the tail didn't change, we've got consistent values to work with.
             if self.tail.load(Ordering::SeqCst) == tail {
@@ -317,7 +1010,7 @@ impl<T> Bounded<T> {
 
     /// Returns `true` if the queue is empty.
     pub fn is_empty(&self) -> bool {
-        let head = self.head.load(Ordering::SeqCst);
+        let head = self.head.load(Ordering::SeqCst) & !self.mark_bit;
         let tail = self.tail.load(Ordering::SeqCst);
 
         // Is the tail equal to the head?
@@ -330,7 +1023,7 @@ impl<T> Bounded<T> {
     /// Returns `true` if the queue is full.
     pub fn is_full(&self) -> bool {
         let tail = self.tail.load(Ordering::SeqCst);
-        let head = self.head.load(Ordering::SeqCst);
+        let head = self.head.load(Ordering::SeqCst) & !self.mark_bit;
 
         // Is the head lagging one lap behind tail?
         //
@@ -358,6 +1051,41 @@ impl<T> Bounded<T> {
     }
 }
 
+/// A guard that keeps the head of a [`Bounded`] queue in place.
+struct Frozen<'a, T> {
+    /// The frozen queue.
+    queue: &'a Bounded<T>,
+
+    /// The value of the head, without the mark bit.
+    head: usize,
+}
+
+impl<T> Drop for Frozen<'_, T> {
+    fn drop(&mut self) {
+        self.queue
+            .head
+            .fetch_and(!self.queue.mark_bit, Ordering::Release);
+    }
+}
+
+impl<T> Instrumented for Bounded<T> {
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity())
+    }
+}
+
 impl<T> Drop for Bounded<T> {
     fn drop(&mut self) {
         // Get the index of the head.
@@ -395,12 +1123,17 @@ impl<T> Drop for Bounded<T> {
                         hix + i - buffer.len()
                     };
 
-                    // Drop the value in the slot.
-                    let slot = &buffer[index];
-                    slot.value.with_mut(|slot| unsafe {
-                        let value = &mut *slot;
-                        value.as_mut_ptr().drop_in_place();
-                    });
+                    // Drop the value in the slot, unless it is skipped or was never written.
+                    let slot = &mut buffer[index];
+                    if slot
+                        .stamp
+                        .with_mut(|&mut stamp| stamp & (mark_bit | (mark_bit - 1)) == index + 1)
+                    {
+                        slot.value.with_mut(|slot| unsafe {
+                            let value = &mut *slot;
+                            value.as_mut_ptr().drop_in_place();
+                        });
+                    }
                 }
             });
         });
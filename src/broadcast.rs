@@ -0,0 +1,600 @@
+//! A bounded broadcast queue where every subscriber sees every item.
+//!
+//! A [`Broadcast`] queue keeps the most recent items in a ring buffer. Each [`Subscriber`] has its
+//! own cursor into the ring and receives a clone of every item pushed after it subscribed.
+//!
+//! Producers do not wait for subscribers that fall behind. When the ring is full, pushing
+//! overwrites the oldest item, and subscribers that had not seen it yet get a
+//! [`PopError::Lagged`] error telling them how many items they missed before they continue from
+//! the oldest retained item.
+//!
+//! Subscribers own a handle to the ring, so they can be moved to other threads.
+//!
+//! The ring uses the same stamps as the bounded [`ConcurrentQueue`](crate::ConcurrentQueue),
+//! but it does not reuse its slots. There, a slot is freed by the one consumer that pops its item
+//! and producers wait for that to happen. Here, an item stays in its slot to be cloned by any
+//! number of subscribers until a producer overwrites it, so each slot also counts the subscribers
+//! reading from it.
+//!
+//! # Limitations
+//!
+//! A producer that is about to overwrite an item waits for subscribers that are cloning that
+//! same item at that moment. Items can't be cloned optimistically and thrown away if a producer
+//! got in the way, the way a seqlock reads, because cloning a value while it is being dropped is
+//! unsound for types like `String`. So if a subscriber is preempted or blocked inside
+//! [`Clone::clone`], the producer overwriting its slot stalls until the clone returns. Other
+//! producers keep going until they come back around to that slot. Keep `clone` cheap and
+//! non-blocking, for example by broadcasting an `Arc`.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::broadcast::{Broadcast, PopError};
+//!
+//! let q = Broadcast::new(2);
+//! let mut a = q.subscribe();
+//! let mut b = q.subscribe();
+//!
+//! q.push(1).unwrap();
+//! assert_eq!(a.pop(), Ok(1));
+//!
+//! q.push(2).unwrap();
+//! q.push(3).unwrap();
+//!
+//! // `a` kept up.
+//! assert_eq!(a.pop(), Ok(2));
+//! assert_eq!(a.pop(), Ok(3));
+//!
+//! // `b` missed an item.
+//! assert_eq!(b.pop(), Err(PopError::Lagged(1)));
+//! assert_eq!(b.pop(), Ok(2));
+//! assert_eq!(b.pop(), Ok(3));
+//!
+//! q.close();
+//! assert_eq!(b.pop(), Err(PopError::Closed));
+//! ```
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::panic::{RefUnwindSafe, UnwindSafe};
+
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::sync::cell::UnsafeCell;
+#[allow(unused_imports)]
+use crate::sync::prelude::*;
+use crate::{busy_wait, PushError};
+
+#[cfg(feature = "std")]
+use std::error;
+
+/// A slot in a broadcast queue.
+struct Slot<T> {
+    /// The current stamp.
+    ///
+    /// If the mark bit is set, a producer is writing into the slot.
+    stamp: AtomicUsize,
+
+    /// The number of subscribers reading from the slot.
+    readers: AtomicUsize,
+
+    /// The value in this slot.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// The ring shared by a broadcast queue and its subscribers.
+struct Inner<T> {
+    /// The tail of the queue.
+    ///
+    /// This value is a "stamp" consisting of an index into the buffer, a mark bit, and a lap, but
+    /// packed into a single `usize`. The lower bits represent the index, while the upper bits
+    /// represent the lap. The mark bit indicates that the queue is closed.
+    ///
+    /// Values are pushed into the tail of the queue.
+    tail: CachePadded<AtomicUsize>,
+
+    /// The buffer holding slots.
+    buffer: Box<[Slot<T>]>,
+
+    /// A stamp with the value of `{ lap: 1, mark: 0, index: 0 }`.
+    one_lap: usize,
+
+    /// If this bit is set in the tail, that means the queue is closed.
+    mark_bit: usize,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Returns the stamp following `stamp`.
+    fn next(&self, stamp: usize) -> usize {
+        let index = stamp & (self.mark_bit - 1);
+        let lap = stamp & !(self.one_lap - 1);
+
+        if index + 1 < self.buffer.len() {
+            // Same lap, incremented index.
+            stamp + 1
+        } else {
+            // One lap forward, index wraps around to zero.
+            lap.wrapping_add(self.one_lap)
+        }
+    }
+
+    /// Returns the number of items from stamp `from` up to stamp `to`.
+    fn distance(&self, from: usize, to: usize) -> usize {
+        let laps =
+            (to & !(self.one_lap - 1)).wrapping_sub(from & !(self.one_lap - 1)) / self.one_lap;
+        (laps * self.buffer.len() + (to & (self.mark_bit - 1)))
+            .wrapping_sub(from & (self.mark_bit - 1))
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        for (i, slot) in self.buffer.iter_mut().enumerate() {
+            // A slot holds a value once it has been written into.
+            if slot.stamp.with_mut(|&mut stamp| stamp != i) {
+                slot.value.with_mut(|slot| unsafe {
+                    let value = &mut *slot;
+                    value.as_mut_ptr().drop_in_place();
+                });
+            }
+        }
+    }
+}
+
+/// A bounded broadcast queue.
+///
+/// Items stay alive until they are overwritten or both the queue and all of its subscribers are
+/// dropped.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct Broadcast<T> {
+    /// The ring.
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> UnwindSafe for Broadcast<T> {}
+impl<T> RefUnwindSafe for Broadcast<T> {}
+
+impl<T> Broadcast<T> {
+    /// Creates a new broadcast queue that retains up to `cap` items.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    ///
+    /// let q = Broadcast::<i32>::new(100);
+    /// ```
+    pub fn new(cap: usize) -> Broadcast<T> {
+        assert!(cap > 0, "capacity must be positive");
+
+        // Allocate a buffer of `cap` slots initialized with stamps.
+        let mut buffer = Vec::with_capacity(cap);
+        for i in 0..cap {
+            // Set the stamp to `{ lap: 0, mark: 0, index: i }`.
+            buffer.push(Slot {
+                stamp: AtomicUsize::new(i),
+                readers: AtomicUsize::new(0),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            });
+        }
+
+        // Compute constants `mark_bit` and `one_lap`.
+        let mark_bit = (cap + 1).next_power_of_two();
+        let one_lap = mark_bit * 2;
+
+        Broadcast {
+            inner: Arc::new(Inner {
+                buffer: buffer.into(),
+                one_lap,
+                mark_bit,
+                tail: CachePadded::new(AtomicUsize::new(0)),
+            }),
+        }
+    }
+
+    /// Creates a new subscriber that receives every item pushed from now on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::{Broadcast, PopError};
+    ///
+    /// let q = Broadcast::new(10);
+    /// q.push(1).unwrap();
+    ///
+    /// let mut s = q.subscribe();
+    /// assert_eq!(s.pop(), Err(PopError::Empty));
+    ///
+    /// q.push(2).unwrap();
+    /// assert_eq!(s.pop(), Ok(2));
+    /// ```
+    pub fn subscribe(&self) -> Subscriber<T> {
+        Subscriber {
+            queue: Broadcast {
+                inner: self.inner.clone(),
+            },
+            head: self.inner.tail.load(Ordering::SeqCst) & !self.inner.mark_bit,
+        }
+    }
+
+    /// Pushes an item into the queue, overwriting the oldest item if the queue is full.
+    ///
+    /// Subscribers that had not popped the overwritten item yet will see [`PopError::Lagged`].
+    /// If a subscriber is cloning the overwritten item right now, this waits for the clone to
+    /// finish, see [Limitations](self#limitations).
+    ///
+    /// If the queue is closed, the item is returned back as [`PushError::Closed`]. This never
+    /// returns [`PushError::Full`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = Broadcast::new(1);
+    ///
+    /// assert_eq!(q.push(1), Ok(()));
+    /// assert_eq!(q.push(2), Ok(()));
+    ///
+    /// q.close();
+    /// assert_eq!(q.push(3), Err(PushError::Closed(3)));
+    /// ```
+    pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        let q = &*self.inner;
+        let mut tail = q.tail.load(Ordering::Relaxed);
+
+        loop {
+            // Check if the queue is closed.
+            if tail & q.mark_bit != 0 {
+                return Err(PushError::Closed(value));
+            }
+
+            // Try moving the tail.
+            match q.tail.compare_exchange_weak(
+                tail,
+                q.next(tail),
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(t) => tail = t,
+            }
+        }
+
+        let slot = &q.buffer[tail & (q.mark_bit - 1)];
+
+        // Wait for the producer of the previous lap to finish writing into the slot.
+        let prev = loop {
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if stamp == tail || stamp == tail.wrapping_sub(q.one_lap) + 1 {
+                break stamp;
+            }
+            busy_wait();
+        };
+
+        // Lock the slot, then wait for subscribers still reading from it.
+        //
+        // The reader count is read with a read-modify-write, which sees every subscriber that
+        // registered before it. A subscriber that registers after it synchronizes with it and so
+        // sees the lock when it checks the stamp.
+        slot.stamp.store(tail | q.mark_bit, Ordering::SeqCst);
+        while slot.readers.fetch_add(0, Ordering::SeqCst) != 0 {
+            busy_wait();
+        }
+
+        slot.value.with_mut(|slot| unsafe {
+            // Drop the value from the previous lap, if there is one.
+            if prev != tail {
+                (*slot).as_mut_ptr().drop_in_place();
+            }
+            slot.write(MaybeUninit::new(value));
+        });
+        slot.stamp.store(tail + 1, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Returns the capacity of the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    ///
+    /// let q = Broadcast::<i32>::new(7);
+    /// assert_eq!(q.capacity(), 7);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.inner.buffer.len()
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// When a queue is closed, no more items can be pushed but subscribers can still pop the
+    /// items they have not seen yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::{Broadcast, PopError};
+    ///
+    /// let q = Broadcast::new(10);
+    /// let mut s = q.subscribe();
+    /// q.push(1).unwrap();
+    ///
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    ///
+    /// assert_eq!(s.pop(), Ok(1));
+    /// assert_eq!(s.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn close(&self) -> bool {
+        let q = &*self.inner;
+        let tail = q.tail.fetch_or(q.mark_bit, Ordering::SeqCst);
+        tail & q.mark_bit == 0
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    ///
+    /// let q = Broadcast::<i32>::new(10);
+    ///
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.inner.tail.load(Ordering::SeqCst) & self.inner.mark_bit != 0
+    }
+}
+
+impl<T> fmt::Debug for Broadcast<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Broadcast")
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// A subscriber to a [`Broadcast`] queue.
+///
+/// Each subscriber pops its own copy of every item. Cloning a subscriber creates a new one at the
+/// same position.
+///
+/// A subscriber keeps the queue's items alive, and it can be sent to another thread if `T` is
+/// `Send` and `Sync`.
+pub struct Subscriber<T> {
+    /// The queue.
+    queue: Broadcast<T>,
+
+    /// The stamp of the next item to pop.
+    head: usize,
+}
+
+impl<T> Subscriber<T> {
+    /// Attempts to pop the next item.
+    ///
+    /// If there are no new items, an error is returned. If items were overwritten before this
+    /// subscriber could pop them, [`PopError::Lagged`] is returned with the number of items that
+    /// were missed, and the next pop continues from the oldest retained item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::{Broadcast, PopError};
+    ///
+    /// let q = Broadcast::new(2);
+    /// let mut s = q.subscribe();
+    ///
+    /// for i in 0..5 {
+    ///     q.push(i).unwrap();
+    /// }
+    ///
+    /// assert_eq!(s.pop(), Err(PopError::Lagged(3)));
+    /// assert_eq!(s.pop(), Ok(3));
+    /// assert_eq!(s.pop(), Ok(4));
+    /// assert_eq!(s.pop(), Err(PopError::Empty));
+    /// ```
+    pub fn pop(&mut self) -> Result<T, PopError>
+    where
+        T: Clone,
+    {
+        let q = &*self.queue.inner;
+
+        loop {
+            let slot = &q.buffer[self.head & (q.mark_bit - 1)];
+
+            // Register as a reader, then check whether the slot holds our item.
+            {
+                let _reader = Reader::new(&slot.readers);
+                if slot.stamp.load(Ordering::SeqCst) == self.head + 1 {
+                    // SAFETY: Producers do not write into the slot while we are reading from it.
+                    let value = slot
+                        .value
+                        .with_mut(|slot| unsafe { (*slot).assume_init_ref().clone() });
+                    self.head = q.next(self.head);
+                    return Ok(value);
+                }
+            }
+
+            let tail = q.tail.load(Ordering::SeqCst);
+            let len = q.distance(self.head, tail & !q.mark_bit);
+
+            if len == 0 {
+                // Check if the queue is closed.
+                if tail & q.mark_bit != 0 {
+                    return Err(PopError::Closed);
+                } else {
+                    return Err(PopError::Empty);
+                }
+            }
+
+            if len > q.buffer.len() {
+                // Our item was overwritten, skip to the oldest retained item.
+                let oldest = (tail & !q.mark_bit).wrapping_sub(q.one_lap);
+                let missed = q.distance(self.head, oldest);
+                self.head = oldest;
+                return Err(PopError::Lagged(missed as u64));
+            }
+
+            // Yield because we need to wait for the item to be written.
+            busy_wait();
+        }
+    }
+
+    /// Returns the number of items this subscriber has not popped yet.
+    ///
+    /// This is at most the capacity of the queue, even if the subscriber has lagged behind.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    ///
+    /// let q = Broadcast::new(2);
+    /// let mut s = q.subscribe();
+    /// assert_eq!(s.len(), 0);
+    ///
+    /// for i in 0..5 {
+    ///     q.push(i).unwrap();
+    /// }
+    /// assert_eq!(s.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        let q = &*self.queue.inner;
+        let tail = q.tail.load(Ordering::SeqCst) & !q.mark_bit;
+        q.distance(self.head, tail).min(q.buffer.len())
+    }
+
+    /// Returns `true` if this subscriber has popped every item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::broadcast::Broadcast;
+    ///
+    /// let q = Broadcast::new(2);
+    /// let mut s = q.subscribe();
+    ///
+    /// assert!(s.is_empty());
+    /// q.push(1).unwrap();
+    /// assert!(!s.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        let q = &*self.queue.inner;
+        let tail = q.tail.load(Ordering::SeqCst) & !q.mark_bit;
+        self.head == tail
+    }
+
+    /// Returns the queue this subscriber belongs to.
+    pub fn queue(&self) -> &Broadcast<T> {
+        &self.queue
+    }
+}
+
+impl<T> Clone for Subscriber<T> {
+    fn clone(&self) -> Self {
+        Subscriber {
+            queue: Broadcast {
+                inner: self.queue.inner.clone(),
+            },
+            head: self.head,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Subscriber<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("len", &self.len())
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+/// Keeps a slot's reader count incremented while alive.
+struct Reader<'a>(&'a AtomicUsize);
+
+impl<'a> Reader<'a> {
+    fn new(readers: &'a AtomicUsize) -> Reader<'a> {
+        readers.fetch_add(1, Ordering::SeqCst);
+        Reader(readers)
+    }
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Error which occurs when popping from a [`Subscriber`].
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum PopError {
+    /// There are no new items, but the queue is not closed.
+    Empty,
+
+    /// There are no new items, and the queue is closed.
+    Closed,
+
+    /// The subscriber fell behind and this many items were overwritten before it could pop them.
+    Lagged(u64),
+}
+
+impl PopError {
+    /// Returns `true` if there are no new items but the queue is not closed.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, PopError::Empty)
+    }
+
+    /// Returns `true` if there are no new items and the queue is closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, PopError::Closed)
+    }
+
+    /// Returns `true` if the subscriber missed some items.
+    pub fn is_lagged(&self) -> bool {
+        matches!(self, PopError::Lagged(_))
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for PopError {}
+
+impl fmt::Debug for PopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopError::Empty => write!(f, "Empty"),
+            PopError::Closed => write!(f, "Closed"),
+            PopError::Lagged(n) => f.debug_tuple("Lagged").field(n).finish(),
+        }
+    }
+}
+
+impl fmt::Display for PopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopError::Empty => write!(f, "Empty"),
+            PopError::Closed => write!(f, "Closed"),
+            PopError::Lagged(n) => write!(f, "Lagged by {} items", n),
+        }
+    }
+}
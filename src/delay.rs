@@ -0,0 +1,399 @@
+//! A queue whose items become available after a delay.
+//!
+//! Every item in a [`DelayQueue`] is pushed with the instant at which it becomes ready.
+//! [`pop`](DelayQueue::pop) only returns items that are ready, earliest first, and
+//! [`next_ready_at`](DelayQueue::next_ready_at) tells a consumer how long it can sleep before the
+//! next item becomes ready.
+//!
+//! Producers push into a lock-free [`ConcurrentQueue`], so pushing never blocks. Consumers move
+//! pushed items into a timer heap guarded by a mutex, so popping is serialized among consumers.
+//!
+//! This module is only available with the `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::delay::DelayQueue;
+//! use concurrent_queue::PopError;
+//! use std::time::{Duration, Instant};
+//!
+//! let q = DelayQueue::unbounded();
+//! let now = Instant::now();
+//!
+//! q.push_at("later", now + Duration::from_secs(60)).unwrap();
+//! q.push_at("now", now).unwrap();
+//!
+//! // Only the ready item can be popped.
+//! assert_eq!(q.pop(), Ok("now"));
+//! assert_eq!(q.pop(), Err(PopError::Empty));
+//! assert_eq!(q.next_ready_at(), Some(now + Duration::from_secs(60)));
+//! ```
+
+use alloc::collections::BinaryHeap;
+use core::cmp::Ordering as CmpOrdering;
+use core::fmt;
+use core::time::Duration;
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// An item in a [`DelayQueue`].
+struct Entry<T> {
+    /// The instant at which the item becomes ready.
+    at: Instant,
+
+    /// The order in which the item reached the timer heap, used to break ties.
+    seq: u64,
+
+    /// The item.
+    value: T,
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // `BinaryHeap` is a max-heap, so the earliest entry must compare as the greatest.
+        other.at.cmp(&self.at).then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// Items that have been moved out of the intake queue.
+struct Timers<T> {
+    /// The items, earliest first.
+    heap: BinaryHeap<Entry<T>>,
+
+    /// The sequence number of the next item moved into the heap.
+    seq: u64,
+}
+
+/// A concurrent queue whose items become available after a delay.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct DelayQueue<T> {
+    /// Items pushed since a consumer last looked at the queue.
+    intake: ConcurrentQueue<Entry<T>>,
+
+    /// Items waiting for their instant.
+    timers: Mutex<Timers<T>>,
+
+    /// The number of items in the queue, ready or not.
+    len: AtomicUsize,
+
+    /// The maximum number of items, or `None` if the queue is unbounded.
+    cap: Option<usize>,
+}
+
+impl<T> DelayQueue<T> {
+    /// Creates a new bounded queue.
+    ///
+    /// The queue holds at most `cap` items, whether they are ready or not.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    ///
+    /// let q = DelayQueue::<i32>::bounded(100);
+    /// ```
+    pub fn bounded(cap: usize) -> DelayQueue<T> {
+        assert!(cap > 0, "capacity must be positive");
+        DelayQueue::with_capacity(Some(cap))
+    }
+
+    /// Creates a new unbounded queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    ///
+    /// let q = DelayQueue::<i32>::unbounded();
+    /// ```
+    pub fn unbounded() -> DelayQueue<T> {
+        DelayQueue::with_capacity(None)
+    }
+
+    /// Creates an empty queue holding at most `cap` items.
+    fn with_capacity(cap: Option<usize>) -> DelayQueue<T> {
+        DelayQueue {
+            intake: ConcurrentQueue::unbounded(),
+            timers: Mutex::new(Timers {
+                heap: BinaryHeap::new(),
+                seq: 0,
+            }),
+            len: AtomicUsize::new(0),
+            cap,
+        }
+    }
+
+    /// Attempts to push an item that becomes ready at `at`.
+    ///
+    /// Items that are ready at the same instant are popped in the order they were pushed. If the
+    /// queue is full or closed, the item is returned back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use concurrent_queue::{PopError, PushError};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = DelayQueue::bounded(1);
+    /// q.push_at(10, Instant::now() + Duration::from_secs(60)).unwrap();
+    /// assert_eq!(q.push_at(20, Instant::now()), Err(PushError::Full(20)));
+    /// assert_eq!(q.pop(), Err(PopError::Empty));
+    /// ```
+    pub fn push_at(&self, value: T, at: Instant) -> Result<(), PushError<T>> {
+        if self.intake.is_closed() {
+            return Err(PushError::Closed(value));
+        }
+
+        // Reserve room for the item.
+        match self.cap {
+            Some(cap) => {
+                let reserved = self
+                    .len
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |len| {
+                        if len < cap {
+                            Some(len + 1)
+                        } else {
+                            None
+                        }
+                    });
+                if reserved.is_err() {
+                    return Err(PushError::Full(value));
+                }
+            }
+            None => {
+                self.len.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let entry = Entry { at, seq: 0, value };
+        self.intake.push(entry).map_err(|err| {
+            // The queue was closed in the meantime.
+            self.len.fetch_sub(1, Ordering::SeqCst);
+            PushError::Closed(err.into_inner().value)
+        })
+    }
+
+    /// Attempts to push an item that becomes ready after `delay`.
+    ///
+    /// If the queue is full or closed, the item is returned back as an error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the instant at which the item becomes ready cannot be represented.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use std::time::Duration;
+    ///
+    /// let q = DelayQueue::unbounded();
+    /// q.push_after(10, Duration::ZERO).unwrap();
+    /// assert_eq!(q.pop(), Ok(10));
+    /// ```
+    pub fn push_after(&self, value: T, delay: Duration) -> Result<(), PushError<T>> {
+        self.push_at(value, Instant::now() + delay)
+    }
+
+    /// Attempts to pop the earliest item that is ready.
+    ///
+    /// If no item is ready yet, [`PopError::Empty`] is returned. [`PopError::Closed`] is only
+    /// returned once the queue is closed and every item has been popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use concurrent_queue::PopError;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = DelayQueue::unbounded();
+    /// let now = Instant::now();
+    /// q.push_at(2, now).unwrap();
+    /// q.push_at(1, now - Duration::from_millis(1)).unwrap();
+    /// q.close();
+    ///
+    /// assert_eq!(q.pop(), Ok(1));
+    /// assert_eq!(q.pop(), Ok(2));
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        let mut timers = self.timers();
+        let closed = self.collect(&mut timers);
+
+        match timers.heap.peek() {
+            Some(entry) if entry.at <= Instant::now() => {
+                let entry = timers.heap.pop().unwrap();
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                Ok(entry.value)
+            }
+            None if closed => Err(PopError::Closed),
+            _ => Err(PopError::Empty),
+        }
+    }
+
+    /// Returns the instant at which the earliest item becomes ready, or `None` if the queue is
+    /// empty.
+    ///
+    /// The instant may be in the past if an item is ready already.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = DelayQueue::unbounded();
+    /// assert_eq!(q.next_ready_at(), None);
+    ///
+    /// let at = Instant::now() + Duration::from_secs(1);
+    /// q.push_at(10, at + Duration::from_secs(1)).unwrap();
+    /// q.push_at(20, at).unwrap();
+    /// assert_eq!(q.next_ready_at(), Some(at));
+    /// ```
+    pub fn next_ready_at(&self) -> Option<Instant> {
+        let mut timers = self.timers();
+        self.collect(&mut timers);
+        timers.heap.peek().map(|entry| entry.at)
+    }
+
+    /// Locks the timer heap.
+    fn timers(&self) -> MutexGuard<'_, Timers<T>> {
+        // Nothing panics while the lock is held, but don't let a panic elsewhere poison it.
+        self.timers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Moves pushed items into the timer heap.
+    ///
+    /// Returns `true` if the queue is closed and no more items can arrive.
+    fn collect(&self, timers: &mut Timers<T>) -> bool {
+        loop {
+            match self.intake.pop() {
+                Ok(mut entry) => {
+                    entry.seq = timers.seq;
+                    timers.seq += 1;
+                    timers.heap.push(entry);
+                }
+                Err(err) => return err.is_closed(),
+            }
+        }
+    }
+
+    /// Returns the number of items in the queue, ready or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = DelayQueue::unbounded();
+    /// q.push_after(1, Duration::ZERO).unwrap();
+    /// q.push_after(2, Duration::from_secs(60)).unwrap();
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if the queue holds no items, ready or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    /// use std::time::Duration;
+    ///
+    /// let q = DelayQueue::unbounded();
+    /// assert!(q.is_empty());
+    ///
+    /// q.push_after(1, Duration::from_secs(60)).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the queue, or `None` if it is unbounded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    ///
+    /// assert_eq!(DelayQueue::<i32>::bounded(7).capacity(), Some(7));
+    /// assert_eq!(DelayQueue::<i32>::unbounded().capacity(), None);
+    /// ```
+    pub fn capacity(&self) -> Option<usize> {
+        self.cap
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// Items that are still delayed can be popped once they are ready.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    ///
+    /// let q = DelayQueue::<i32>::unbounded();
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    /// ```
+    pub fn close(&self) -> bool {
+        self.intake.close()
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::delay::DelayQueue;
+    ///
+    /// let q = DelayQueue::<i32>::unbounded();
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.intake.is_closed()
+    }
+}
+
+impl<T> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DelayQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
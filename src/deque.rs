@@ -0,0 +1,519 @@
+//! A bounded work-stealing deque.
+//!
+//! A [`Worker`] owns the deque: it pushes and pops items at the bottom end, so the item pushed
+//! last is popped first. Any number of [`Stealer`]s take items from the top end, oldest first.
+//! This is the deque of Chase and Lev, on a fixed ring of slots like the one behind bounded
+//! [`ConcurrentQueue`]s.
+//!
+//! A typical executor gives every thread a [`Worker`], shares the [`Stealer`]s, and keeps a
+//! global [`ConcurrentQueue`]. Idle threads refill the global queue from busy ones with
+//! [`Stealer::steal_batch_into`].
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::deque::Worker;
+//!
+//! let w = Worker::new(16);
+//! let s = w.stealer();
+//!
+//! w.push(1).unwrap();
+//! w.push(2).unwrap();
+//! w.push(3).unwrap();
+//!
+//! // The owner pops the newest item, thieves steal the oldest.
+//! assert_eq!(w.pop(), Ok(3));
+//! assert_eq!(s.steal(), Ok(1));
+//! assert_eq!(s.steal(), Ok(2));
+//! ```
+
+use alloc::{boxed::Box, sync::Arc};
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::panic::{RefUnwindSafe, UnwindSafe};
+
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{fence, AtomicUsize, Ordering};
+use crate::sync::cell::UnsafeCell;
+#[allow(unused_imports)]
+use crate::sync::prelude::*;
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// A slot in a deque.
+struct Slot<T> {
+    /// The position the slot can be written at next.
+    ///
+    /// A stealer advances the stamp by one lap once it has read the item it took, which tells the
+    /// worker that the slot is free again.
+    stamp: AtomicUsize,
+
+    /// The value in this slot.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// The state shared by a worker and its stealers.
+struct Inner<T> {
+    /// The position of the oldest item, where stealers take items.
+    top: CachePadded<AtomicUsize>,
+
+    /// The position after the newest item, where the worker pushes and pops.
+    bottom: CachePadded<AtomicUsize>,
+
+    /// The ring of slots.
+    buffer: Box<[Slot<T>]>,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Returns the slot for the given position.
+    fn slot(&self, pos: usize) -> &Slot<T> {
+        &self.buffer[pos % self.buffer.len()]
+    }
+
+    /// Moves the item at the given position out of its slot.
+    ///
+    /// # Safety
+    ///
+    /// The slot must hold an item, and the caller must have exclusive access to it.
+    unsafe fn read(&self, pos: usize) -> T {
+        self.slot(pos)
+            .value
+            .with_mut(|slot| slot.read().assume_init())
+    }
+
+    /// Returns the number of items in the deque.
+    fn len(&self) -> usize {
+        let t = self.top.load(Ordering::SeqCst);
+        let b = self.bottom.load(Ordering::SeqCst);
+
+        // The worker moves `bottom` below `top` for a moment when popping from an empty deque.
+        (b.wrapping_sub(t) as isize).max(0) as usize
+    }
+
+    /// Takes the oldest item.
+    fn steal(&self) -> Result<T, PopError> {
+        loop {
+            let t = self.top.load(Ordering::Acquire);
+            fence(Ordering::SeqCst);
+            let b = self.bottom.load(Ordering::Acquire);
+
+            if b.wrapping_sub(t) as isize <= 0 {
+                return Err(PopError::Empty);
+            }
+
+            // Claim the item, or try again if another thread got to it first.
+            if self
+                .top
+                .compare_exchange(t, t.wrapping_add(1), Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+            {
+                // SAFETY: The item at `t` was claimed by us, and the worker doesn't write into
+                // its slot until we advance the stamp below.
+                let value = unsafe { self.read(t) };
+                self.slot(t)
+                    .stamp
+                    .store(t.wrapping_add(self.buffer.len()), Ordering::Release);
+                return Ok(value);
+            }
+        }
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let Self {
+            top,
+            bottom,
+            buffer,
+        } = self;
+
+        top.with_mut(|&mut top| {
+            bottom.with_mut(|&mut bottom| {
+                // Drop the items that were never taken.
+                let mut pos = top;
+                while pos != bottom {
+                    let slot = &mut buffer[pos % buffer.len()];
+                    // SAFETY: Every slot between `top` and `bottom` holds an item.
+                    slot.value
+                        .with_mut(|slot| unsafe { (*slot).assume_init_drop() });
+                    pos = pos.wrapping_add(1);
+                }
+            })
+        });
+    }
+}
+
+/// The owner of a work-stealing deque.
+///
+/// Only the owner pushes items, and it pops them in last-in, first-out order. A worker can be
+/// sent to another thread but not shared between threads.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct Worker<T> {
+    /// The deque.
+    inner: Arc<Inner<T>>,
+
+    /// Makes the worker `!Sync`, since only one thread may push and pop.
+    _marker: PhantomData<Cell<()>>,
+}
+
+impl<T> UnwindSafe for Worker<T> {}
+impl<T> RefUnwindSafe for Worker<T> {}
+
+impl<T> Worker<T> {
+    /// Creates a new deque that holds at most `cap` items.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::<i32>::new(100);
+    /// ```
+    pub fn new(cap: usize) -> Worker<T> {
+        assert!(cap > 0, "capacity must be positive");
+
+        // Slot `i` is ready to be written at position `i`.
+        let buffer: Box<[Slot<T>]> = (0..cap)
+            .map(|i| Slot {
+                stamp: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        Worker {
+            inner: Arc::new(Inner {
+                top: CachePadded::new(AtomicUsize::new(0)),
+                bottom: CachePadded::new(AtomicUsize::new(0)),
+                buffer,
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a stealer for this deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::new(4);
+    /// let s = w.stealer();
+    ///
+    /// w.push(1).unwrap();
+    /// assert_eq!(s.steal(), Ok(1));
+    /// ```
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Attempts to push an item onto the bottom of the deque.
+    ///
+    /// If the deque is full, the item is returned back as [`PushError::Full`]. A slot only becomes
+    /// free once the stealer that took its item has finished reading it, so the deque may briefly
+    /// stay full after an item was stolen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let w = Worker::new(1);
+    /// assert_eq!(w.push(10), Ok(()));
+    /// assert_eq!(w.push(20), Err(PushError::Full(20)));
+    /// ```
+    pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        let inner = &*self.inner;
+        let b = inner.bottom.load(Ordering::Relaxed);
+        let slot = inner.slot(b);
+
+        // The slot may still hold an item, or a stealer may still be reading it.
+        if slot.stamp.load(Ordering::Acquire) != b {
+            return Err(PushError::Full(value));
+        }
+
+        // SAFETY: The slot is free and stealers can't claim it until `bottom` moves past it.
+        slot.value.with_mut(|slot| unsafe {
+            slot.write(MaybeUninit::new(value));
+        });
+        inner.bottom.store(b.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Attempts to pop the newest item from the bottom of the deque.
+    ///
+    /// If the deque is empty, [`PopError::Empty`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    /// use concurrent_queue::PopError;
+    ///
+    /// let w = Worker::new(4);
+    /// w.push(1).unwrap();
+    /// w.push(2).unwrap();
+    ///
+    /// assert_eq!(w.pop(), Ok(2));
+    /// assert_eq!(w.pop(), Ok(1));
+    /// assert_eq!(w.pop(), Err(PopError::Empty));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        let inner = &*self.inner;
+        let b = inner.bottom.load(Ordering::Relaxed);
+        let t = inner.top.load(Ordering::Relaxed);
+        if b.wrapping_sub(t) as isize <= 0 {
+            return Err(PopError::Empty);
+        }
+
+        // Take the newest item before looking at what stealers are doing. Every store to `bottom`
+        // releases the items below it, since stealers may read any of them.
+        let b = b.wrapping_sub(1);
+        inner.bottom.store(b, Ordering::Release);
+        fence(Ordering::SeqCst);
+        let t = inner.top.load(Ordering::Relaxed);
+
+        match (b.wrapping_sub(t) as isize).cmp(&0) {
+            // The deque was emptied by stealers in the meantime.
+            core::cmp::Ordering::Less => {
+                inner.bottom.store(b.wrapping_add(1), Ordering::Release);
+                Err(PopError::Empty)
+            }
+
+            // Stealers can't reach this item. Its slot is written at the same position next time.
+            // SAFETY: The slot holds an item that only we can take.
+            core::cmp::Ordering::Greater => Ok(unsafe { inner.read(b) }),
+
+            // This is the last item, so race stealers for it.
+            core::cmp::Ordering::Equal => {
+                let won = inner
+                    .top
+                    .compare_exchange(t, t.wrapping_add(1), Ordering::SeqCst, Ordering::Relaxed)
+                    .is_ok();
+                inner.bottom.store(b.wrapping_add(1), Ordering::Release);
+
+                if won {
+                    // SAFETY: We claimed the item, just like a stealer would.
+                    let value = unsafe { inner.read(b) };
+                    inner
+                        .slot(b)
+                        .stamp
+                        .store(b.wrapping_add(inner.buffer.len()), Ordering::Relaxed);
+                    Ok(value)
+                } else {
+                    Err(PopError::Empty)
+                }
+            }
+        }
+    }
+
+    /// Returns the number of items in the deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::new(4);
+    /// w.push(1).unwrap();
+    /// w.push(2).unwrap();
+    /// assert_eq!(w.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the deque is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::new(4);
+    /// assert!(w.is_empty());
+    ///
+    /// w.push(1).unwrap();
+    /// assert!(!w.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::<i32>::new(7);
+    /// assert_eq!(w.capacity(), 7);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.inner.buffer.len()
+    }
+}
+
+impl<T> fmt::Debug for Worker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Worker")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// A handle that steals items from a [`Worker`]'s deque.
+///
+/// Stealers take the oldest items first. They can be cloned and shared between threads.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct Stealer<T> {
+    /// The deque.
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> UnwindSafe for Stealer<T> {}
+impl<T> RefUnwindSafe for Stealer<T> {}
+
+impl<T> Stealer<T> {
+    /// Attempts to steal the oldest item from the top of the deque.
+    ///
+    /// If the deque is empty, [`PopError::Empty`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    /// use concurrent_queue::PopError;
+    ///
+    /// let w = Worker::new(4);
+    /// let s = w.stealer();
+    /// w.push(1).unwrap();
+    /// w.push(2).unwrap();
+    ///
+    /// assert_eq!(s.steal(), Ok(1));
+    /// assert_eq!(s.steal(), Ok(2));
+    /// assert_eq!(s.steal(), Err(PopError::Empty));
+    /// ```
+    pub fn steal(&self) -> Result<T, PopError> {
+        self.inner.steal()
+    }
+
+    /// Steals about half of the items and pushes them into `dest`.
+    ///
+    /// Items are moved oldest first, and stealing stops early if `dest` is full or closed. Room
+    /// in `dest` is reserved before each item is stolen, so no item is ever lost. If the deque
+    /// turns out to be empty, the last reservation is abandoned, which consumers of `dest` skip
+    /// over as described in [`ConcurrentQueue::reserve`].
+    ///
+    /// Returns the number of items moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let w = Worker::new(8);
+    /// for i in 0..5 {
+    ///     w.push(i).unwrap();
+    /// }
+    ///
+    /// let global = ConcurrentQueue::unbounded();
+    /// assert_eq!(w.stealer().steal_batch_into(&global), 3);
+    /// assert_eq!(global.try_iter().collect::<Vec<_>>(), [0, 1, 2]);
+    /// assert_eq!(w.pop(), Ok(4));
+    /// ```
+    pub fn steal_batch_into(&self, dest: &ConcurrentQueue<T>) -> usize {
+        let batch = (self.inner.len() + 1) / 2;
+
+        let mut moved = 0;
+        while moved < batch {
+            let slot = match dest.reserve() {
+                Ok(slot) => slot,
+                Err(_) => break,
+            };
+            match self.inner.steal() {
+                Ok(value) => slot.write(value),
+                Err(_) => break,
+            }
+            moved += 1;
+        }
+        moved
+    }
+
+    /// Returns the number of items in the deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::new(4);
+    /// w.push(1).unwrap();
+    /// assert_eq!(w.stealer().len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the deque is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::<i32>::new(4);
+    /// assert!(w.stealer().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the capacity of the deque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::deque::Worker;
+    ///
+    /// let w = Worker::<i32>::new(7);
+    /// assert_eq!(w.stealer().capacity(), 7);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.inner.buffer.len()
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Stealer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stealer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
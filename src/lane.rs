@@ -0,0 +1,452 @@
+//! A queue with one FIFO lane per key, served fairly.
+//!
+//! A [`LaneQueue`] keeps a separate [`ConcurrentQueue`] for every lane, such as one per tenant of
+//! a service. [`pop`](LaneQueue::pop) serves the lanes by weighted round-robin: each lane in turn
+//! hands out up to its weight in items before the next lane is served, so a busy lane cannot starve
+//! the others. As every item costs the same, this is equivalent to deficit round-robin with a
+//! quantum equal to the weight.
+//!
+//! Lanes are added while the queue is in use, and each lane can be bounded and closed on its own.
+//!
+//! The position of the scheduler is a single atomic counter, so consumers do not wait on each
+//! other. When several consumers pop at the same time, they may all take an item in the same turn,
+//! so the weights are only followed approximately under contention.
+//!
+//! This module is only available with the `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::lane::LaneQueue;
+//!
+//! let q = LaneQueue::new();
+//! q.add_lane("noisy", 1);
+//! q.add_lane("quiet", 1);
+//!
+//! for i in 0..3 {
+//!     q.push(&"noisy", i).unwrap();
+//! }
+//! q.push(&"quiet", 10).unwrap();
+//!
+//! // The lanes take turns.
+//! assert_eq!(q.pop(), Ok(0));
+//! assert_eq!(q.pop(), Ok(10));
+//! assert_eq!(q.pop(), Ok(1));
+//! assert_eq!(q.pop(), Ok(2));
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::Hash;
+
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use crate::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// A lane in a [`LaneQueue`].
+struct Lane<T> {
+    /// The items in the lane.
+    queue: ConcurrentQueue<T>,
+
+    /// The position of the lane's turn in a round, which is the sum of the weights of the lanes
+    /// before it.
+    start: usize,
+}
+
+/// The lanes of a [`LaneQueue`].
+struct Lanes<K, T> {
+    /// The index of each lane, by key.
+    keys: HashMap<K, usize>,
+
+    /// The lanes, in the order they were added.
+    list: Vec<Lane<T>>,
+
+    /// The sum of the weights of all lanes, which is the length of a round.
+    round: usize,
+}
+
+/// A concurrent queue with one FIFO lane per key, served by weighted round-robin.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct LaneQueue<K, T> {
+    /// The lanes.
+    lanes: RwLock<Lanes<K, T>>,
+
+    /// The position of the scheduler.
+    ///
+    /// Taken modulo the length of a round, this is the position of the next item to hand out,
+    /// which belongs to the lane whose turn covers it.
+    turn: AtomicUsize,
+
+    /// Whether the queue is closed.
+    closed: AtomicBool,
+}
+
+impl<K: Hash + Eq, T> LaneQueue<K, T> {
+    /// Creates a new queue without any lanes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::<u32, i32>::new();
+    /// ```
+    pub fn new() -> LaneQueue<K, T> {
+        LaneQueue {
+            lanes: RwLock::new(Lanes {
+                keys: HashMap::new(),
+                list: Vec::new(),
+                round: 0,
+            }),
+            turn: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Adds a lane of unlimited capacity.
+    ///
+    /// The lane hands out up to `weight` items in its turn. Returns `true` if the lane was added,
+    /// or `false` if there already is a lane with this key or the queue is closed.
+    ///
+    /// # Panics
+    ///
+    /// If the weight is zero or the total weight of the lanes overflows `usize`, this method will
+    /// panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::<&str, i32>::new();
+    /// assert!(q.add_lane("a", 1));
+    /// assert!(!q.add_lane("a", 2));
+    /// ```
+    pub fn add_lane(&self, key: K, weight: usize) -> bool {
+        self.add(key, weight, ConcurrentQueue::unbounded)
+    }
+
+    /// Adds a lane holding at most `cap` items.
+    ///
+    /// The lane hands out up to `weight` items in its turn. Returns `true` if the lane was added,
+    /// or `false` if there already is a lane with this key or the queue is closed.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity or the weight is zero, or the total weight of the lanes overflows `usize`,
+    /// this method will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_bounded_lane("a", 1, 1);
+    ///
+    /// q.push(&"a", 10).unwrap();
+    /// assert_eq!(q.push(&"a", 20), Err(PushError::Full(20)));
+    /// ```
+    pub fn add_bounded_lane(&self, key: K, cap: usize, weight: usize) -> bool {
+        self.add(key, weight, || ConcurrentQueue::bounded(cap))
+    }
+
+    /// Adds a lane backed by the queue built by `queue`.
+    fn add(&self, key: K, weight: usize, queue: impl FnOnce() -> ConcurrentQueue<T>) -> bool {
+        assert!(weight > 0, "weight must be positive");
+
+        let mut lanes = self.lanes.write().unwrap_or_else(PoisonError::into_inner);
+        // `close` holds the lock while it closes the lanes, so no lane is added after that.
+        if self.is_closed() || lanes.keys.contains_key(&key) {
+            return false;
+        }
+
+        let start = lanes.round;
+        lanes.round = start
+            .checked_add(weight)
+            .expect("total weight overflows `usize`");
+
+        // Keep the turn at the same position in the round, so the new lane is served after the
+        // existing ones. Consumers hold the read lock, so none of them is moving the turn.
+        if start > 0 {
+            let turn = self.turn.load(Ordering::Relaxed);
+            self.turn.store(turn % start, Ordering::Relaxed);
+        }
+
+        let index = lanes.list.len();
+        lanes.list.push(Lane {
+            queue: queue(),
+            start,
+        });
+        lanes.keys.insert(key, index);
+        true
+    }
+
+    /// Attempts to push an item into the lane with the given key.
+    ///
+    /// If the lane is full, the item is returned back as [`PushError::Full`]. If the lane or the
+    /// queue is closed, or there is no lane with this key, it is returned back as
+    /// [`PushError::Closed`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 1);
+    ///
+    /// q.push(&"a", 10).unwrap();
+    /// assert_eq!(q.push(&"b", 20), Err(PushError::Closed(20)));
+    /// ```
+    pub fn push(&self, key: &K, value: T) -> Result<(), PushError<T>> {
+        let lanes = self.read();
+        match lanes.keys.get(key) {
+            Some(&index) => lanes.list[index].queue.push(value),
+            None => Err(PushError::Closed(value)),
+        }
+    }
+
+    /// Attempts to pop an item from the lane whose turn it is.
+    ///
+    /// Lanes without items are skipped. If every lane is empty, an error is returned, which is
+    /// [`PopError::Closed`] once the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    /// use concurrent_queue::PopError;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 2);
+    /// q.add_lane("b", 1);
+    ///
+    /// for i in 0..3 {
+    ///     q.push(&"a", i).unwrap();
+    ///     q.push(&"b", i + 10).unwrap();
+    /// }
+    /// q.close();
+    ///
+    /// let items: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+    /// assert_eq!(items, [0, 1, 10, 2, 11, 12]);
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        let lanes = self.read();
+        let count = lanes.list.len();
+
+        let mut closed = true;
+        if count > 0 {
+            let turn = self.turn.load(Ordering::Relaxed);
+            let pos = turn % lanes.round;
+            let first = lanes.list.partition_point(|lane| lane.start <= pos) - 1;
+
+            // Try every lane once, starting with the one whose turn it is.
+            for i in 0..count {
+                let index = (first + i) % count;
+                let lane = &lanes.list[index];
+
+                match lane.queue.pop() {
+                    Ok(value) => {
+                        // Move past this item. Lanes that were skipped because they were empty
+                        // give up the rest of their turn.
+                        let next = if i == 0 {
+                            turn.wrapping_add(1)
+                        } else {
+                            let round = if index < first { lanes.round } else { 0 };
+                            turn.wrapping_sub(pos)
+                                .wrapping_add(round)
+                                .wrapping_add(lane.start + 1)
+                        };
+
+                        // If another consumer moved the turn in the meantime, this item counts
+                        // towards its turn instead.
+                        let _ = self.turn.compare_exchange(
+                            turn,
+                            next,
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                        );
+                        return Ok(value);
+                    }
+                    Err(err) => closed &= err.is_closed(),
+                }
+            }
+        }
+
+        if closed && self.is_closed() {
+            Err(PopError::Closed)
+        } else {
+            Err(PopError::Empty)
+        }
+    }
+
+    /// Closes the lane with the given key.
+    ///
+    /// Returns `true` if this call closed the lane, or `false` if it was already closed or there
+    /// is no lane with this key. The remaining items in the lane can still be popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 1);
+    /// q.push(&"a", 10).unwrap();
+    ///
+    /// assert!(q.close_lane(&"a"));
+    /// assert_eq!(q.push(&"a", 20), Err(PushError::Closed(20)));
+    /// assert_eq!(q.pop(), Ok(10));
+    /// ```
+    pub fn close_lane(&self, key: &K) -> bool {
+        let lanes = self.read();
+        match lanes.keys.get(key) {
+            Some(&index) => lanes.list[index].queue.close(),
+            None => false,
+        }
+    }
+
+    /// Returns the number of items in the lane with the given key, or `None` if there is no such
+    /// lane.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 1);
+    /// q.push(&"a", 10).unwrap();
+    ///
+    /// assert_eq!(q.lane_len(&"a"), Some(1));
+    /// assert_eq!(q.lane_len(&"b"), None);
+    /// ```
+    pub fn lane_len(&self, key: &K) -> Option<usize> {
+        let lanes = self.read();
+        lanes
+            .keys
+            .get(key)
+            .map(|&index| lanes.list[index].queue.len())
+    }
+
+    /// Returns the number of lanes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::<&str, i32>::new();
+    /// q.add_lane("a", 1);
+    /// q.add_bounded_lane("b", 10, 1);
+    /// assert_eq!(q.lanes(), 2);
+    /// ```
+    pub fn lanes(&self) -> usize {
+        self.read().list.len()
+    }
+
+    /// Returns the number of items in the queue, across all lanes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 1);
+    /// q.add_lane("b", 1);
+    /// q.push(&"a", 10).unwrap();
+    /// q.push(&"b", 20).unwrap();
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.read().list.iter().map(|lane| lane.queue.len()).sum()
+    }
+
+    /// Returns `true` if every lane is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::new();
+    /// q.add_lane("a", 1);
+    /// assert!(q.is_empty());
+    ///
+    /// q.push(&"a", 10).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.read().list.iter().all(|lane| lane.queue.is_empty())
+    }
+
+    /// Closes the queue and every lane in it.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed. No lanes
+    /// can be added to a closed queue, but the remaining items can still be popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::<&str, i32>::new();
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    /// assert!(!q.add_lane("a", 1));
+    /// ```
+    pub fn close(&self) -> bool {
+        let lanes = self.read();
+        let closed = !self.closed.swap(true, Ordering::SeqCst);
+        for lane in &lanes.list {
+            lane.queue.close();
+        }
+        closed
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::lane::LaneQueue;
+    ///
+    /// let q = LaneQueue::<&str, i32>::new();
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Locks the lanes for reading.
+    fn read(&self) -> RwLockReadGuard<'_, Lanes<K, T>> {
+        self.lanes.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Hash + Eq, T> Default for LaneQueue<K, T> {
+    fn default() -> LaneQueue<K, T> {
+        LaneQueue::new()
+    }
+}
+
+impl<K: Hash + Eq, T> fmt::Debug for LaneQueue<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LaneQueue")
+            .field("lanes", &self.lanes())
+            .field("len", &self.len())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
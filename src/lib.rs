@@ -8,6 +8,17 @@
 //! Queues also have the capability to get [closed] at any point. When closed, no more items can be
 //! pushed into the queue, although the remaining items can still be popped.
 //!
+//! The [`broadcast`] module provides a bounded queue where every subscriber receives every item,
+//! the [`priority`] module provides a queue with a fixed number of priority levels, and the
+//! [`deque`] module provides a work-stealing deque.
+//! With the `std` feature, the `ttl` module provides a queue whose items expire after a deadline,
+//! the `delay` module provides a queue whose items only become available after a delay, the
+//! `lane` module provides a queue that serves several FIFO lanes fairly, and the `sharded` module
+//! provides a queue spread over several shards to scale to many threads.
+//!
+//! To consume from several queues at once, [`pop_any`] and [`Select`] pop from whichever queue
+//! has an item.
+//!
 //! These features make it easy to build channels similar to [`std::sync::mpsc`] on top of this
 //! crate.
 //!
@@ -38,10 +49,36 @@
 //! to be available. See the documentation for the [`std::alloc::GlobalAlloc`] trait for more
 //! information.
 //!
+//! The `metrics` feature makes every queue count its pushes, pops, rejections, displacements and
+//! contention, along with a histogram of its occupancy and its high-water mark. These can then be
+//! read through `ConcurrentQueue::stats()` and `ConcurrentQueue::high_water_mark()`. Without this
+//! feature, the counters are compiled out entirely.
+//!
+//! The `seq` feature numbers the items pushed into each queue, so that producers and consumers can
+//! match them up through `ConcurrentQueue::push_seq()` and `ConcurrentQueue::pop_seq()`. It also
+//! lets consumers learn how many items were overwritten through
+//! `ConcurrentQueue::pop_with_loss()`.
+//!
+//! The `latency` feature timestamps every item as it is pushed, so that the time items spend in
+//! the queue can be read through `ConcurrentQueue::latency_stats()` and
+//! `ConcurrentQueue::oldest_item_age()`. It requires `std` and 64-bit atomics.
+//!
+//! The `tracing` feature emits [`tracing`] events when a queue is closed, rejects a push,
+//! displaces an item, allocates a block or busy-waits for a long time. Each event carries the
+//! queue's capacity, its length and the name set through `ConcurrentQueue::with_name()`.
+//!
+//! The `serde` feature implements [`serde`]'s `Serialize` and `Deserialize` for `ConcurrentQueue`.
+//! A queue is serialized as its capacity, whether it is closed and its items from the head to the
+//! tail, and deserialized into a new bounded or unbounded queue with the same contents. The
+//! overflow policy and the name are not serialized, so a deserialized queue rejects pushes when
+//! it is full and has no name.
+//!
 //! [Bounded]: `ConcurrentQueue::bounded()`
 //! [Unbounded]: `ConcurrentQueue::unbounded()`
 //! [closed]: `ConcurrentQueue::close()`
 //! [`portable-atomic`]: https://crates.io/crates/portable-atomic
+//! [`tracing`]: https://crates.io/crates/tracing
+//! [`serde`]: https://crates.io/crates/serde
 //! [`README`]: https://github.com/taiki-e/portable-atomic/blob/main/README.md#optional-cfg
 
 #![warn(missing_docs, missing_debug_implementations, rust_2018_idioms)]
@@ -57,22 +94,54 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
 use core::panic::{RefUnwindSafe, UnwindSafe};
+#[cfg(feature = "latency")]
+use core::time::Duration;
 use sync::atomic::{self, Ordering};
 
 #[cfg(feature = "std")]
 use std::error;
 
 use crate::bounded::Bounded;
+use crate::metrics::{trace_event, Instrumented, Metrics, Spin};
+use crate::scq::Scq;
 use crate::single::Single;
 use crate::sync::busy_wait;
 use crate::unbounded::Unbounded;
 
+#[cfg(feature = "latency")]
+pub use crate::metrics::LatencyStats;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::Stats;
+pub use crate::select::{pop_any, Select};
+
 mod bounded;
+mod metrics;
+mod scq;
+mod select;
+#[cfg(feature = "serde")]
+mod serialize;
 mod single;
 mod unbounded;
 
+pub mod broadcast;
+#[cfg(feature = "std")]
+pub mod delay;
+pub mod deque;
+#[cfg(feature = "std")]
+pub mod lane;
+pub mod priority;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "std")]
+pub mod ttl;
+
 mod sync;
 
 /// Make the given function const if the given condition is true.
@@ -110,7 +179,13 @@ pub(crate) use const_fn;
 /// assert_eq!(q.pop(), Ok('b'));
 /// assert_eq!(q.pop(), Err(PopError::Empty));
 /// ```
-pub struct ConcurrentQueue<T>(Inner<T>);
+pub struct ConcurrentQueue<T> {
+    /// The underlying queue.
+    inner: Inner<T>,
+
+    /// What `push` does when the queue is full, unless it rejects the item.
+    overflow: Option<Box<Overflow<T>>>,
+}
 
 unsafe impl<T: Send> Send for ConcurrentQueue<T> {}
 unsafe impl<T: Send> Sync for ConcurrentQueue<T> {}
@@ -123,6 +198,7 @@ enum Inner<T> {
     Single(Single<T>),
     Bounded(Bounded<T>),
     Unbounded(Unbounded<T>),
+    Scq(Scq<T>),
 }
 
 impl<T> ConcurrentQueue<T> {
@@ -142,10 +218,87 @@ impl<T> ConcurrentQueue<T> {
     /// let q = ConcurrentQueue::<i32>::bounded(100);
     /// ```
     pub fn bounded(cap: usize) -> ConcurrentQueue<T> {
-        if cap == 1 {
-            ConcurrentQueue(Inner::Single(Single::new()))
+        ConcurrentQueue::bounded_with_policy(cap, OverflowPolicy::Reject)
+    }
+
+    /// Creates a new bounded queue with the given overflow policy.
+    ///
+    /// The queue allocates enough space for `cap` items. When it is full, [`push`](Self::push)
+    /// follows `policy`. Items dropped by the policy are passed to the handler set by
+    /// [`with_eviction_handler`](Self::with_eviction_handler), if there is one.
+    ///
+    /// Other methods such as [`force_push`](Self::force_push) and [`reserve`](Self::reserve) are
+    /// not affected by the policy.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, OverflowPolicy};
+    ///
+    /// let q = ConcurrentQueue::bounded_with_policy(2, OverflowPolicy::DropOldest);
+    ///
+    /// for i in 1..=4 {
+    ///     q.push(i).unwrap();
+    /// }
+    ///
+    /// assert_eq!(q.pop(), Ok(3));
+    /// assert_eq!(q.pop(), Ok(4));
+    /// ```
+    pub fn bounded_with_policy(cap: usize, policy: OverflowPolicy) -> ConcurrentQueue<T> {
+        let inner = if cap == 1 {
+            Inner::Single(Single::new())
         } else {
-            ConcurrentQueue(Inner::Bounded(Bounded::new(cap)))
+            Inner::Bounded(Bounded::new(cap))
+        };
+
+        let overflow = match policy {
+            OverflowPolicy::Reject => None,
+            policy => Some(Box::new(Overflow {
+                policy,
+                evict: None,
+            })),
+        };
+
+        ConcurrentQueue { inner, overflow }
+    }
+
+    /// Creates a new bounded queue built on fetch-and-add.
+    ///
+    /// The queue holds up to `cap` items, like one created by [`bounded`](Self::bounded), but
+    /// producers and consumers claim their positions with a single fetch-and-add instead of
+    /// retrying a compare-and-swap on the head or the tail. This follows the Scalable Circular
+    /// Queue (SCQ) by Ruslan Nikolaev, and holds up better when many threads push and pop at once.
+    /// With few threads, [`bounded`](Self::bounded) is usually faster.
+    ///
+    /// Sequence numbers are handed out as items are pushed, so under concurrent pushes items may
+    /// come out slightly out of sequence-number order. [`len`](Self::len) is approximate while
+    /// pushes and pops are in progress.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, PushError};
+    ///
+    /// let q = ConcurrentQueue::bounded_faa(2);
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap();
+    /// assert_eq!(q.push(3), Err(PushError::Full(3)));
+    ///
+    /// assert_eq!(q.pop(), Ok(1));
+    /// assert_eq!(q.pop(), Ok(2));
+    /// ```
+    pub fn bounded_faa(cap: usize) -> ConcurrentQueue<T> {
+        ConcurrentQueue {
+            inner: Inner::Scq(Scq::new(cap)),
+            overflow: None,
         }
     }
 
@@ -161,13 +314,158 @@ impl<T> ConcurrentQueue<T> {
         /// let q = ConcurrentQueue::<i32>::unbounded();
         /// ```
         pub const fn unbounded() -> ConcurrentQueue<T> {
-            ConcurrentQueue(Inner::Unbounded(Unbounded::new()))
+            ConcurrentQueue {
+                inner: Inner::Unbounded(Unbounded::new(false)),
+                overflow: None,
+            }
+        }
+    );
+
+    const_fn!(
+        const_if: #[cfg(not(loom))];
+        /// Creates a new unbounded queue whose pops never wait for producers.
+        ///
+        /// In a queue created by [`unbounded`](Self::unbounded), a pop that reaches an item that
+        /// is still being pushed waits until the push completes, which can take a whole scheduler
+        /// quantum if the producer is preempted. In this queue, the pop skips past the item
+        /// instead and the producer pushes it again at the tail, so a pop finishes in a bounded
+        /// number of steps unless other consumers keep it busy.
+        ///
+        /// Items that one thread pushes one after another are still popped in order, but an item
+        /// written through a [`WriteSlot`] can end up behind items pushed after the slot was
+        /// reserved. An item that was pushed again gets the sequence number of its new slot, which
+        /// is the one `push_seq()` returns.
+        ///
+        /// An item that was skipped is pushed again even if the queue was closed in the meantime,
+        /// because its push had already been accepted. Until it is, pops on a closed queue report
+        /// [`PopError::Empty`], so [`PopError::Closed`] still means the queue is closed and
+        /// drained.
+        ///
+        /// Where a pop would otherwise wait for another thread, such as a consumer moving the
+        /// head to the next block or an inspection like [`retain`](Self::retain), it reports
+        /// [`PopError::Empty`] instead. Pushes can wait for other producers.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use concurrent_queue::ConcurrentQueue;
+        ///
+        /// let q = ConcurrentQueue::unbounded_nonblocking();
+        ///
+        /// // A push is in progress.
+        /// let slot = q.reserve().unwrap();
+        /// q.push(2).unwrap();
+        ///
+        /// // Consumers don't wait for it.
+        /// assert_eq!(q.pop(), Ok(2));
+        /// assert!(q.pop().is_err());
+        ///
+        /// // Once it completes, the item is at the tail.
+        /// slot.write(1);
+        /// assert_eq!(q.pop(), Ok(1));
+        /// ```
+        pub const fn unbounded_nonblocking() -> ConcurrentQueue<T> {
+            ConcurrentQueue {
+                inner: Inner::Unbounded(Unbounded::new(true)),
+                overflow: None,
+            }
         }
     );
 
+    /// Sets a handler that receives items dropped by the queue's [`OverflowPolicy`].
+    ///
+    /// Without a handler, dropped items are simply dropped. Queues that reject items when they are
+    /// full never drop any, so the handler is not kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, OverflowPolicy};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let evicted = Arc::new(AtomicUsize::new(0));
+    /// let q = ConcurrentQueue::bounded_with_policy(1, OverflowPolicy::DropNewest)
+    ///     .with_eviction_handler({
+    ///         let evicted = evicted.clone();
+    ///         move |i| {
+    ///             evicted.fetch_add(i, Ordering::Relaxed);
+    ///         }
+    ///     });
+    ///
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap();
+    /// q.push(3).unwrap();
+    ///
+    /// assert_eq!(q.pop(), Ok(1));
+    /// assert_eq!(evicted.load(Ordering::Relaxed), 5);
+    /// ```
+    pub fn with_eviction_handler<F>(mut self, handler: F) -> ConcurrentQueue<T>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        if let Some(overflow) = &mut self.overflow {
+            overflow.evict = Some(Box::new(handler));
+        }
+        self
+    }
+
+    /// Assigns a name to the queue.
+    ///
+    /// The name shows up in the [`Debug`](fmt::Debug) output of the queue. With the `tracing`
+    /// feature, it is also attached to every event emitted by the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::<i32>::unbounded().with_name("jobs");
+    /// assert_eq!(q.name(), Some("jobs"));
+    /// ```
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> ConcurrentQueue<T> {
+        self.metrics_mut().set_name(name.into().into_boxed_str());
+        self
+    }
+
+    /// Returns the name assigned to the queue, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::<i32>::bounded(1);
+    /// assert_eq!(q.name(), None);
+    /// ```
+    pub fn name(&self) -> Option<&str> {
+        self.metrics().name()
+    }
+
+    /// Returns the overflow policy of the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, OverflowPolicy};
+    ///
+    /// let q = ConcurrentQueue::<i32>::bounded(1);
+    /// assert_eq!(q.overflow_policy(), OverflowPolicy::Reject);
+    ///
+    /// let q = ConcurrentQueue::<i32>::bounded_with_policy(1, OverflowPolicy::DropNewest);
+    /// assert_eq!(q.overflow_policy(), OverflowPolicy::DropNewest);
+    /// ```
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow
+            .as_ref()
+            .map_or(OverflowPolicy::Reject, |overflow| overflow.policy)
+    }
+
     /// Attempts to push an item into the queue.
     ///
-    /// If the queue is full or closed, the item is returned back as an error.
+    /// If the queue is full or closed, the item is returned back as an error. Queues created with
+    /// [`bounded_with_policy`](Self::bounded_with_policy) follow their [`OverflowPolicy`] instead
+    /// when full.
     ///
     /// # Examples
     ///
@@ -195,10 +493,62 @@ impl<T> ConcurrentQueue<T> {
     /// assert_eq!(q.push(20), Err(PushError::Closed(20)));
     /// ```
     pub fn push(&self, value: T) -> Result<(), PushError<T>> {
-        match &self.0 {
+        let result = match self.overflow_policy() {
+            OverflowPolicy::Reject => self.push_inner(value),
+            OverflowPolicy::DropOldest => match self.force_push_inner(value) {
+                Ok(Some(old)) => {
+                    self.evict(old);
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(TryPushError::Closed(value)) => Err(PushError::Closed(value)),
+                Err(TryPushError::Full(value) | TryPushError::Busy(value)) => {
+                    Err(PushError::Full(value))
+                }
+            },
+            OverflowPolicy::DropNewest => match self.push_inner(value) {
+                Err(PushError::Full(value)) => {
+                    trace_event!(DEBUG, self, "dropped the newest item, queue is full");
+                    self.evict(value);
+                    Ok(())
+                }
+                result => result,
+            },
+        };
+
+        self.rejected(result)
+    }
+
+    /// Pushes an item into the underlying queue.
+    fn push_inner(&self, value: T) -> Result<(), PushError<T>> {
+        match &self.inner {
             Inner::Single(q) => q.push(value),
             Inner::Bounded(q) => q.push(value),
             Inner::Unbounded(q) => q.push(value),
+            Inner::Scq(q) => q.push(value),
+        }
+    }
+
+    /// Emits an event if a push was rejected.
+    fn rejected<S, U>(&self, result: Result<S, PushError<U>>) -> Result<S, PushError<U>> {
+        match &result {
+            Ok(_) => {}
+            Err(PushError::Full(_)) => trace_event!(DEBUG, self, "push rejected, queue is full"),
+            Err(PushError::Closed(_)) => {
+                trace_event!(DEBUG, self, "push rejected, queue is closed")
+            }
+        }
+        result
+    }
+
+    /// Passes an item dropped by the overflow policy to the eviction handler.
+    fn evict(&self, value: T) {
+        if let Some(evict) = self
+            .overflow
+            .as_ref()
+            .and_then(|overflow| overflow.evict.as_ref())
+        {
+            evict(value);
         }
     }
 
@@ -208,6 +558,11 @@ impl<T> ConcurrentQueue<T> {
     /// queue is replaced with the provided item. The displaced item is returned as `Some(T)`.
     /// If the queue is closed, an error is returned.
     ///
+    /// If the slot the item would go into is held by a [`ReadSlot`] or a [`WriteSlot`], nothing
+    /// can be displaced, so this waits until the guard is dropped. Calling it while the current
+    /// thread holds such a guard can therefore block forever. Use
+    /// [`try_force_push`](Self::try_force_push) to get an error instead.
+    ///
     /// # Examples
     ///
     /// ```
@@ -238,235 +593,1203 @@ impl<T> ConcurrentQueue<T> {
     /// assert_eq!(q.pop(), Ok(5));
     /// assert_eq!(q.pop(), Ok(6));
     /// ```
-    pub fn force_push(&self, value: T) -> Result<Option<T>, ForcePushError<T>> {
-        match &self.0 {
-            Inner::Single(q) => q.force_push(value),
-            Inner::Bounded(q) => q.force_push(value),
-            Inner::Unbounded(q) => match q.push(value) {
-                Ok(()) => Ok(None),
-                Err(PushError::Closed(value)) => Err(ForcePushError(value)),
-                Err(PushError::Full(_)) => unreachable!(),
-            },
+    pub fn force_push(&self, mut value: T) -> Result<Option<T>, ForcePushError<T>> {
+        let mut spin = Spin::new(self);
+
+        loop {
+            match self.force_push_inner(value) {
+                Ok(old) => return Ok(old),
+                Err(TryPushError::Closed(v)) => return Err(ForcePushError(v)),
+                Err(TryPushError::Full(v) | TryPushError::Busy(v)) => value = v,
+            }
+
+            // Wait for the guard holding the slot to be dropped.
+            spin.wait();
         }
     }
 
-    /// Attempts to pop an item from the queue.
+    /// Push an element into the queue, displacing another element, without waiting for guards.
     ///
-    /// If the queue is empty, an error is returned.
+    /// This works like [`force_push`](Self::force_push), except that if the slot the item would
+    /// go into is held by a [`ReadSlot`] or a [`WriteSlot`], the item is returned back as
+    /// [`TryPushError::Busy`] instead of waiting for the guard to be dropped. If the queue is
+    /// closed, [`TryPushError::Closed`] is returned.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::{ConcurrentQueue, PopError};
+    /// use concurrent_queue::{ConcurrentQueue, TryPushError};
     ///
     /// let q = ConcurrentQueue::bounded(1);
+    /// q.push(1).unwrap();
     ///
-    /// // Pop errors when the queue is empty.
-    /// assert_eq!(q.pop(), Err(PopError::Empty));
-    ///
-    /// // Push one item and close the queue.
-    /// assert_eq!(q.push(10), Ok(()));
-    /// q.close();
-    ///
-    /// // Remaining items can be popped.
-    /// assert_eq!(q.pop(), Ok(10));
+    /// // The only slot is held, so there is nothing to displace.
+    /// let item = q.pop_ref().unwrap();
+    /// assert_eq!(q.try_force_push(2), Err(TryPushError::Busy(2)));
     ///
-    /// // Again, pop errors when the queue is empty,
-    /// // but now also indicates that the queue is closed.
-    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// drop(item);
+    /// assert_eq!(q.try_force_push(2), Ok(None));
+    /// assert_eq!(q.try_force_push(3), Ok(Some(2)));
     /// ```
-    pub fn pop(&self) -> Result<T, PopError> {
-        match &self.0 {
-            Inner::Single(q) => q.pop(),
-            Inner::Bounded(q) => q.pop(),
-            Inner::Unbounded(q) => q.pop(),
+    pub fn try_force_push(&self, value: T) -> Result<Option<T>, TryPushError<T>> {
+        let result = self.force_push_inner(value);
+        if let Err(TryPushError::Busy(_)) = &result {
+            trace_event!(DEBUG, self, "push rejected, slot is held");
         }
+        result
     }
 
-    /// Get an iterator over the items in the queue.
+    /// Pushes an item into the underlying queue, displacing another item if needed.
     ///
-    /// The iterator will continue until the queue is empty or closed. It will never block;
-    /// if the queue is empty, the iterator will return `None`. If new items are pushed into
-    /// the queue, the iterator may return `Some` in the future after returning `None`.
+    /// `Busy` is returned if the slot the item would go into is held by a guard.
+    fn force_push_inner(&self, value: T) -> Result<Option<T>, TryPushError<T>> {
+        let result = match &self.inner {
+            Inner::Single(q) => q.force_push(value),
+            Inner::Bounded(q) => q.force_push(value),
+            Inner::Unbounded(q) => match q.push(value) {
+                Ok(()) => Ok(None),
+                Err(PushError::Closed(value)) => Err(TryPushError::Closed(value)),
+                Err(PushError::Full(_)) => unreachable!(),
+            },
+            Inner::Scq(q) => q.force_push(value),
+        };
+
+        match &result {
+            Ok(Some(_)) => trace_event!(DEBUG, self, "displaced an item, queue is full"),
+            Err(TryPushError::Closed(_)) => {
+                trace_event!(DEBUG, self, "push rejected, queue is closed")
+            }
+            // A held slot is reported by `try_force_push`, since `force_push` retries it.
+            _ => {}
+        }
+
+        result
+    }
+
+    /// Attempts to pop an item from the queue, along with the number of items that were
+    /// overwritten right before it.
+    ///
+    /// The count is the number of items displaced by [`force_push`](Self::force_push), including
+    /// through [`OverflowPolicy::DropOldest`], between the popped item and the item before it. It
+    /// is tied to the position of the popped item, so displacements that happen while popping are
+    /// reported with a later item, and with several consumers each one learns about the items lost
+    /// right before the ones it pops. Items taken out by other methods, like [`pop`](Self::pop) or
+    /// [`retain`](Self::retain), are not counted, but items lost before an item popped by another
+    /// method are not reported again.
+    ///
+    /// Unbounded queues never displace items, so the count is always zero for them.
+    ///
+    /// If the queue is empty, an error is returned.
+    ///
+    /// This method is only available with the `seq` feature, since it relies on 64-bit atomics
+    /// like the sequence numbers.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::ConcurrentQueue;
+    /// use concurrent_queue::{ConcurrentQueue, PopError};
     ///
-    /// let q = ConcurrentQueue::bounded(5);
-    /// q.push(1).unwrap();
-    /// q.push(2).unwrap();
-    /// q.push(3).unwrap();
+    /// let q = ConcurrentQueue::bounded(2);
     ///
-    /// let mut iter = q.try_iter();
-    /// assert_eq!(iter.by_ref().sum::<i32>(), 6);
-    /// assert_eq!(iter.next(), None);
+    /// for i in 1..=5 {
+    ///     q.force_push(i).unwrap();
+    /// }
     ///
-    /// // Pushing more items will make them available to the iterator.
-    /// q.push(4).unwrap();
-    /// assert_eq!(iter.next(), Some(4));
-    /// assert_eq!(iter.next(), None);
+    /// // Three items were displaced before we got to pop.
+    /// assert_eq!(q.pop_with_loss(), Ok((4, 3)));
+    /// assert_eq!(q.pop_with_loss(), Ok((5, 0)));
+    /// assert_eq!(q.pop_with_loss(), Err(PopError::Empty));
     /// ```
-    pub fn try_iter(&self) -> TryIter<'_, T> {
-        TryIter { queue: self }
+    #[cfg(feature = "seq")]
+    pub fn pop_with_loss(&self) -> Result<(T, u64), PopError> {
+        match &self.inner {
+            Inner::Single(q) => q.pop_with_loss(),
+            Inner::Bounded(q) => q.pop_with_loss(),
+            Inner::Unbounded(q) => q.pop().map(|value| (value, 0)),
+            Inner::Scq(q) => q.pop_with_loss(),
+        }
     }
 
-    /// Returns `true` if the queue is empty.
+    /// Attempts to push an item into the queue, returning its sequence number.
+    ///
+    /// Every slot handed out at the tail of the queue is numbered, starting from zero, in the order
+    /// the slots are handed out. The numbers are 64 bits wide on every target, so they never wrap
+    /// in practice. Items displaced by [`force_push`](Self::force_push) and reservations dropped
+    /// without being published still use up a number, which shows up as a gap on the consumer
+    /// side.
+    ///
+    /// Unlike [`push`](Self::push), this does not apply the [`OverflowPolicy`]: if the queue is
+    /// full or closed, the item is returned back as an error.
+    ///
+    /// This method is only available with the `seq` feature.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::ConcurrentQueue;
+    /// use concurrent_queue::{ConcurrentQueue, PushError};
     ///
-    /// let q = ConcurrentQueue::<i32>::unbounded();
+    /// let q = ConcurrentQueue::bounded(2);
     ///
-    /// assert!(q.is_empty());
-    /// q.push(1).unwrap();
-    /// assert!(!q.is_empty());
+    /// assert_eq!(q.push_seq('a'), Ok(0));
+    /// assert_eq!(q.push_seq('b'), Ok(1));
+    /// assert_eq!(q.push_seq('c'), Err(PushError::Full('c')));
+    ///
+    /// assert_eq!(q.pop_seq(), Ok((0, 'a')));
+    /// assert_eq!(q.push_seq('c'), Ok(2));
     /// ```
-    pub fn is_empty(&self) -> bool {
-        match &self.0 {
-            Inner::Single(q) => q.is_empty(),
-            Inner::Bounded(q) => q.is_empty(),
-            Inner::Unbounded(q) => q.is_empty(),
+    #[cfg(feature = "seq")]
+    pub fn push_seq(&self, value: T) -> Result<u64, PushError<T>> {
+        match self.reserve() {
+            Ok(slot) => Ok(slot.write_seq(value)),
+            Err(PushError::Full(())) => Err(PushError::Full(value)),
+            Err(PushError::Closed(())) => Err(PushError::Closed(value)),
         }
     }
 
-    /// Returns `true` if the queue is full.
+    /// Attempts to pop an item from the queue, along with its sequence number.
     ///
-    /// An unbounded queue is never full.
+    /// The sequence number is the one the item was given when it was pushed, as returned by
+    /// [`push_seq`](Self::push_seq). Items are numbered no matter how they were pushed.
+    ///
+    /// If the queue is empty, an error is returned.
+    ///
+    /// This method is only available with the `seq` feature.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::ConcurrentQueue;
+    /// use concurrent_queue::{ConcurrentQueue, PopError};
     ///
-    /// let q = ConcurrentQueue::bounded(1);
+    /// let q = ConcurrentQueue::bounded(2);
     ///
-    /// assert!(!q.is_full());
-    /// q.push(1).unwrap();
-    /// assert!(q.is_full());
+    /// for i in 0..4 {
+    ///     q.force_push(i).unwrap();
+    /// }
+    ///
+    /// // The first two items were displaced, leaving a gap.
+    /// assert_eq!(q.pop_seq(), Ok((2, 2)));
+    /// assert_eq!(q.pop_seq(), Ok((3, 3)));
+    /// assert_eq!(q.pop_seq(), Err(PopError::Empty));
     /// ```
-    pub fn is_full(&self) -> bool {
-        match &self.0 {
-            Inner::Single(q) => q.is_full(),
-            Inner::Bounded(q) => q.is_full(),
-            Inner::Unbounded(q) => q.is_full(),
+    #[cfg(feature = "seq")]
+    pub fn pop_seq(&self) -> Result<(u64, T), PopError> {
+        match &self.inner {
+            Inner::Single(q) => q.pop_seq(),
+            Inner::Bounded(q) => q.pop_seq(),
+            Inner::Unbounded(q) => q.pop_seq(),
+            Inner::Scq(q) => q.pop_seq(),
         }
     }
 
-    /// Returns the number of items in the queue.
+    /// Reserves a slot at the tail of the queue so that an item can be written into it in place.
+    ///
+    /// The returned [`WriteSlot`] exposes the uninitialized slot inside the queue. The item
+    /// becomes visible to consumers once it is published with [`WriteSlot::write`] or
+    /// [`WriteSlot::commit`]. If the slot is dropped without being published, consumers will skip
+    /// over it.
+    ///
+    /// Consumers that reach a reserved slot wait until it is published or dropped, so the slot
+    /// should be held only briefly. In a queue created by
+    /// [`unbounded_nonblocking`](Self::unbounded_nonblocking), they skip past it instead.
+    ///
+    /// If the queue is full or closed, an error is returned.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::ConcurrentQueue;
+    /// use concurrent_queue::{ConcurrentQueue, PushError};
     ///
-    /// let q = ConcurrentQueue::unbounded();
-    /// assert_eq!(q.len(), 0);
+    /// let q = ConcurrentQueue::bounded(1);
     ///
-    /// assert_eq!(q.push(10), Ok(()));
-    /// assert_eq!(q.len(), 1);
+    /// // Write an item directly into the queue.
+    /// let mut slot = q.reserve().unwrap();
+    /// slot.value_mut().write([1u8; 64]);
+    /// unsafe { slot.commit() };
     ///
-    /// assert_eq!(q.push(20), Ok(()));
-    /// assert_eq!(q.len(), 2);
+    /// // The queue is now full.
+    /// assert_eq!(q.reserve().err(), Some(PushError::Full(())));
+    /// assert_eq!(q.pop(), Ok([1u8; 64]));
+    ///
+    /// // Dropping a reserved slot leaves nothing behind.
+    /// drop(q.reserve().unwrap());
+    /// assert!(q.pop().is_err());
     /// ```
-    pub fn len(&self) -> usize {
-        match &self.0 {
-            Inner::Single(q) => q.len(),
-            Inner::Bounded(q) => q.len(),
-            Inner::Unbounded(q) => q.len(),
-        }
+    pub fn reserve(&self) -> Result<WriteSlot<'_, T>, PushError<()>> {
+        let inner = match &self.inner {
+            Inner::Single(q) => q.reserve().map(WriteSlotInner::Single),
+            Inner::Bounded(q) => q.reserve().map(WriteSlotInner::Bounded),
+            Inner::Unbounded(q) => q.reserve().map(WriteSlotInner::Unbounded),
+            Inner::Scq(q) => q.reserve().map(WriteSlotInner::Scq),
+        };
+
+        self.rejected(inner).map(WriteSlot)
     }
 
-    /// Returns the capacity of the queue.
+    /// Attempts to pop an item from the queue.
     ///
-    /// Unbounded queues have infinite capacity, represented as [`None`].
+    /// If the queue is empty, an error is returned.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::ConcurrentQueue;
+    /// use concurrent_queue::{ConcurrentQueue, PopError};
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    ///
+    /// // Pop errors when the queue is empty.
+    /// assert_eq!(q.pop(), Err(PopError::Empty));
+    ///
+    /// // Push one item and close the queue.
+    /// assert_eq!(q.push(10), Ok(()));
+    /// q.close();
+    ///
+    /// // Remaining items can be popped.
+    /// assert_eq!(q.pop(), Ok(10));
+    ///
+    /// // Again, pop errors when the queue is empty,
+    /// // but now also indicates that the queue is closed.
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        match &self.inner {
+            Inner::Single(q) => q.pop(),
+            Inner::Bounded(q) => q.pop(),
+            Inner::Unbounded(q) => q.pop(),
+            Inner::Scq(q) => q.pop(),
+        }
+    }
+
+    /// Attempts to pop an item from the queue without moving it out of its slot.
+    ///
+    /// The returned [`ReadSlot`] dereferences to the item, which stays inside the queue until the
+    /// guard is dropped. The slot is only recycled once that happens, so producers that reach it
+    /// before then see it as occupied. In particular, [`push`](Self::push) reports the queue as
+    /// full, [`force_push`](Self::force_push) waits for the guard to be dropped and
+    /// [`try_force_push`](Self::try_force_push) returns [`TryPushError::Busy`].
+    ///
+    /// If the queue is empty, an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    /// q.push(vec![1, 2, 3]).unwrap();
+    ///
+    /// // Process the item in place.
+    /// let mut item = q.pop_ref().unwrap();
+    /// item.push(4);
+    /// assert_eq!(*item, [1, 2, 3, 4]);
+    ///
+    /// // The slot is still in use.
+    /// assert_eq!(q.push(vec![]), Err(PushError::Full(vec![])));
+    /// assert_eq!(q.pop(), Err(PopError::Empty));
+    ///
+    /// // Dropping the guard frees the slot.
+    /// drop(item);
+    /// assert_eq!(q.push(vec![]), Ok(()));
+    /// ```
+    pub fn pop_ref(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        let inner = match &self.inner {
+            Inner::Single(q) => ReadSlotInner::Single(q.pop_ref()?),
+            Inner::Bounded(q) => ReadSlotInner::Bounded(q.pop_ref()?),
+            Inner::Unbounded(q) => ReadSlotInner::Unbounded(q.pop_ref()?),
+            Inner::Scq(q) => ReadSlotInner::Scq(q.pop_ref()?),
+        };
+
+        Ok(ReadSlot(inner))
+    }
+
+    /// Attempts to push an item into the queue without waiting for other threads.
+    ///
+    /// A [`push`](Self::push) can busy-wait for other threads that are in the middle of an
+    /// operation, such as a producer installing a new block in an unbounded queue, or a consumer
+    /// that is in the middle of popping from a bounded queue. Where that would happen, this returns
+    /// [`TryPushError::Busy`] instead, which makes it safe to call from contexts that can't
+    /// afford to spin, like signal handlers.
+    ///
+    /// Unlike [`push`](Self::push), this does not apply the [`OverflowPolicy`]: if the queue is
+    /// full or closed, the item is returned back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, TryPushError};
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    ///
+    /// assert_eq!(q.try_push_nowait(1), Ok(()));
+    /// assert_eq!(q.try_push_nowait(2), Err(TryPushError::Full(2)));
+    ///
+    /// // The only slot is locked while it is being inspected.
+    /// q.retain(|_| {
+    ///     assert_eq!(q.try_push_nowait(2), Err(TryPushError::Busy(2)));
+    ///     false
+    /// });
+    ///
+    /// assert_eq!(q.try_push_nowait(2), Ok(()));
+    /// ```
+    pub fn try_push_nowait(&self, value: T) -> Result<(), TryPushError<T>> {
+        let slot = match &self.inner {
+            Inner::Single(q) => q.reserve_nowait().map(WriteSlotInner::Single),
+            Inner::Bounded(q) => q.reserve_nowait().map(WriteSlotInner::Bounded),
+            Inner::Unbounded(q) => q.reserve_nowait().map(WriteSlotInner::Unbounded),
+            Inner::Scq(q) => q.reserve_nowait().map(WriteSlotInner::Scq),
+        };
+
+        match slot {
+            Ok(slot) => {
+                WriteSlot(slot).write(value);
+                Ok(())
+            }
+            Err(TryPushError::Full(())) => Err(TryPushError::Full(value)),
+            Err(TryPushError::Closed(())) => Err(TryPushError::Closed(value)),
+            Err(TryPushError::Busy(())) => {
+                trace_event!(DEBUG, self, "push would have waited for another thread");
+                Err(TryPushError::Busy(value))
+            }
+        }
+    }
+
+    /// Attempts to pop an item from the queue without waiting for other threads.
+    ///
+    /// A [`pop`](Self::pop) can busy-wait for other threads that are in the middle of an
+    /// operation, such as a producer that is still writing the item at the head of the queue.
+    /// Where that would happen, this returns [`TryPopError::Busy`] instead, which makes it safe
+    /// to call from contexts that can't afford to spin, like signal handlers.
+    ///
+    /// In a queue created by [`unbounded_nonblocking`](Self::unbounded_nonblocking), an item that
+    /// is still being written is skipped instead, as [`pop`](Self::pop) does in that queue. In a
+    /// queue created by [`bounded_faa`](Self::bounded_faa), a pop can still wait briefly if an
+    /// inspection like [`retain`](Self::retain) starts at the same moment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, TryPopError};
+    ///
+    /// let q = ConcurrentQueue::bounded(2);
+    /// assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+    ///
+    /// // A push is in progress.
+    /// let slot = q.reserve().unwrap();
+    /// assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+    ///
+    /// slot.write(1);
+    /// assert_eq!(q.try_pop_nowait(), Ok(1));
+    /// ```
+    pub fn try_pop_nowait(&self) -> Result<T, TryPopError> {
+        let result = match &self.inner {
+            Inner::Single(q) => q.pop_nowait(),
+            Inner::Bounded(q) => q.pop_nowait(),
+            Inner::Unbounded(q) => q.pop_nowait(),
+            Inner::Scq(q) => q.pop_nowait(),
+        };
+
+        if let Err(TryPopError::Busy) = result {
+            trace_event!(DEBUG, self, "pop would have waited for another thread");
+        }
+        result
+    }
+
+    /// Get an iterator over the items in the queue.
+    ///
+    /// The iterator will continue until the queue is empty or closed. It will never block;
+    /// if the queue is empty, the iterator will return `None`. If new items are pushed into
+    /// the queue, the iterator may return `Some` in the future after returning `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(5);
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap();
+    /// q.push(3).unwrap();
+    ///
+    /// let mut iter = q.try_iter();
+    /// assert_eq!(iter.by_ref().sum::<i32>(), 6);
+    /// assert_eq!(iter.next(), None);
+    ///
+    /// // Pushing more items will make them available to the iterator.
+    /// q.push(4).unwrap();
+    /// assert_eq!(iter.next(), Some(4));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { queue: self }
+    }
+
+    /// Returns a view that formats the items in the queue without popping them.
+    ///
+    /// This is meant for debugging. The items are read while they are formatted, which makes
+    /// consumers wait until formatting is done. Producers are not held up, so items pushed while
+    /// the view is being formatted may or may not show up, and items that are still being written
+    /// are left out.
+    ///
+    /// Because consumers are held back while the items are formatted, their `Debug`
+    /// implementation must not pop from this queue, or inspect it again through this view,
+    /// [`snapshot`](Self::snapshot) or [`retain`](Self::retain): those wait until formatting is
+    /// done, which never happens. It can still push into the queue, and
+    /// [`try_pop_nowait`](Self::try_pop_nowait) reports [`TryPopError::Busy`] instead of waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap();
+    ///
+    /// assert_eq!(format!("{:?}", q.debug_items()), "[1, 2]");
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn debug_items(&self) -> DebugItems<'_, T>
+    where
+        T: fmt::Debug,
+    {
+        DebugItems { queue: self }
+    }
+
+    /// Returns clones of the items in the queue, from the head to the tail, without popping them.
+    ///
+    /// Consumers wait while the items are cloned, so the snapshot never contains an item that was
+    /// popped before it was taken, and every item that was in the queue when it was taken and
+    /// finished being pushed is in it. Producers are not held up, and items that are still being
+    /// written are left out.
+    ///
+    /// The items are cloned before consumers are let through again, so `T::clone` must not wait
+    /// for a consumer of this queue, such as by popping from it or taking another snapshot of it.
+    /// Doing so deadlocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(3);
+    /// q.push(String::from("a")).unwrap();
+    /// q.push(String::from("b")).unwrap();
+    ///
+    /// assert_eq!(q.snapshot(), ["a", "b"]);
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.with_items(|items| items.iter().map(|&item| item.clone()).collect())
+    }
+
+    /// Removes the items for which `f` returns `false`, keeping the order of the others.
+    ///
+    /// Consumers wait while the items are checked, so no item is popped in the meantime. Producers
+    /// are not held up, and items that are still being written are kept.
+    ///
+    /// Since `f` runs while consumers are held back, it must not pop from this queue or inspect it
+    /// with another method that holds them back, which would deadlock. Pushing into the queue is
+    /// fine, and [`try_pop_nowait`](Self::try_pop_nowait) reports [`TryPopError::Busy`]. The
+    /// removed items are dropped after consumers are let through again.
+    ///
+    /// The slots of removed items are freed once consumers reach them, so they still count
+    /// towards [`len`](Self::len) and the capacity of a bounded queue until then.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// for i in 1..=6 {
+    ///     q.push(i).unwrap();
+    /// }
+    ///
+    /// q.retain(|&i| i % 3 != 0);
+    /// assert_eq!(q.try_iter().collect::<Vec<_>>(), [1, 2, 4, 5]);
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&self, mut f: F) {
+        drop(self.remove_if(|item| !f(item)));
+    }
+
+    /// Removes the items for which `f` returns `true` and returns them in order.
+    ///
+    /// This works like [`retain`](Self::retain) with the opposite condition, except that the
+    /// removed items are handed back instead of being dropped. The same restrictions apply to `f`,
+    /// which runs while consumers are held back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(4);
+    /// for job in ["a:1", "b:1", "a:2", "b:2"] {
+    ///     q.push(job).unwrap();
+    /// }
+    ///
+    /// assert_eq!(q.remove_if(|job| job.starts_with("a:")), ["a:1", "a:2"]);
+    /// assert_eq!(q.pop(), Ok("b:1"));
+    /// assert_eq!(q.pop(), Ok("b:2"));
+    /// ```
+    pub fn remove_if<F: FnMut(&T) -> bool>(&self, f: F) -> Vec<T> {
+        match &self.inner {
+            Inner::Single(q) => q.remove_if(f),
+            Inner::Bounded(q) => q.remove_if(f),
+            Inner::Unbounded(q) => q.remove_if(f),
+            Inner::Scq(q) => q.remove_if(f),
+        }
+    }
+
+    /// Moves up to `max` items from the head of this queue to the tail of `dest`.
+    ///
+    /// At most half of the items in this queue, rounded up, are moved, so that its other consumers
+    /// are left with the rest. This is how a worker with an empty local queue takes work from a
+    /// busy one.
+    ///
+    /// Room for the items is reserved at the tail of `dest` in one step, and then the items are
+    /// claimed from the head of this queue in another. Only as many items are moved as fit, so
+    /// none are lost if `dest` is full or closed. Items that are still being written are not
+    /// waited for. If fewer items could be claimed than there was room for, the leftover slots
+    /// in `dest` are skipped by its consumers.
+    ///
+    /// Returns the number of items moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let busy = ConcurrentQueue::unbounded();
+    /// for i in 0..8 {
+    ///     busy.push(i).unwrap();
+    /// }
+    ///
+    /// // Half of the items are moved, oldest first.
+    /// let idle = ConcurrentQueue::bounded(16);
+    /// assert_eq!(busy.steal_into(&idle, 100), 4);
+    /// assert_eq!(idle.try_iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    ///
+    /// // No more items are moved than fit.
+    /// let small = ConcurrentQueue::bounded(1);
+    /// assert_eq!(busy.steal_into(&small, 100), 1);
+    /// assert_eq!(small.pop(), Ok(4));
+    /// assert_eq!(busy.len(), 3);
+    /// ```
+    pub fn steal_into(&self, dest: &ConcurrentQueue<T>, max: usize) -> usize {
+        let count = max.min((self.len() + 1) / 2);
+        if count == 0 {
+            return 0;
+        }
+
+        let slots = match dest.rejected(dest.reserve_batch(count)) {
+            Ok(slots) => slots,
+            Err(_) => return 0,
+        };
+
+        // Slots that are left over are dropped along with the iterator, which releases them.
+        let mut slots = slots.into_iter();
+        self.pop_batch(slots.len(), |item| slots.next().unwrap().write(item))
+    }
+
+    /// Calls `f` with the items in the queue, from the head to the tail, without popping them.
+    fn with_items<R, F: FnOnce(&[&T]) -> R>(&self, f: F) -> R {
+        match &self.inner {
+            Inner::Single(q) => q.with_items(f),
+            Inner::Bounded(q) => q.with_items(f),
+            Inner::Unbounded(q) => q.with_items(f),
+            Inner::Scq(q) => q.with_items(f),
+        }
+    }
+
+    /// Reserves up to `max` consecutive slots at the tail of the queue.
+    fn reserve_batch(&self, max: usize) -> Result<Vec<WriteSlot<'_, T>>, PushError<()>> {
+        fn wrap<'a, T, S>(
+            slots: Vec<S>,
+            f: fn(S) -> WriteSlotInner<'a, T>,
+        ) -> Vec<WriteSlot<'a, T>> {
+            slots.into_iter().map(|slot| WriteSlot(f(slot))).collect()
+        }
+
+        match &self.inner {
+            Inner::Single(q) => q
+                .reserve_batch(max)
+                .map(|slots| wrap(slots, WriteSlotInner::Single)),
+            Inner::Bounded(q) => q
+                .reserve_batch(max)
+                .map(|slots| wrap(slots, WriteSlotInner::Bounded)),
+            Inner::Unbounded(q) => q
+                .reserve_batch(max)
+                .map(|slots| wrap(slots, WriteSlotInner::Unbounded)),
+            Inner::Scq(q) => q
+                .reserve_batch(max)
+                .map(|slots| wrap(slots, WriteSlotInner::Scq)),
+        }
+    }
+
+    /// Pops up to `max` items from the head of the queue into `f` without waiting for any.
+    fn pop_batch<F: FnMut(T)>(&self, max: usize, f: F) -> usize {
+        match &self.inner {
+            Inner::Single(q) => q.pop_batch(max, f),
+            Inner::Bounded(q) => q.pop_batch(max, f),
+            Inner::Unbounded(q) => q.pop_batch(max, f),
+            Inner::Scq(q) => q.pop_batch(max, f),
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::<i32>::unbounded();
+    ///
+    /// assert!(q.is_empty());
+    /// q.push(1).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        match &self.inner {
+            Inner::Single(q) => q.is_empty(),
+            Inner::Bounded(q) => q.is_empty(),
+            Inner::Unbounded(q) => q.is_empty(),
+            Inner::Scq(q) => q.is_empty(),
+        }
+    }
+
+    /// Returns `true` if the queue is full.
+    ///
+    /// An unbounded queue is never full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    ///
+    /// assert!(!q.is_full());
+    /// q.push(1).unwrap();
+    /// assert!(q.is_full());
+    /// ```
+    pub fn is_full(&self) -> bool {
+        match &self.inner {
+            Inner::Single(q) => q.is_full(),
+            Inner::Bounded(q) => q.is_full(),
+            Inner::Unbounded(q) => q.is_full(),
+            Inner::Scq(q) => q.is_full(),
+        }
+    }
+
+    /// Returns the number of items in the queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// assert_eq!(q.len(), 0);
+    ///
+    /// assert_eq!(q.push(10), Ok(()));
+    /// assert_eq!(q.len(), 1);
+    ///
+    /// assert_eq!(q.push(20), Ok(()));
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        match &self.inner {
+            Inner::Single(q) => q.len(),
+            Inner::Bounded(q) => q.len(),
+            Inner::Unbounded(q) => q.len(),
+            Inner::Scq(q) => q.len(),
+        }
+    }
+
+    /// Returns the capacity of the queue.
+    ///
+    /// Unbounded queues have infinite capacity, represented as [`None`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
     ///
     /// let q = ConcurrentQueue::<i32>::bounded(7);
     /// assert_eq!(q.capacity(), Some(7));
     ///
-    /// let q = ConcurrentQueue::<i32>::unbounded();
-    /// assert_eq!(q.capacity(), None);
+    /// let q = ConcurrentQueue::<i32>::unbounded();
+    /// assert_eq!(q.capacity(), None);
+    /// ```
+    pub fn capacity(&self) -> Option<usize> {
+        match &self.inner {
+            Inner::Single(_) => Some(1),
+            Inner::Bounded(q) => Some(q.capacity()),
+            Inner::Unbounded(_) => None,
+            Inner::Scq(q) => Some(q.capacity()),
+        }
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// When a queue is closed, no more items can be pushed but the remaining items can still be
+    /// popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// assert_eq!(q.push(10), Ok(()));
+    ///
+    /// assert!(q.close());  // `true` because this call closes the queue.
+    /// assert!(!q.close()); // `false` because the queue is already closed.
+    ///
+    /// // Cannot push any more items when closed.
+    /// assert_eq!(q.push(20), Err(PushError::Closed(20)));
+    ///
+    /// // Remaining items can still be popped.
+    /// assert_eq!(q.pop(), Ok(10));
+    ///
+    /// // When no more items are present, the error is `Closed`.
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn close(&self) -> bool {
+        let closed = match &self.inner {
+            Inner::Single(q) => q.close(),
+            Inner::Bounded(q) => q.close(),
+            Inner::Unbounded(q) => q.close(),
+            Inner::Scq(q) => q.close(),
+        };
+
+        if closed {
+            trace_event!(DEBUG, self, "closed the queue");
+        }
+        closed
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::<i32>::unbounded();
+    ///
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        match &self.inner {
+            Inner::Single(q) => q.is_closed(),
+            Inner::Bounded(q) => q.is_closed(),
+            Inner::Unbounded(q) => q.is_closed(),
+            Inner::Scq(q) => q.is_closed(),
+        }
+    }
+
+    /// Returns the highest number of items the queue has held.
+    ///
+    /// The mark is updated by producers right after each push, and covers the lifetime of the
+    /// queue or the time since the last call to
+    /// [`reset_high_water_mark`](Self::reset_high_water_mark). Because producers don't stop
+    /// consumers while measuring, it is an approximation when pushes and pops run concurrently.
+    ///
+    /// This method is only available with the `metrics` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// assert_eq!(q.high_water_mark(), 0);
+    ///
+    /// for i in 0..10 {
+    ///     q.push(i).unwrap();
+    /// }
+    /// while q.pop().is_ok() {}
+    ///
+    /// assert_eq!(q.high_water_mark(), 10);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn high_water_mark(&self) -> usize {
+        self.metrics().high_water_mark()
+    }
+
+    /// Resets the high-water mark to the current number of items in the queue.
+    ///
+    /// This method is only available with the `metrics` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(10);
+    /// for i in 0..5 {
+    ///     q.push(i).unwrap();
+    /// }
+    /// q.pop().unwrap();
+    ///
+    /// q.reset_high_water_mark();
+    /// assert_eq!(q.high_water_mark(), 4);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn reset_high_water_mark(&self) {
+        self.metrics().reset_high_water_mark(self.len());
+    }
+
+    /// Returns a snapshot of the operation counters of the queue.
+    ///
+    /// This method is only available with the `metrics` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap_err();
+    /// q.force_push(3).unwrap();
+    /// q.pop().unwrap();
+    ///
+    /// let stats = q.stats();
+    /// assert_eq!(stats.pushes, 2);
+    /// assert_eq!(stats.pops, 1);
+    /// assert_eq!(stats.full, 1);
+    /// assert_eq!(stats.displaced, 1);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> Stats {
+        self.metrics().stats()
+    }
+
+    /// Returns a snapshot of how long popped items spent in the queue.
+    ///
+    /// This method is only available with the `latency` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    /// use std::time::Duration;
+    ///
+    /// let q = ConcurrentQueue::bounded(10);
+    /// q.push(1).unwrap();
+    /// std::thread::sleep(Duration::from_millis(1));
+    /// q.pop().unwrap();
+    ///
+    /// let stats = q.latency_stats();
+    /// assert_eq!(stats.count, 1);
+    /// assert!(stats.max >= Duration::from_millis(1));
+    /// ```
+    #[cfg(feature = "latency")]
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.metrics().latency_stats()
+    }
+
+    /// Returns how long the oldest item in the queue has been waiting to be popped.
+    ///
+    /// Items that are still being written are not in the queue yet, and items that were removed
+    /// by [`retain`](Self::retain) are gone, so both are passed over, along with abandoned
+    /// reservations. Returns `None` if no other items are left.
+    /// The age is read without stopping consumers, so if the item is popped in the meantime, the
+    /// age of a newer item may be returned. For unbounded queues, consumers briefly wait while
+    /// the timestamp is read, because the block holding the item could be freed otherwise.
+    ///
+    /// This method is only available with the `latency` feature.
+    ///
+    /// # Examples
+    ///
     /// ```
-    pub fn capacity(&self) -> Option<usize> {
-        match &self.0 {
-            Inner::Single(_) => Some(1),
-            Inner::Bounded(q) => Some(q.capacity()),
-            Inner::Unbounded(_) => None,
+    /// use concurrent_queue::ConcurrentQueue;
+    /// use std::time::Duration;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// assert_eq!(q.oldest_item_age(), None);
+    ///
+    /// q.push(1).unwrap();
+    /// std::thread::sleep(Duration::from_millis(1));
+    /// q.push(2).unwrap();
+    /// assert!(q.oldest_item_age().unwrap() >= Duration::from_millis(1));
+    /// ```
+    #[cfg(feature = "latency")]
+    pub fn oldest_item_age(&self) -> Option<Duration> {
+        match &self.inner {
+            Inner::Single(q) => q.oldest_item_age(),
+            Inner::Bounded(q) => q.oldest_item_age(),
+            Inner::Unbounded(q) => q.oldest_item_age(),
+            Inner::Scq(q) => q.oldest_item_age(),
         }
     }
+}
 
-    /// Closes the queue.
+impl<T> Instrumented for ConcurrentQueue<T> {
+    fn metrics(&self) -> &Metrics {
+        match &self.inner {
+            Inner::Single(q) => q.metrics(),
+            Inner::Bounded(q) => q.metrics(),
+            Inner::Unbounded(q) => q.metrics(),
+            Inner::Scq(q) => q.metrics(),
+        }
+    }
+
+    fn metrics_mut(&mut self) -> &mut Metrics {
+        match &mut self.inner {
+            Inner::Single(q) => q.metrics_mut(),
+            Inner::Bounded(q) => q.metrics_mut(),
+            Inner::Unbounded(q) => q.metrics_mut(),
+            Inner::Scq(q) => q.metrics_mut(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.capacity()
+    }
+}
+
+impl<T> fmt::Debug for ConcurrentQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentQueue")
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// A slot reserved in a [`ConcurrentQueue`] by [`ConcurrentQueue::reserve()`].
+///
+/// The item is published to consumers by [`write`](Self::write) or [`commit`](Self::commit).
+/// If this is dropped without being published, the slot is released and consumers skip over it.
+pub struct WriteSlot<'a, T>(WriteSlotInner<'a, T>);
+
+enum WriteSlotInner<'a, T> {
+    Single(single::WriteSlot<'a, T>),
+    Bounded(bounded::WriteSlot<'a, T>),
+    Unbounded(unbounded::WriteSlot<'a, T>),
+    Scq(scq::WriteSlot<'a, T>),
+}
+
+impl<T> WriteSlot<'_, T> {
+    /// Returns the uninitialized item in the slot.
     ///
-    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    /// # Examples
     ///
-    /// When a queue is closed, no more items can be pushed but the remaining items can still be
-    /// popped.
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    ///
+    /// let mut slot = q.reserve().unwrap();
+    /// slot.value_mut().write(String::from("hello"));
+    /// unsafe { slot.commit() };
+    ///
+    /// assert_eq!(q.pop().as_deref(), Ok("hello"));
+    /// ```
+    pub fn value_mut(&mut self) -> &mut MaybeUninit<T> {
+        match &mut self.0 {
+            WriteSlotInner::Single(slot) => slot.value_mut(),
+            WriteSlotInner::Bounded(slot) => slot.value_mut(),
+            WriteSlotInner::Unbounded(slot) => slot.value_mut(),
+            WriteSlotInner::Scq(slot) => slot.value_mut(),
+        }
+    }
+
+    /// Returns the sequence number of the slot.
+    ///
+    /// See [`ConcurrentQueue::push_seq()`] for how items are numbered. This method is only
+    /// available with the `seq` feature.
     ///
     /// # Examples
     ///
     /// ```
-    /// use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+    /// use concurrent_queue::ConcurrentQueue;
     ///
     /// let q = ConcurrentQueue::unbounded();
-    /// assert_eq!(q.push(10), Ok(()));
+    /// q.push(1).unwrap();
     ///
-    /// assert!(q.close());  // `true` because this call closes the queue.
-    /// assert!(!q.close()); // `false` because the queue is already closed.
+    /// let slot = q.reserve().unwrap();
+    /// assert_eq!(slot.seq(), 1);
+    /// slot.write(2);
+    /// ```
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        match &self.0 {
+            WriteSlotInner::Single(slot) => slot.seq(),
+            WriteSlotInner::Bounded(slot) => slot.seq(),
+            WriteSlotInner::Unbounded(slot) => slot.seq(),
+            WriteSlotInner::Scq(slot) => slot.seq(),
+        }
+    }
+
+    /// Writes an item into the slot and publishes it.
     ///
-    /// // Cannot push any more items when closed.
-    /// assert_eq!(q.push(20), Err(PushError::Closed(20)));
+    /// # Examples
     ///
-    /// // Remaining items can still be popped.
-    /// assert_eq!(q.pop(), Ok(10));
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
     ///
-    /// // When no more items are present, the error is `Closed`.
-    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// let q = ConcurrentQueue::bounded(2);
+    ///
+    /// q.reserve().unwrap().write(1);
+    /// assert_eq!(q.pop(), Ok(1));
     /// ```
-    pub fn close(&self) -> bool {
+    pub fn write(self, value: T) {
+        match self.0 {
+            WriteSlotInner::Single(slot) => slot.write(value),
+            WriteSlotInner::Bounded(slot) => slot.write(value),
+            WriteSlotInner::Unbounded(slot) => slot.write(value),
+            WriteSlotInner::Scq(slot) => slot.write(value),
+        }
+    }
+
+    /// Writes an item into the slot and publishes it, returning the sequence number it ended up
+    /// with.
+    #[cfg(feature = "seq")]
+    fn write_seq(self, value: T) -> u64 {
+        match self.0 {
+            // The item is moved to another slot if a consumer gave up waiting for it.
+            WriteSlotInner::Unbounded(slot) => slot.write_seq(value),
+            inner => {
+                let slot = WriteSlot(inner);
+                let seq = slot.seq();
+                slot.write(value);
+                seq
+            }
+        }
+    }
+
+    /// Publishes the item in the slot.
+    ///
+    /// # Safety
+    ///
+    /// The item must have been initialized through [`value_mut`](Self::value_mut).
+    pub unsafe fn commit(self) {
+        match self.0 {
+            WriteSlotInner::Single(slot) => slot.commit(),
+            WriteSlotInner::Bounded(slot) => slot.commit(),
+            WriteSlotInner::Unbounded(slot) => slot.commit(),
+            WriteSlotInner::Scq(slot) => slot.commit(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for WriteSlot<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteSlot").finish_non_exhaustive()
+    }
+}
+
+/// An item popped from a [`ConcurrentQueue`] by [`ConcurrentQueue::pop_ref()`].
+///
+/// The item stays in its slot until this is dropped, at which point it is dropped and the slot is
+/// recycled.
+pub struct ReadSlot<'a, T>(ReadSlotInner<'a, T>);
+
+enum ReadSlotInner<'a, T> {
+    Single(single::ReadSlot<'a, T>),
+    Bounded(bounded::ReadSlot<'a, T>),
+    Unbounded(unbounded::ReadSlot<'a, T>),
+    Scq(scq::ReadSlot<'a, T>),
+}
+
+impl<T> ReadSlot<'_, T> {
+    /// Returns the sequence number the item was pushed with.
+    ///
+    /// See [`ConcurrentQueue::push_seq()`] for how items are numbered. This method is only
+    /// available with the `seq` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::bounded(1);
+    /// q.push('a').unwrap();
+    /// q.pop().unwrap();
+    /// q.push('b').unwrap();
+    ///
+    /// let item = q.pop_ref().unwrap();
+    /// assert_eq!(item.seq(), 1);
+    /// assert_eq!(*item, 'b');
+    /// ```
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
         match &self.0 {
-            Inner::Single(q) => q.close(),
-            Inner::Bounded(q) => q.close(),
-            Inner::Unbounded(q) => q.close(),
+            ReadSlotInner::Single(slot) => slot.seq(),
+            ReadSlotInner::Bounded(slot) => slot.seq(),
+            ReadSlotInner::Unbounded(slot) => slot.seq(),
+            ReadSlotInner::Scq(slot) => slot.seq(),
         }
     }
 
-    /// Returns `true` if the queue is closed.
+    /// Moves the item out of the slot and recycles it.
     ///
     /// # Examples
     ///
     /// ```
     /// use concurrent_queue::ConcurrentQueue;
     ///
-    /// let q = ConcurrentQueue::<i32>::unbounded();
+    /// let q = ConcurrentQueue::unbounded();
+    /// q.push(1).unwrap();
     ///
-    /// assert!(!q.is_closed());
-    /// q.close();
-    /// assert!(q.is_closed());
+    /// let item = q.pop_ref().unwrap();
+    /// assert_eq!(item.into_inner(), 1);
     /// ```
-    pub fn is_closed(&self) -> bool {
+    pub fn into_inner(self) -> T {
+        match self.0 {
+            ReadSlotInner::Single(slot) => slot.into_inner(),
+            ReadSlotInner::Bounded(slot) => slot.into_inner(),
+            ReadSlotInner::Unbounded(slot) => slot.into_inner(),
+            ReadSlotInner::Scq(slot) => slot.into_inner(),
+        }
+    }
+}
+
+impl<T> Deref for ReadSlot<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
         match &self.0 {
-            Inner::Single(q) => q.is_closed(),
-            Inner::Bounded(q) => q.is_closed(),
-            Inner::Unbounded(q) => q.is_closed(),
+            ReadSlotInner::Single(slot) => slot.value(),
+            ReadSlotInner::Bounded(slot) => slot.value(),
+            ReadSlotInner::Unbounded(slot) => slot.value(),
+            ReadSlotInner::Scq(slot) => slot.value(),
         }
     }
 }
 
-impl<T> fmt::Debug for ConcurrentQueue<T> {
+impl<T> DerefMut for ReadSlot<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match &mut self.0 {
+            ReadSlotInner::Single(slot) => slot.value_mut(),
+            ReadSlotInner::Bounded(slot) => slot.value_mut(),
+            ReadSlotInner::Unbounded(slot) => slot.value_mut(),
+            ReadSlotInner::Scq(slot) => slot.value_mut(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ReadSlot<'_, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("ConcurrentQueue")
-            .field("len", &self.len())
-            .field("capacity", &self.capacity())
-            .field("is_closed", &self.is_closed())
-            .finish()
+        f.debug_tuple("ReadSlot").field(&**self).finish()
     }
 }
 
@@ -495,6 +1818,56 @@ impl<T> Iterator for TryIter<'_, T> {
     }
 }
 
+/// A view that formats the items in a [`ConcurrentQueue`] without popping them.
+///
+/// Returned by [`ConcurrentQueue::debug_items()`].
+#[derive(Clone)]
+pub struct DebugItems<'a, T> {
+    queue: &'a ConcurrentQueue<T>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for DebugItems<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.queue
+            .with_items(|items| f.debug_list().entries(items).finish())
+    }
+}
+
+/// What [`ConcurrentQueue::push()`] does when a bounded queue is full.
+///
+/// See [`ConcurrentQueue::bounded_with_policy()`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum OverflowPolicy {
+    /// The item is returned back as [`PushError::Full`].
+    ///
+    /// This is the default.
+    Reject,
+
+    /// The oldest item in the queue is displaced, as with [`ConcurrentQueue::force_push()`].
+    ///
+    /// If the slot the item would go into is held by a [`ReadSlot`] or a [`WriteSlot`], the item is
+    /// returned back as [`PushError::Full`] instead of waiting for the guard to be dropped.
+    DropOldest,
+
+    /// The item being pushed is dropped.
+    DropNewest,
+}
+
+/// The overflow policy of a queue that doesn't reject items when it is full.
+struct Overflow<T> {
+    /// What `push` does when the queue is full.
+    policy: OverflowPolicy,
+
+    /// Receives items dropped by the overflow policy.
+    evict: Option<Box<dyn Fn(T) + Send + Sync>>,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Reject
+    }
+}
+
 /// Error which occurs when popping from an empty queue.
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum PopError {
@@ -601,6 +1974,158 @@ impl<T> fmt::Display for PushError<T> {
     }
 }
 
+/// Error which occurs when [`ConcurrentQueue::try_pop_nowait()`] can't pop an item.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum TryPopError {
+    /// The queue is empty but not closed.
+    Empty,
+
+    /// The queue is empty and closed.
+    Closed,
+
+    /// Popping would have to wait for another thread.
+    Busy,
+}
+
+impl TryPopError {
+    /// Returns `true` if the queue is empty but not closed.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, TryPopError::Empty)
+    }
+
+    /// Returns `true` if the queue is empty and closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TryPopError::Closed)
+    }
+
+    /// Returns `true` if popping would have to wait for another thread.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, TryPopError::Busy)
+    }
+
+    /// Converts an error from an operation that waits instead of reporting `Busy`.
+    pub(crate) fn into_pop_error(self) -> PopError {
+        match self {
+            TryPopError::Empty => PopError::Empty,
+            TryPopError::Closed => PopError::Closed,
+            TryPopError::Busy => unreachable!("a waiting pop reported `Busy`"),
+        }
+    }
+}
+
+impl From<PopError> for TryPopError {
+    fn from(err: PopError) -> Self {
+        match err {
+            PopError::Empty => TryPopError::Empty,
+            PopError::Closed => TryPopError::Closed,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for TryPopError {}
+
+impl fmt::Debug for TryPopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPopError::Empty => write!(f, "Empty"),
+            TryPopError::Closed => write!(f, "Closed"),
+            TryPopError::Busy => write!(f, "Busy"),
+        }
+    }
+}
+
+impl fmt::Display for TryPopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPopError::Empty => write!(f, "Empty"),
+            TryPopError::Closed => write!(f, "Closed"),
+            TryPopError::Busy => write!(f, "Busy"),
+        }
+    }
+}
+
+/// Error which occurs when [`ConcurrentQueue::try_push_nowait()`] or
+/// [`ConcurrentQueue::try_force_push()`] can't push an item.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum TryPushError<T> {
+    /// The queue is full but not closed.
+    Full(T),
+
+    /// The queue is closed.
+    Closed(T),
+
+    /// Pushing would have to wait for another thread.
+    Busy(T),
+}
+
+impl<T> TryPushError<T> {
+    /// Unwraps the item that couldn't be pushed.
+    pub fn into_inner(self) -> T {
+        match self {
+            TryPushError::Full(t) => t,
+            TryPushError::Closed(t) => t,
+            TryPushError::Busy(t) => t,
+        }
+    }
+
+    /// Returns `true` if the queue is full but not closed.
+    pub fn is_full(&self) -> bool {
+        matches!(self, TryPushError::Full(_))
+    }
+
+    /// Returns `true` if the queue is closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TryPushError::Closed(_))
+    }
+
+    /// Returns `true` if pushing would have to wait for another thread.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, TryPushError::Busy(_))
+    }
+
+    /// Converts an error from an operation that waits instead of reporting `Busy`.
+    pub(crate) fn into_push_error(self) -> PushError<T> {
+        match self {
+            TryPushError::Full(t) => PushError::Full(t),
+            TryPushError::Closed(t) => PushError::Closed(t),
+            TryPushError::Busy(_) => unreachable!("a waiting push reported `Busy`"),
+        }
+    }
+}
+
+impl<T> From<PushError<T>> for TryPushError<T> {
+    fn from(err: PushError<T>) -> Self {
+        match err {
+            PushError::Full(t) => TryPushError::Full(t),
+            PushError::Closed(t) => TryPushError::Closed(t),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> error::Error for TryPushError<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for TryPushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPushError::Full(t) => f.debug_tuple("Full").field(t).finish(),
+            TryPushError::Closed(t) => f.debug_tuple("Closed").field(t).finish(),
+            TryPushError::Busy(t) => f.debug_tuple("Busy").field(t).finish(),
+        }
+    }
+}
+
+impl<T> fmt::Display for TryPushError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPushError::Full(_) => write!(f, "Full"),
+            TryPushError::Closed(_) => write!(f, "Closed"),
+            TryPushError::Busy(_) => write!(f, "Busy"),
+        }
+    }
+}
+
 /// Error that occurs when force-pushing into a full queue.
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ForcePushError<T>(pub T);
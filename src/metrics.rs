@@ -0,0 +1,493 @@
+//! Operation counters kept by the queues when the `metrics` feature is enabled, item timestamps
+//! kept when the `latency` feature is enabled, and events emitted when the `tracing` feature is
+//! enabled.
+
+use alloc::boxed::Box;
+#[cfg(feature = "latency")]
+use core::{cell::UnsafeCell, time::Duration};
+#[cfg(feature = "latency")]
+use std::{sync::Once, time::Instant};
+
+#[cfg(feature = "latency")]
+use crate::sync::atomic::AtomicU64;
+#[cfg(feature = "metrics")]
+use crate::sync::atomic::AtomicUsize;
+#[cfg(any(feature = "metrics", feature = "latency"))]
+use crate::sync::atomic::Ordering;
+
+/// The number of iterations after which a busy-wait loop is reported as long.
+#[cfg(feature = "tracing")]
+const LONG_SPIN: u32 = 1 << 10;
+
+/// The number of buckets in the occupancy histogram.
+#[cfg(feature = "metrics")]
+pub const OCCUPANCY_BUCKETS: usize = 16;
+
+/// The number of buckets in the latency histogram.
+#[cfg(feature = "latency")]
+pub const LATENCY_BUCKETS: usize = 24;
+
+/// Counters for the operations on a queue.
+///
+/// Without the `metrics` feature, only the name is kept and recording anything is a no-op.
+pub struct Metrics {
+    /// The name assigned to the queue.
+    name: Option<Box<str>>,
+
+    /// The highest number of items seen in the queue right after a push.
+    #[cfg(feature = "metrics")]
+    high_water: AtomicUsize,
+
+    /// How many pushes left the queue in each range of occupancy.
+    #[cfg(feature = "metrics")]
+    occupancy: [AtomicUsize; OCCUPANCY_BUCKETS],
+
+    #[cfg(feature = "metrics")]
+    pushes: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    pops: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    full: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    displaced: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    cas_retries: AtomicUsize,
+    #[cfg(feature = "metrics")]
+    busy_waits: AtomicUsize,
+
+    /// How many popped items spent each range of time in the queue.
+    #[cfg(feature = "latency")]
+    latency: [AtomicU64; LATENCY_BUCKETS],
+    #[cfg(feature = "latency")]
+    latency_total: AtomicU64,
+    #[cfg(feature = "latency")]
+    latency_max: AtomicU64,
+}
+
+/// Creates an array of atomics set to zero, with one element per token.
+#[cfg(any(feature = "metrics", feature = "latency"))]
+macro_rules! zeroed {
+    (@$atomic:ident $n:tt) => {
+        $atomic::new(0)
+    };
+    ($atomic:ident; $($n:tt)*) => {
+        [$(zeroed!(@$atomic $n)),*]
+    };
+}
+
+/// Increments a counter, if counters are enabled.
+macro_rules! record {
+    ($self:ident.$counter:ident) => {
+        #[cfg(feature = "metrics")]
+        $self.$counter.fetch_add(1, Ordering::Relaxed);
+    };
+}
+
+impl Metrics {
+    crate::const_fn!(
+        const_if: #[cfg(not(loom))];
+        /// Creates a new set of counters, all starting from zero.
+        pub const fn new() -> Metrics {
+            Metrics {
+                name: None,
+                #[cfg(feature = "metrics")]
+                high_water: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                occupancy: zeroed!(AtomicUsize; 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15),
+                #[cfg(feature = "metrics")]
+                pushes: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                pops: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                full: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                displaced: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                cas_retries: AtomicUsize::new(0),
+                #[cfg(feature = "metrics")]
+                busy_waits: AtomicUsize::new(0),
+                #[cfg(feature = "latency")]
+                latency: zeroed!(AtomicU64; 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23),
+                #[cfg(feature = "latency")]
+                latency_total: AtomicU64::new(0),
+                #[cfg(feature = "latency")]
+                latency_max: AtomicU64::new(0),
+            }
+        }
+    );
+
+    /// Returns the name assigned to the queue.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Assigns a name to the queue.
+    pub fn set_name(&mut self, name: Box<str>) {
+        self.name = Some(name);
+    }
+
+    /// Records an item that was pushed into the queue.
+    #[inline]
+    pub fn push(&self) {
+        record!(self.pushes);
+    }
+
+    /// Records the number of items in the queue right after a push.
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub fn occupancy(&self, len: usize) {
+        // Only write to the shared cache line when there is a new maximum.
+        if len > self.high_water.load(Ordering::Relaxed) {
+            self.high_water.fetch_max(len, Ordering::Relaxed);
+        }
+
+        // Bucket `i` holds lengths from `2^i` to `2^(i + 1) - 1`.
+        let bucket = (usize::BITS - 1).saturating_sub(len.leading_zeros()) as usize;
+        self.occupancy[bucket.min(OCCUPANCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the highest number of items seen in the queue.
+    #[cfg(feature = "metrics")]
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water.load(Ordering::Relaxed)
+    }
+
+    /// Sets the high-water mark to `len`.
+    #[cfg(feature = "metrics")]
+    pub fn reset_high_water_mark(&self, len: usize) {
+        self.high_water.store(len, Ordering::Relaxed);
+    }
+
+    /// Records an item that was popped from the queue.
+    #[inline]
+    pub fn pop(&self) {
+        record!(self.pops);
+    }
+
+    /// Records a push that failed because the queue was full.
+    #[inline]
+    pub fn full(&self) {
+        record!(self.full);
+    }
+
+    /// Records an item that was displaced by `force_push`.
+    #[inline]
+    pub fn displace(&self) {
+        record!(self.displaced);
+    }
+
+    /// Records a failed compare-and-swap that had to be retried.
+    #[inline]
+    pub fn cas_retry(&self) {
+        record!(self.cas_retries);
+    }
+
+    /// Records an iteration of a busy-wait loop, then busy-waits.
+    #[inline]
+    pub fn busy_wait(&self) {
+        record!(self.busy_waits);
+        crate::busy_wait();
+    }
+
+    /// Records the time an item spent in the queue, given the time it was pushed.
+    #[inline]
+    pub fn pop_latency(&self, pushed_at: &Timestamp) {
+        #[cfg(feature = "latency")]
+        {
+            let nanos = now().saturating_sub(pushed_at.get());
+
+            // Bucket `i` holds times from `2^i` to `2^(i + 1) - 1` microseconds.
+            let micros = nanos / 1000;
+            let bucket = (u64::BITS - 1).saturating_sub(micros.leading_zeros()) as usize;
+            self.latency[bucket.min(LATENCY_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+
+            self.latency_total.fetch_add(nanos, Ordering::Relaxed);
+            if nanos > self.latency_max.load(Ordering::Relaxed) {
+                self.latency_max.fetch_max(nanos, Ordering::Relaxed);
+            }
+        }
+
+        #[cfg(not(feature = "latency"))]
+        let _ = pushed_at;
+    }
+
+    /// Takes a snapshot of the latency histogram.
+    #[cfg(feature = "latency")]
+    pub fn latency_stats(&self) -> LatencyStats {
+        let mut histogram = [0; LATENCY_BUCKETS];
+        for (count, bucket) in histogram.iter_mut().zip(&self.latency) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+
+        LatencyStats {
+            count: histogram.iter().sum(),
+            total: Duration::from_nanos(self.latency_total.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.latency_max.load(Ordering::Relaxed)),
+            histogram,
+        }
+    }
+
+    /// Takes a snapshot of the counters.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> Stats {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed) as u64;
+
+        let mut occupancy = [0; OCCUPANCY_BUCKETS];
+        for (count, bucket) in occupancy.iter_mut().zip(&self.occupancy) {
+            *count = load(bucket);
+        }
+
+        Stats {
+            pushes: load(&self.pushes),
+            pops: load(&self.pops),
+            full: load(&self.full),
+            displaced: load(&self.displaced),
+            cas_retries: load(&self.cas_retries),
+            busy_waits: load(&self.busy_waits),
+            occupancy,
+        }
+    }
+}
+
+/// A queue that keeps [`Metrics`].
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub trait Instrumented {
+    /// Returns the counters for the operations on the queue.
+    fn metrics(&self) -> &Metrics;
+
+    /// Returns the counters for the operations on the queue.
+    fn metrics_mut(&mut self) -> &mut Metrics;
+
+    /// Returns the number of items in the queue.
+    fn len(&self) -> usize;
+
+    /// Returns the capacity of the queue, or `None` if it is unbounded.
+    fn capacity(&self) -> Option<usize>;
+}
+
+/// Emits a `tracing` event about a queue.
+///
+/// The event carries the name, capacity and length of the queue.
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($level:ident, $queue:expr, $message:literal) => {{
+        let queue = $queue;
+        tracing::event!(
+            tracing::Level::$level,
+            name = $crate::metrics::Instrumented::metrics(queue).name(),
+            capacity = $crate::metrics::Instrumented::capacity(queue),
+            len = $crate::metrics::Instrumented::len(queue),
+            $message
+        );
+    }};
+}
+
+/// Emits nothing without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($($tt:tt)*) => {{}};
+}
+
+pub(crate) use trace_event;
+
+/// A busy-wait loop that is reported once it goes on for too long.
+pub struct Spin<'a, Q: ?Sized> {
+    /// The queue we are waiting on.
+    queue: &'a Q,
+
+    /// The number of iterations so far.
+    #[cfg(feature = "tracing")]
+    count: u32,
+}
+
+impl<'a, Q: Instrumented + ?Sized> Spin<'a, Q> {
+    /// Starts a busy-wait loop on `queue`.
+    #[inline]
+    pub fn new(queue: &'a Q) -> Spin<'a, Q> {
+        Spin {
+            queue,
+            #[cfg(feature = "tracing")]
+            count: 0,
+        }
+    }
+
+    /// Busy-waits for one iteration of the loop.
+    #[inline]
+    pub fn wait(&mut self) {
+        self.queue.metrics().busy_wait();
+
+        #[cfg(feature = "tracing")]
+        {
+            self.count = self.count.saturating_add(1);
+            if self.count == LONG_SPIN {
+                trace_event!(WARN, self.queue, "busy-waiting for a long time");
+            }
+        }
+    }
+}
+
+/// A snapshot of the operation counters of a [`ConcurrentQueue`](crate::ConcurrentQueue).
+///
+/// Returned by [`ConcurrentQueue::stats()`](crate::ConcurrentQueue::stats). The counters are
+/// read one at a time while the queue is in use, so they are not guaranteed to be consistent
+/// with one another.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Stats {
+    /// The number of items pushed into the queue.
+    pub pushes: u64,
+
+    /// The number of items popped from the queue.
+    pub pops: u64,
+
+    /// The number of pushes rejected because the queue was full.
+    pub full: u64,
+
+    /// The number of items displaced by [`force_push`](crate::ConcurrentQueue::force_push).
+    pub displaced: u64,
+
+    /// The number of times a compare-and-swap on the queue failed and had to be retried.
+    pub cas_retries: u64,
+
+    /// The number of times an operation had to busy-wait for another thread.
+    pub busy_waits: u64,
+
+    /// A histogram of the number of items in the queue right after each push.
+    ///
+    /// Bucket `i` counts the pushes that left between `2^i` and `2^(i + 1) - 1` items in the
+    /// queue. The last bucket also counts everything above that.
+    pub occupancy: [u64; OCCUPANCY_BUCKETS],
+}
+
+/// A snapshot of the time items spent in a [`ConcurrentQueue`](crate::ConcurrentQueue).
+///
+/// Returned by [`ConcurrentQueue::latency_stats()`](crate::ConcurrentQueue::latency_stats). Each
+/// item is measured from the moment its push completes to the moment it is popped. The fields are
+/// read one at a time while the queue is in use, so they are not guaranteed to be consistent with
+/// one another.
+#[cfg(feature = "latency")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct LatencyStats {
+    /// The number of items measured.
+    pub count: u64,
+
+    /// The total time the measured items spent in the queue.
+    pub total: Duration,
+
+    /// The longest time an item spent in the queue.
+    pub max: Duration,
+
+    /// A histogram of the time items spent in the queue.
+    ///
+    /// Bucket `i` counts the items that spent between `2^i` and `2^(i + 1)` microseconds in the
+    /// queue. The first bucket also counts items that spent less than a microsecond, and the last
+    /// bucket also counts everything above its range.
+    pub histogram: [u64; LATENCY_BUCKETS],
+}
+
+#[cfg(feature = "latency")]
+impl LatencyStats {
+    /// Returns the average time the measured items spent in the queue.
+    ///
+    /// Returns `None` if no items were measured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ConcurrentQueue;
+    ///
+    /// let q = ConcurrentQueue::unbounded();
+    /// assert_eq!(q.latency_stats().mean(), None);
+    ///
+    /// q.push(1).unwrap();
+    /// q.pop().unwrap();
+    /// assert!(q.latency_stats().mean().is_some());
+    /// ```
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let nanos = self.total.as_nanos() / u128::from(self.count);
+        Some(Duration::from_nanos(nanos as u64))
+    }
+}
+
+/// The time an item was pushed into its slot.
+///
+/// Without the `latency` feature, this is empty and setting it is a no-op.
+pub struct Timestamp {
+    #[cfg(feature = "latency")]
+    nanos: AtomicU64,
+}
+
+impl Timestamp {
+    crate::const_fn!(
+        const_if: #[cfg(not(loom))];
+        /// Creates an unset timestamp.
+        pub const fn new() -> Timestamp {
+            Timestamp {
+                #[cfg(feature = "latency")]
+                nanos: AtomicU64::new(0),
+            }
+        }
+    );
+
+    /// Sets the timestamp to the current time.
+    #[inline]
+    pub fn set(&self) {
+        #[cfg(feature = "latency")]
+        self.nanos.store(now(), Ordering::Relaxed);
+    }
+
+    /// Returns the timestamp in nanoseconds since the first timestamp was taken.
+    #[cfg(feature = "latency")]
+    pub fn get(&self) -> u64 {
+        self.nanos.load(Ordering::Relaxed)
+    }
+
+    /// Returns how long ago the timestamp was set.
+    #[cfg(feature = "latency")]
+    pub fn age(&self) -> Duration {
+        Duration::from_nanos(now().saturating_sub(self.get()))
+    }
+}
+
+/// The instant timestamps are measured from, set by the first call to [`now`].
+#[cfg(feature = "latency")]
+struct Epoch {
+    /// Guards the write to `instant`.
+    init: Once,
+
+    /// The instant, once `init` has completed.
+    instant: UnsafeCell<Option<Instant>>,
+}
+
+// SAFETY: `instant` is only written inside `init.call_once`, and only read after a call to
+// `call_once` has returned, which synchronizes with the write.
+#[cfg(feature = "latency")]
+unsafe impl Sync for Epoch {}
+
+#[cfg(feature = "latency")]
+impl Epoch {
+    /// Returns the instant, setting it to the current time on the first call.
+    fn get(&self) -> Instant {
+        // SAFETY: See the `Sync` impl above.
+        self.init
+            .call_once(|| unsafe { *self.instant.get() = Some(Instant::now()) });
+        unsafe { (*self.instant.get()).unwrap() }
+    }
+}
+
+/// Returns the current time in nanoseconds since the first call to this function.
+#[cfg(feature = "latency")]
+fn now() -> u64 {
+    static EPOCH: Epoch = Epoch {
+        init: Once::new(),
+        instant: UnsafeCell::new(None),
+    };
+
+    EPOCH.get().elapsed().as_nanos() as u64
+}
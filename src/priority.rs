@@ -0,0 +1,350 @@
+//! A queue with a fixed number of priority levels.
+//!
+//! A [`PriorityQueue`] keeps one [`ConcurrentQueue`] per priority level. Items are pushed into the
+//! level of their priority and [`pop`](PriorityQueue::pop) takes from the highest non-empty level,
+//! so items of the same priority are popped in the order they were pushed.
+//!
+//! Under constant load, items of low priority may never be popped. An aging policy set with
+//! [`with_aging`](PriorityQueue::with_aging) prevents that by periodically serving lower levels
+//! first.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::priority::PriorityQueue;
+//!
+//! let q = PriorityQueue::unbounded(3);
+//!
+//! q.push("low", 0).unwrap();
+//! q.push("high", 2).unwrap();
+//! q.push("medium", 1).unwrap();
+//!
+//! assert_eq!(q.pop(), Ok("high"));
+//! assert_eq!(q.pop(), Ok("medium"));
+//! assert_eq!(q.pop(), Ok("low"));
+//! ```
+
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// A concurrent queue with a fixed number of priority levels.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct PriorityQueue<T> {
+    /// One queue per level, lowest priority first.
+    levels: Box<[ConcurrentQueue<T>]>,
+
+    /// Every this many pops, lower levels are served first.
+    aging: Option<usize>,
+
+    /// The number of calls to [`pop`](PriorityQueue::pop) so far, used by the aging policy.
+    pops: AtomicUsize,
+}
+
+impl<T> PriorityQueue<T> {
+    /// Creates a new queue with `levels` priority levels, each holding at most `cap` items.
+    ///
+    /// # Panics
+    ///
+    /// If the number of levels or the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::<i32>::bounded(3, 100);
+    /// ```
+    pub fn bounded(levels: usize, cap: usize) -> PriorityQueue<T> {
+        PriorityQueue::new(levels, || ConcurrentQueue::bounded(cap))
+    }
+
+    /// Creates a new queue with `levels` priority levels of unlimited capacity.
+    ///
+    /// # Panics
+    ///
+    /// If the number of levels is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::<i32>::unbounded(3);
+    /// ```
+    pub fn unbounded(levels: usize) -> PriorityQueue<T> {
+        PriorityQueue::new(levels, ConcurrentQueue::unbounded)
+    }
+
+    /// Creates a queue with `levels` levels built by `level`.
+    fn new(levels: usize, level: impl FnMut() -> ConcurrentQueue<T>) -> PriorityQueue<T> {
+        assert!(levels > 0, "number of levels must be positive");
+
+        PriorityQueue {
+            levels: core::iter::repeat_with(level).take(levels).collect(),
+            aging: None,
+            pops: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets an aging policy that prevents items of low priority from starving.
+    ///
+    /// Every `interval`-th call to [`pop`](PriorityQueue::pop) serves the lower levels first.
+    /// These calls take turns starting at each level, from the lowest one upwards, so a non-empty
+    /// level is served at least once every `interval * levels` calls even if higher levels are
+    /// never empty. Calls that find the queue empty count too.
+    ///
+    /// # Panics
+    ///
+    /// If the interval is zero, this method will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::unbounded(2).with_aging(3);
+    /// for i in 0..3 {
+    ///     q.push(i, 1).unwrap();
+    /// }
+    /// q.push(10, 0).unwrap();
+    ///
+    /// // The third pop serves the lower level.
+    /// assert_eq!(q.pop(), Ok(0));
+    /// assert_eq!(q.pop(), Ok(1));
+    /// assert_eq!(q.pop(), Ok(10));
+    /// assert_eq!(q.pop(), Ok(2));
+    /// ```
+    pub fn with_aging(mut self, interval: usize) -> PriorityQueue<T> {
+        assert!(interval > 0, "aging interval must be positive");
+        self.aging = Some(interval);
+        self
+    }
+
+    /// Attempts to push an item with the given priority.
+    ///
+    /// Priorities range from `0`, the lowest, to `levels() - 1`, the highest. If the level is
+    /// full or the queue is closed, the item is returned back as an error.
+    ///
+    /// # Panics
+    ///
+    /// If the priority is not lower than the number of levels, this method will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = PriorityQueue::bounded(2, 1);
+    ///
+    /// q.push(10, 0).unwrap();
+    /// q.push(20, 1).unwrap();
+    /// assert_eq!(q.push(30, 1), Err(PushError::Full(30)));
+    ///
+    /// q.close();
+    /// assert_eq!(q.push(40, 0), Err(PushError::Closed(40)));
+    /// ```
+    pub fn push(&self, value: T, priority: usize) -> Result<(), PushError<T>> {
+        self.level(priority).push(value)
+    }
+
+    /// Attempts to pop an item from the highest non-empty level.
+    ///
+    /// If every level is empty, an error is returned, which is [`PopError::Closed`] once the queue
+    /// is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    /// use concurrent_queue::PopError;
+    ///
+    /// let q = PriorityQueue::unbounded(2);
+    /// q.push(10, 0).unwrap();
+    /// q.push(20, 1).unwrap();
+    /// q.close();
+    ///
+    /// assert_eq!(q.pop(), Ok(20));
+    /// assert_eq!(q.pop(), Ok(10));
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        let levels = self.levels.len();
+
+        if let Some(interval) = self.aging {
+            // Each call takes a distinct turn, so concurrent pops cannot skip an aging turn.
+            let turn = self.pops.fetch_add(1, Ordering::Relaxed);
+            if (turn + 1) % interval == 0 {
+                // Serve the lower levels first, starting at a different one each time.
+                let start = (turn / interval) % levels;
+                for level in &self.levels[start..] {
+                    if let Ok(value) = level.pop() {
+                        return Ok(value);
+                    }
+                }
+            }
+        }
+
+        let mut closed = true;
+        for level in self.levels.iter().rev() {
+            match level.pop() {
+                Ok(value) => return Ok(value),
+                Err(PopError::Empty) => closed = false,
+                Err(PopError::Closed) => {}
+            }
+        }
+
+        if closed {
+            Err(PopError::Closed)
+        } else {
+            Err(PopError::Empty)
+        }
+    }
+
+    /// Returns the level of the given priority.
+    fn level(&self, priority: usize) -> &ConcurrentQueue<T> {
+        assert!(
+            priority < self.levels.len(),
+            "priority {} is out of range for {} levels",
+            priority,
+            self.levels.len()
+        );
+        &self.levels[priority]
+    }
+
+    /// Returns the number of priority levels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::<i32>::unbounded(4);
+    /// assert_eq!(q.levels(), 4);
+    /// ```
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the number of items in the queue, across all levels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::unbounded(2);
+    /// q.push(1, 0).unwrap();
+    /// q.push(2, 1).unwrap();
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(ConcurrentQueue::len).sum()
+    }
+
+    /// Returns the number of items with the given priority.
+    ///
+    /// # Panics
+    ///
+    /// If the priority is not lower than the number of levels, this method will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::unbounded(2);
+    /// q.push(1, 1).unwrap();
+    /// assert_eq!(q.len_at(0), 0);
+    /// assert_eq!(q.len_at(1), 1);
+    /// ```
+    pub fn len_at(&self, priority: usize) -> usize {
+        self.level(priority).len()
+    }
+
+    /// Returns `true` if every level is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::unbounded(2);
+    /// assert!(q.is_empty());
+    ///
+    /// q.push(1, 0).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(ConcurrentQueue::is_empty)
+    }
+
+    /// Returns the capacity of each level, or `None` if the levels are unbounded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// assert_eq!(PriorityQueue::<i32>::bounded(2, 7).capacity(), Some(7));
+    /// assert_eq!(PriorityQueue::<i32>::unbounded(2).capacity(), None);
+    /// ```
+    pub fn capacity(&self) -> Option<usize> {
+        self.levels[0].capacity()
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// When a queue is closed, no more items can be pushed into any level, but the remaining items
+    /// can still be popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::<i32>::unbounded(2);
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    /// ```
+    pub fn close(&self) -> bool {
+        // The lowest level is closed last, so once it is closed, so is every other level.
+        for level in &self.levels[1..] {
+            level.close();
+        }
+        self.levels[0].close()
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::priority::PriorityQueue;
+    ///
+    /// let q = PriorityQueue::<i32>::unbounded(2);
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.levels[0].is_closed()
+    }
+}
+
+impl<T> fmt::Debug for PriorityQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorityQueue")
+            .field("levels", &self.levels())
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
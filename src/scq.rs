@@ -0,0 +1,1210 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::mem::{self, MaybeUninit};
+#[cfg(feature = "latency")]
+use core::time::Duration;
+
+use crossbeam_utils::CachePadded;
+
+use crate::metrics::{Instrumented, Metrics, Spin, Timestamp};
+#[cfg(feature = "seq")]
+use crate::sync::atomic::AtomicU64;
+use crate::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering};
+use crate::sync::cell::UnsafeCell;
+#[allow(unused_imports)]
+use crate::sync::prelude::*;
+use crate::{PopError, PushError, TryPopError, TryPushError};
+
+// Positions in a ring are shifted left by one bit in its head and tail to make room for a flag:
+// * If the tail of the allocated ring has `CLOSED` set, the queue is closed.
+// * If the head of the allocated ring has `FROZEN` set, consumers wait before using the entries
+//   they claim.
+const CLOSED: usize = 1;
+const FROZEN: usize = 1;
+
+// Positions wrap around at this mask, one bit short of a `usize`.
+const POS_MASK: usize = usize::MAX >> 1;
+
+// The number of ring entries that fit in a cache line. Consecutive positions are spread over
+// different cache lines so that threads working on neighboring entries don't contend.
+const ENTRIES_PER_LINE: usize = 64 / mem::size_of::<usize>();
+
+/// Loads the value of an atomic with `Ordering::SeqCst`.
+///
+/// The algorithm relies on these loads returning the latest value. Loom treats `SeqCst` loads like
+/// `Acquire` loads, which may keep returning a stale value, so it uses a read-modify-write instead.
+macro_rules! load {
+    ($atomic:expr) => {{
+        #[cfg(not(loom))]
+        let value = $atomic.load(Ordering::SeqCst);
+        #[cfg(loom)]
+        let value = $atomic.fetch_add(0, Ordering::SeqCst);
+        value
+    }};
+}
+
+/// Returns `true` if position `a` comes before position `b`.
+fn before(a: usize, b: usize) -> bool {
+    ((a.wrapping_sub(b) << 1) as isize) < 0
+}
+
+/// A ring of slot indices.
+///
+/// Every entry holds the cycle it was last written on in its upper bits, followed by a "safe" bit
+/// and the slot index. Indices with the `half` bit set stand for an empty entry.
+struct Ring {
+    /// The position consumers take entries from, shifted left by one bit.
+    head: CachePadded<AtomicUsize>,
+
+    /// The position producers put entries at, shifted left by one bit.
+    tail: CachePadded<AtomicUsize>,
+
+    /// The number of failed attempts to pop before the ring is known to be empty.
+    threshold: CachePadded<AtomicIsize>,
+
+    /// The entries.
+    entries: Box<[AtomicUsize]>,
+}
+
+impl Ring {
+    /// Creates a ring of `len` entries, holding the indices `0..full`.
+    fn new(len: usize, full: usize) -> Ring {
+        let empty = len | (len - 1);
+        let mut entries = (0..len).map(|_| empty).collect::<Vec<_>>();
+
+        // Fill the ring as if the indices were pushed on the first cycle.
+        let ring = |pos| Ring::remap_in(len, pos);
+        for i in 0..full {
+            entries[ring(len + i)] = Ring::cycle_in(len, len + i) | len | i;
+        }
+
+        Ring {
+            head: CachePadded::new(AtomicUsize::new(len << 1)),
+            tail: CachePadded::new(AtomicUsize::new((len + full) << 1)),
+            threshold: CachePadded::new(AtomicIsize::new(if full > 0 {
+                Ring::full_threshold(len)
+            } else {
+                -1
+            })),
+            entries: entries.into_iter().map(AtomicUsize::new).collect(),
+        }
+    }
+
+    /// Returns the threshold of a ring of `len` entries that isn't empty.
+    fn full_threshold(len: usize) -> isize {
+        (len / 2 * 3 - 1) as isize
+    }
+
+    /// Returns the cycle of position `pos` in a ring of `len` entries, in the upper bits.
+    fn cycle_in(len: usize, pos: usize) -> usize {
+        (pos << 1) & !(len * 2 - 1)
+    }
+
+    /// Returns the entry for position `pos` in a ring of `len` entries.
+    fn remap_in(len: usize, pos: usize) -> usize {
+        let index = pos & (len - 1);
+        let lines = len / ENTRIES_PER_LINE;
+        if lines <= 1 {
+            index
+        } else {
+            (index % lines) * ENTRIES_PER_LINE + index / lines
+        }
+    }
+
+    /// Returns the entry for position `pos`.
+    fn entry(&self, pos: usize) -> &AtomicUsize {
+        &self.entries[Ring::remap_in(self.entries.len(), pos)]
+    }
+
+    /// Returns the cycle of position `pos`, in the upper bits.
+    fn cycle(&self, pos: usize) -> usize {
+        Ring::cycle_in(self.entries.len(), pos)
+    }
+
+    /// Returns the cycle bits of an entry.
+    fn cycle_of(&self, entry: usize) -> usize {
+        entry & !(self.entries.len() * 2 - 1)
+    }
+
+    /// Returns the "safe" bit.
+    fn safe(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the bit that marks an index as empty.
+    fn half(&self) -> usize {
+        self.entries.len() / 2
+    }
+
+    /// Returns the number of positions between the head and the tail.
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::SeqCst) >> 1;
+        let head = self.head.load(Ordering::SeqCst) >> 1;
+        if before(head, tail) {
+            tail.wrapping_sub(head) & POS_MASK
+        } else {
+            0
+        }
+    }
+
+    /// Moves the tail forward to `head` if it is behind it.
+    fn catch_up(&self, mut head: usize) {
+        let mut tail = load!(self.tail);
+
+        while before(tail >> 1, head) {
+            match self.tail.compare_exchange(
+                tail,
+                (head << 1) | (tail & CLOSED),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(t) => {
+                    tail = t;
+                    head = load!(self.head) >> 1;
+                }
+            }
+        }
+    }
+}
+
+/// A slot holding an item.
+struct Slot<T> {
+    /// The sequence number of the value in this slot.
+    #[cfg(feature = "seq")]
+    seq: UnsafeCell<u64>,
+
+    /// The number of items lost right before the value in this slot.
+    #[cfg(feature = "seq")]
+    lost: UnsafeCell<u64>,
+
+    /// Whether the item in this slot was removed or its reservation was abandoned, in which case
+    /// the slot is freed once a consumer reaches it.
+    removed: AtomicBool,
+
+    /// The time the value was pushed.
+    pushed_at: Timestamp,
+
+    /// The value in this slot.
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A slot reserved for writing by [`Scq::reserve`].
+///
+/// If it is dropped without being committed, consumers skip over it and free it.
+pub struct WriteSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Scq<T>,
+
+    /// The index of the slot.
+    index: usize,
+}
+
+impl<T> WriteSlot<'_, T> {
+    /// Returns the slot.
+    fn slot(&self) -> &Slot<T> {
+        &self.queue.slots[self.index]
+    }
+
+    /// Returns the uninitialized value in the slot.
+    pub fn value_mut(&mut self) -> &mut MaybeUninit<T> {
+        // SAFETY: We have exclusive access to the slot until it is committed or dropped.
+        self.slot().value.with_mut(|slot| unsafe { &mut *slot })
+    }
+
+    /// Returns the sequence number of the value written into the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: We have exclusive access to the slot.
+        self.slot().seq.with_mut(|seq| unsafe { *seq })
+    }
+
+    /// Writes a value into the slot and publishes it.
+    pub fn write(mut self, value: T) {
+        self.value_mut().write(value);
+        // SAFETY: The value was just initialized.
+        unsafe { self.commit() }
+    }
+
+    /// Publishes the value in the slot.
+    ///
+    /// The value is published even if the queue was closed after the slot was reserved.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    pub unsafe fn commit(self) {
+        let queue = self.queue;
+        queue.metrics.push();
+        self.slot().pushed_at.set();
+        #[cfg(feature = "seq")]
+        queue.take_lost(self.slot(), 0);
+        queue.enqueue(&queue.allocated, self.index, true);
+        #[cfg(feature = "metrics")]
+        queue.record_push();
+        queue.writing.fetch_sub(1, Ordering::SeqCst);
+        queue.reserved.fetch_sub(1, Ordering::SeqCst);
+        mem::forget(self);
+    }
+}
+
+impl<T> Drop for WriteSlot<'_, T> {
+    fn drop(&mut self) {
+        // Publish the slot as removed, so that it takes up its place until consumers reach it.
+        self.queue.mark_removed(self.slot());
+        self.queue.enqueue(&self.queue.allocated, self.index, true);
+        self.queue.writing.fetch_sub(1, Ordering::SeqCst);
+        self.queue.reserved.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A slot claimed for reading by [`Scq::pop_ref`].
+///
+/// The slot goes back to the free ring once this is dropped.
+pub struct ReadSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Scq<T>,
+
+    /// The index of the slot.
+    index: usize,
+
+    /// Whether this was returned by [`Scq::pop_ref`] and is counted in [`Scq::held`].
+    held: bool,
+}
+
+impl<T> ReadSlot<'_, T> {
+    /// Returns the slot.
+    fn slot(&self) -> &Slot<T> {
+        &self.queue.slots[self.index]
+    }
+
+    /// Returns the sequence number of the value in the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: We have exclusive access to the slot.
+        self.slot().seq.with_mut(|seq| unsafe { *seq })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value(&self) -> &T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot()
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_ref() })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot()
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_mut() })
+    }
+
+    /// Moves the value out of the slot and releases it.
+    pub fn into_inner(self) -> T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        let value = self
+            .slot()
+            .value
+            .with_mut(|slot| unsafe { slot.read().assume_init() });
+        self.release();
+        mem::forget(self);
+        value
+    }
+
+    /// Puts the slot back into the free ring.
+    fn release(&self) {
+        if self.held {
+            self.queue.held.fetch_sub(1, Ordering::SeqCst);
+        }
+        self.queue.enqueue(&self.queue.free, self.index, true);
+    }
+}
+
+impl<T> Drop for ReadSlot<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot().value.with_mut(|slot| unsafe {
+            let value = &mut *slot;
+            value.as_mut_ptr().drop_in_place();
+        });
+        self.release();
+    }
+}
+
+/// A bounded queue built on fetch-and-add, following Nikolaev's Scalable Circular Queue (SCQ).
+///
+/// Items live in a fixed array of slots. The indices of these slots circulate between two rings:
+/// the free ring holds the slots that can be pushed into, and the allocated ring holds the slots
+/// that hold items, in order. A push takes an index from the free ring, writes the item into its
+/// slot and puts the index into the allocated ring, and a pop does the opposite.
+///
+/// Each ring has at least twice as many entries as there are slots. Producers and consumers claim
+/// entries by incrementing the tail and the head of a ring with fetch-and-add, so unlike
+/// [`Bounded`], they never retry on contention for the head or the tail. Each entry holds the
+/// cycle it was last written on, so that a consumer that gets ahead of a producer can tell that the
+/// entry is empty and mark it so that the producer retries. A threshold counts down the failed
+/// attempts to pop, which stops consumers from going around an empty ring forever.
+///
+/// [`Bounded`]: crate::bounded::Bounded
+pub struct Scq<T> {
+    /// The indices of the slots holding items, in the order they were pushed.
+    allocated: Ring,
+
+    /// The indices of the free slots.
+    free: Ring,
+
+    /// The slots holding items.
+    slots: Box<[Slot<T>]>,
+
+    /// The sequence number of the next slot handed out.
+    #[cfg(feature = "seq")]
+    next_seq: CachePadded<AtomicU64>,
+
+    /// The number of items lost after the last item in the queue, which are counted before the
+    /// next item pushed.
+    #[cfg(feature = "seq")]
+    lost: AtomicU64,
+
+    /// The number of slots in the allocated ring whose item was removed or whose reservation was
+    /// abandoned.
+    removed: AtomicUsize,
+
+    /// The number of reserved slots that were not committed or dropped yet.
+    ///
+    /// These are published even if the queue is closed, so consumers don't report it as closed
+    /// while there are any.
+    reserved: AtomicUsize,
+
+    /// The number of [`ReadSlot`]s returned by [`pop_ref`](Scq::pop_ref) that were not dropped
+    /// yet.
+    held: AtomicUsize,
+
+    /// The number of [`WriteSlot`]s returned by [`reserve`](Scq::reserve) that were not committed
+    /// or dropped yet.
+    writing: AtomicUsize,
+
+    /// Counters for the operations on the queue.
+    metrics: Metrics,
+}
+
+impl<T> Scq<T> {
+    /// Creates a new queue.
+    pub fn new(cap: usize) -> Scq<T> {
+        assert!(cap > 0, "capacity must be positive");
+
+        // The rings need twice as many entries as there can be indices, rounded up to a power of
+        // two. The empty bit of an index must not be zero, so there are at least four entries.
+        let len = cap.next_power_of_two().max(2) * 2;
+
+        Scq {
+            allocated: Ring::new(len, 0),
+            free: Ring::new(len, cap),
+            slots: (0..cap)
+                .map(|_| Slot {
+                    #[cfg(feature = "seq")]
+                    seq: UnsafeCell::new(0),
+                    #[cfg(feature = "seq")]
+                    lost: UnsafeCell::new(0),
+                    removed: AtomicBool::new(false),
+                    pushed_at: Timestamp::new(),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            #[cfg(feature = "seq")]
+            next_seq: CachePadded::new(AtomicU64::new(0)),
+            #[cfg(feature = "seq")]
+            lost: AtomicU64::new(0),
+            removed: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(0),
+            held: AtomicUsize::new(0),
+            writing: AtomicUsize::new(0),
+            metrics: Metrics::new(),
+        }
+    }
+
+    /// Puts `index` into `ring`.
+    ///
+    /// Fails only if `ring` is closed, unless `force` is set.
+    fn enqueue(&self, ring: &Ring, index: usize, force: bool) -> bool {
+        #[cfg(loom)]
+        let mut spin = Spin::new(self);
+
+        loop {
+            let tail = ring.tail.fetch_add(1 << 1, Ordering::SeqCst);
+            if tail & CLOSED != 0 && !force {
+                return false;
+            }
+
+            let pos = tail >> 1;
+            let cycle = ring.cycle(pos);
+            let entry = ring.entry(pos);
+            let mut e = entry.load(Ordering::Acquire);
+
+            // The entry must be empty and from an earlier cycle. If a consumer that got to it
+            // first marked it as unsafe, it is only usable if no consumer is waiting on it yet.
+            while before_cycle(ring.cycle_of(e), cycle)
+                && e & ring.half() != 0
+                && (e & ring.safe() != 0 || !before(pos, load!(ring.head) >> 1))
+            {
+                match entry.compare_exchange_weak(
+                    e,
+                    cycle | ring.safe() | index,
+                    Ordering::SeqCst,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        let threshold = Ring::full_threshold(ring.entries.len());
+                        if load!(ring.threshold) != threshold {
+                            // A swap rather than a store keeps every write to the threshold a
+                            // read-modify-write, which loom needs to order it with `fetch_sub`.
+                            ring.threshold.swap(threshold, Ordering::SeqCst);
+                        }
+                        return true;
+                    }
+                    Err(x) => {
+                        self.metrics.cas_retry();
+                        e = x;
+                    }
+                }
+            }
+
+            // Loom complains if there isn't an explicit busy wait here.
+            #[cfg(loom)]
+            spin.wait();
+        }
+    }
+
+    /// Takes the index at the head of `ring`.
+    ///
+    /// Normally, this gives up once the threshold says the ring is empty. If `force` is set, it
+    /// claims positions until it gets past the tail instead.
+    fn dequeue(&self, ring: &Ring, force: bool) -> Option<usize> {
+        if !force && load!(ring.threshold) < 0 {
+            return None;
+        }
+
+        let mut spin = Spin::new(self);
+
+        loop {
+            let head = ring.head.fetch_add(1 << 1, Ordering::SeqCst);
+
+            // If the head is frozen, wait until it is thawed.
+            if head & FROZEN != 0 {
+                while load!(ring.head) & FROZEN != 0 {
+                    spin.wait();
+                }
+            }
+
+            let pos = head >> 1;
+            let cycle = ring.cycle(pos);
+            let entry = ring.entry(pos);
+            let mut e = entry.load(Ordering::Acquire);
+
+            loop {
+                if ring.cycle_of(e) == cycle {
+                    if e & ring.half() != 0 {
+                        // The item was displaced; move on to the next position.
+                        break;
+                    }
+
+                    // Take the index, leaving the entry empty.
+                    entry.fetch_or(ring.half(), Ordering::AcqRel);
+                    return Some(e & (ring.half() - 1));
+                }
+
+                // The producer for this position hasn't got here yet. Mark the entry so that it
+                // doesn't use it: move an empty entry to our cycle, or mark a full one as unsafe.
+                if before_cycle(ring.cycle_of(e), cycle) {
+                    let new = if e & ring.half() != 0 {
+                        cycle | (e & ring.safe()) | (ring.entries.len() - 1)
+                    } else {
+                        e & !ring.safe()
+                    };
+
+                    if let Err(x) =
+                        entry.compare_exchange_weak(e, new, Ordering::SeqCst, Ordering::Acquire)
+                    {
+                        self.metrics.cas_retry();
+                        e = x;
+                        continue;
+                    }
+                }
+
+                // If the ring is empty, bring the tail up to the head.
+                let next = (pos + 1) & POS_MASK;
+                let tail = load!(ring.tail) >> 1;
+                if !before(next, tail) {
+                    ring.catch_up(next);
+                    if !force {
+                        ring.threshold.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    return None;
+                }
+
+                if !force && ring.threshold.fetch_sub(1, Ordering::SeqCst) <= 0 {
+                    return None;
+                }
+                break;
+            }
+
+            // Loom complains if there isn't an explicit busy wait here.
+            #[cfg(loom)]
+            spin.wait();
+        }
+    }
+
+    /// Takes the index of the slot holding the item at the head of the queue.
+    ///
+    /// The slots of removed items are freed on the way.
+    fn take_item(&self, force: bool) -> Option<usize> {
+        loop {
+            let index = self.dequeue(&self.allocated, force)?;
+            let slot = &self.slots[index];
+            if !slot.removed.load(Ordering::Relaxed) {
+                return Some(index);
+            }
+            self.free_removed(slot, index);
+        }
+    }
+
+    /// Marks the item in `slot` as removed, so that its slot is freed once a consumer reaches it.
+    fn mark_removed(&self, slot: &Slot<T>) {
+        slot.removed.store(true, Ordering::Relaxed);
+        self.removed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Puts the slot of a removed item, which was taken out of the allocated ring, back into the
+    /// free ring.
+    fn free_removed(&self, slot: &Slot<T>, index: usize) {
+        slot.removed.store(false, Ordering::Relaxed);
+        self.removed.fetch_sub(1, Ordering::SeqCst);
+        self.enqueue(&self.free, index, true);
+    }
+
+    /// Takes a free slot, numbering it if the `seq` feature is enabled.
+    fn take_free(&self) -> Result<usize, PushError<()>> {
+        if self.is_closed() {
+            return Err(PushError::Closed(()));
+        }
+
+        match self.dequeue(&self.free, false) {
+            Some(index) => {
+                #[cfg(feature = "seq")]
+                {
+                    let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+                    // SAFETY: We have exclusive access to the free slot.
+                    self.slots[index]
+                        .seq
+                        .with_mut(|slot| unsafe { *slot = seq });
+                }
+                Ok(index)
+            }
+            None => {
+                self.metrics.full();
+                Err(PushError::Full(()))
+            }
+        }
+    }
+
+    /// Attempts to push an item into the queue.
+    pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        let index = match self.take_free() {
+            Ok(index) => index,
+            Err(PushError::Full(())) => return Err(PushError::Full(value)),
+            Err(PushError::Closed(())) => return Err(PushError::Closed(value)),
+        };
+
+        let slot = &self.slots[index];
+        // SAFETY: We have exclusive access to the free slot.
+        slot.value
+            .with_mut(|slot| unsafe { slot.write(MaybeUninit::new(value)) });
+        slot.pushed_at.set();
+        #[cfg(feature = "seq")]
+        self.take_lost(slot, 0);
+
+        if self.enqueue(&self.allocated, index, false) {
+            self.metrics.push();
+            #[cfg(feature = "metrics")]
+            self.record_push();
+            Ok(())
+        } else {
+            // The queue was closed in the meantime, so take the value back out.
+            // SAFETY: The value was just written, and the slot was not published.
+            let value = slot
+                .value
+                .with_mut(|slot| unsafe { slot.read().assume_init() });
+            // SAFETY: The slot was not published.
+            #[cfg(feature = "seq")]
+            self.lost.fetch_add(
+                slot.lost.with_mut(|lost| unsafe { *lost }),
+                Ordering::Relaxed,
+            );
+            self.enqueue(&self.free, index, true);
+            Err(PushError::Closed(value))
+        }
+    }
+
+    /// Pushes an item into the queue, displacing another item if needed.
+    ///
+    /// If every slot is held by a [`ReadSlot`] or a [`WriteSlot`], there is nothing to displace, so
+    /// `Busy` is returned instead of waiting for a guard to be dropped.
+    pub fn force_push(&self, mut value: T) -> Result<Option<T>, TryPushError<T>> {
+        let mut spin = Spin::new(self);
+
+        loop {
+            match self.push(value) {
+                Ok(()) => return Ok(None),
+                Err(PushError::Closed(v)) => return Err(TryPushError::Closed(v)),
+                Err(PushError::Full(v)) => value = v,
+            }
+
+            // Count ourselves as a reservation, since the new value is published even if the
+            // queue gets closed after we take out the oldest one.
+            self.reserved.fetch_add(1, Ordering::SeqCst);
+            if self.is_closed() {
+                self.reserved.fetch_sub(1, Ordering::SeqCst);
+                return Err(TryPushError::Closed(value));
+            }
+
+            // Look for the oldest item while consumers are held back. Slots that are being
+            // pushed into or popped from will be free soon, so only displace an item if every
+            // slot holds one or is held by a `ReadSlot` or a `WriteSlot`, which may not be
+            // dropped any time soon.
+            let mut frozen = self.freeze();
+
+            // Free the slots of removed items at the head, and try pushing again if there were
+            // any.
+            if self.skip_head(&mut frozen) {
+                drop(frozen);
+                self.reserved.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+
+            let mut count = 0;
+            let mut oldest = None;
+            #[cfg(feature = "seq")]
+            let mut next = None;
+            self.for_each_item(&frozen, |entry, slot| {
+                count += 1;
+                #[cfg(feature = "seq")]
+                if oldest.is_some() && next.is_none() {
+                    next = Some(slot);
+                }
+                oldest = oldest.or(Some((entry, slot)));
+            });
+
+            let removed = load!(self.removed);
+            if count + removed + load!(self.held) + load!(self.writing) >= self.capacity() {
+                let (entry, slot) = match oldest {
+                    Some(oldest) => oldest,
+                    None if removed == 0 => {
+                        drop(frozen);
+                        self.reserved.fetch_sub(1, Ordering::SeqCst);
+                        return Err(TryPushError::Busy(value));
+                    }
+                    // The removed items are behind a slot that is still being pushed into, so
+                    // wait for consumers to free them.
+                    None => {
+                        drop(frozen);
+                        self.reserved.fetch_sub(1, Ordering::SeqCst);
+                        spin.wait();
+                        continue;
+                    }
+                };
+
+                // Empty the entry so that consumers skip over it, and reuse its slot. If it was at
+                // the head, move the head past it so that emptied entries don't pile up when
+                // nothing pops.
+                let half = self.allocated.half();
+                let index = entry.fetch_or(half, Ordering::AcqRel) & (half - 1);
+                self.skip_head(&mut frozen);
+
+                // Count the old value as lost before the next item, which is the new one if there
+                // are no others.
+                // SAFETY: The slots can't be popped while the head is frozen.
+                #[cfg(feature = "seq")]
+                let lost = match next {
+                    Some(next) => {
+                        let lost = slot.lost.with_mut(|lost| unsafe { *lost }) + 1;
+                        next.lost.with_mut(|next| unsafe { *next += lost });
+                        0
+                    }
+                    None => slot.lost.with_mut(|lost| unsafe { *lost }) + 1,
+                };
+                drop(frozen);
+
+                self.metrics.displace();
+                // SAFETY: We have exclusive access to the slot, and it holds the old value.
+                let old = slot.value.with_mut(|slot| unsafe {
+                    mem::replace(&mut *slot, MaybeUninit::new(value)).assume_init()
+                });
+                #[cfg(feature = "seq")]
+                {
+                    let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+                    // SAFETY: We have exclusive access to the slot.
+                    slot.seq.with_mut(|slot| unsafe { *slot = seq });
+                }
+                slot.pushed_at.set();
+                #[cfg(feature = "seq")]
+                self.take_lost(slot, lost);
+
+                self.enqueue(&self.allocated, index, true);
+                self.metrics.push();
+                #[cfg(feature = "metrics")]
+                self.record_push();
+                self.reserved.fetch_sub(1, Ordering::SeqCst);
+                return Ok(Some(old));
+            }
+
+            // Wait for a slot to be freed, and try pushing again.
+            drop(frozen);
+            self.reserved.fetch_sub(1, Ordering::SeqCst);
+            spin.wait();
+        }
+    }
+
+    /// Reserves a slot at the tail of the queue.
+    pub fn reserve(&self) -> Result<WriteSlot<'_, T>, PushError<()>> {
+        // Announce the reservation before checking whether the queue is closed, so that consumers
+        // that see no reservations know that no more can be made.
+        self.reserved.fetch_add(1, Ordering::SeqCst);
+
+        match self.take_free() {
+            Ok(index) => {
+                self.writing.fetch_add(1, Ordering::SeqCst);
+                Ok(WriteSlot { queue: self, index })
+            }
+            Err(err) => {
+                self.reserved.fetch_sub(1, Ordering::SeqCst);
+                Err(err)
+            }
+        }
+    }
+
+    /// Reserves a slot at the tail of the queue, which never waits for other threads.
+    pub fn reserve_nowait(&self) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        self.reserve().map_err(TryPushError::from)
+    }
+
+    /// Reserves up to `max` slots at the tail of the queue.
+    ///
+    /// Stops when the queue is full instead of waiting for room.
+    pub fn reserve_batch(&self, max: usize) -> Result<Vec<WriteSlot<'_, T>>, PushError<()>> {
+        let mut slots = Vec::new();
+
+        while slots.len() < max {
+            match self.reserve() {
+                Ok(slot) => slots.push(slot),
+                Err(err) if slots.is_empty() => return Err(err),
+                Err(_) => break,
+            }
+        }
+
+        Ok(slots)
+    }
+
+    /// Attempts to pop an item from the queue.
+    pub fn pop(&self) -> Result<T, PopError> {
+        self.claim().map(ReadSlot::into_inner)
+    }
+
+    /// Attempts to pop an item from the queue, reporting `Busy` if the head is frozen.
+    ///
+    /// A consumer that claims a position while the head is frozen has to wait until it is thawed,
+    /// so this checks first. An inspection that starts right after the check still makes it wait.
+    pub fn pop_nowait(&self) -> Result<T, TryPopError> {
+        if load!(self.allocated.head) & FROZEN != 0 {
+            return Err(TryPopError::Busy);
+        }
+        self.pop().map_err(TryPopError::from)
+    }
+
+    /// Attempts to pop an item from the queue, leaving it in its slot until the guard is dropped.
+    pub fn pop_ref(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        let mut slot = self.claim()?;
+
+        // Let producers know that the slot is still in use.
+        self.held.fetch_add(1, Ordering::SeqCst);
+        slot.held = true;
+        Ok(slot)
+    }
+
+    /// Attempts to pop an item from the queue along with its sequence number.
+    #[cfg(feature = "seq")]
+    pub fn pop_seq(&self) -> Result<(u64, T), PopError> {
+        let slot = self.claim()?;
+        Ok((slot.seq(), slot.into_inner()))
+    }
+
+    /// Attempts to pop an item from the queue along with the number of items lost right before
+    /// it.
+    #[cfg(feature = "seq")]
+    pub fn pop_with_loss(&self) -> Result<(T, u64), PopError> {
+        let slot = self.claim()?;
+        // SAFETY: We have exclusive access to the slot.
+        let lost = slot.slot().lost.with_mut(|lost| unsafe { *lost });
+        Ok((slot.into_inner(), lost))
+    }
+
+    /// Sets the number of items lost right before the item in `slot`, which is about to be
+    /// published, taking over the ones lost after the last item in the queue.
+    #[cfg(feature = "seq")]
+    fn take_lost(&self, slot: &Slot<T>, lost: u64) {
+        let lost = lost + self.lost.swap(0, Ordering::Relaxed);
+        // SAFETY: We have exclusive access to the slot until it is published.
+        slot.lost.with_mut(|slot| unsafe { *slot = lost });
+    }
+
+    /// Takes the slot at the head of the queue.
+    fn claim(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        let index = match self.take_item(false) {
+            Some(index) => index,
+            None if !self.is_closed() => return Err(PopError::Empty),
+            None => self.drain()?,
+        };
+
+        self.metrics.pop();
+        self.metrics.pop_latency(&self.slots[index].pushed_at);
+        Ok(ReadSlot {
+            queue: self,
+            index,
+            held: false,
+        })
+    }
+
+    /// Pops from a closed queue, which is only reported as closed once no pushes are in progress.
+    fn drain(&self) -> Result<usize, PopError> {
+        // Claiming every position up to the tail makes producers that got a position before the
+        // queue was closed either finish or give up.
+        if let Some(index) = self.take_item(true) {
+            return Ok(index);
+        }
+
+        // Reserved slots are published even though the queue is closed, so look again once
+        // there are none left.
+        if load!(self.reserved) != 0 {
+            return Err(PopError::Empty);
+        }
+        self.take_item(true).ok_or(PopError::Closed)
+    }
+
+    /// Pops up to `max` items from the head of the queue.
+    ///
+    /// Stops when the queue is empty. Returns the number of items passed to `f`.
+    pub fn pop_batch<F: FnMut(T)>(&self, max: usize, mut f: F) -> usize {
+        let mut count = 0;
+
+        while count < max {
+            match self.take_item(false) {
+                Some(index) => {
+                    self.metrics.pop();
+                    self.metrics.pop_latency(&self.slots[index].pushed_at);
+                    f(ReadSlot {
+                        queue: self,
+                        index,
+                        held: false,
+                    }
+                    .into_inner());
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+
+        count
+    }
+
+    /// Returns how long the item at the head of the queue has been in it.
+    ///
+    /// This does not hold back consumers, so if the item is popped in the meantime, the age of a
+    /// newer item may be returned instead.
+    #[cfg(feature = "latency")]
+    pub fn oldest_item_age(&self) -> Option<Duration> {
+        let ring = &self.allocated;
+        let mut pos = ring.head.load(Ordering::Relaxed) >> 1;
+        let tail = ring.tail.load(Ordering::Relaxed) >> 1;
+
+        // Skip the entries that are empty, removed or not written yet.
+        while before(pos, tail) {
+            let e = ring.entry(pos).load(Ordering::Acquire);
+            if ring.cycle_of(e) == ring.cycle(pos) && e & ring.half() == 0 {
+                let slot = &self.slots[e & (ring.half() - 1)];
+                if !slot.removed.load(Ordering::Relaxed) {
+                    return Some(slot.pushed_at.age());
+                }
+            }
+            pos = (pos + 1) & POS_MASK;
+        }
+
+        None
+    }
+
+    /// Stops consumers from using the entries they claim until the returned guard is dropped.
+    ///
+    /// While the head is frozen, the items between the head and the tail stay in place.
+    fn freeze(&self) -> Frozen<'_, T> {
+        let mut head = self.allocated.head.load(Ordering::Relaxed);
+        let mut spin = Spin::new(self);
+
+        loop {
+            // Wait until other inspections are done.
+            if head & FROZEN != 0 {
+                spin.wait();
+                head = self.allocated.head.load(Ordering::Relaxed);
+                continue;
+            }
+
+            match self.allocated.head.compare_exchange_weak(
+                head,
+                head | FROZEN,
+                Ordering::SeqCst,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Frozen {
+                        queue: self,
+                        head: head >> 1,
+                    }
+                }
+                Err(h) => {
+                    self.metrics.cas_retry();
+                    head = h;
+                }
+            }
+        }
+    }
+
+    /// Moves the frozen head past the emptied entries and the removed items at the head of the
+    /// queue, freeing the slots of the removed items.
+    ///
+    /// This only happens if no consumer has claimed a position since the head was frozen. Returns
+    /// `true` if any slot was freed.
+    fn skip_head(&self, frozen: &mut Frozen<'_, T>) -> bool {
+        let ring = &self.allocated;
+        let half = ring.half();
+        let tail = load!(ring.tail) >> 1;
+        let mut pos = frozen.head;
+
+        // Find the first entry that holds an item or is not written yet.
+        while before(pos, tail) {
+            let e = ring.entry(pos).load(Ordering::Acquire);
+            if ring.cycle_of(e) != ring.cycle(pos)
+                || (e & half == 0 && !self.slots[e & (half - 1)].removed.load(Ordering::Relaxed))
+            {
+                break;
+            }
+            pos = (pos + 1) & POS_MASK;
+        }
+
+        if pos == frozen.head
+            || ring
+                .head
+                .compare_exchange(
+                    (frozen.head << 1) | FROZEN,
+                    (pos << 1) | FROZEN,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return false;
+        }
+
+        // The skipped positions are ours now, so take out the removed items and free their slots.
+        let mut freed = false;
+        while frozen.head != pos {
+            let e = ring.entry(frozen.head).fetch_or(half, Ordering::AcqRel);
+            if e & half == 0 {
+                let index = e & (half - 1);
+                self.free_removed(&self.slots[index], index);
+                freed = true;
+            }
+            frozen.head = (frozen.head + 1) & POS_MASK;
+        }
+        freed
+    }
+
+    /// Calls `f` on the entry and the slot of every item between the frozen head and the tail.
+    ///
+    /// Removed items are left out.
+    fn for_each_item<'a, F>(&'a self, frozen: &Frozen<'_, T>, mut f: F)
+    where
+        F: FnMut(&'a AtomicUsize, &'a Slot<T>),
+    {
+        let ring = &self.allocated;
+        let tail = load!(ring.tail) >> 1;
+        let mut pos = frozen.head;
+
+        while before(pos, tail) {
+            let entry = ring.entry(pos);
+            let e = entry.load(Ordering::Acquire);
+
+            if ring.cycle_of(e) == ring.cycle(pos) && e & ring.half() == 0 {
+                let slot = &self.slots[e & (ring.half() - 1)];
+                if !slot.removed.load(Ordering::Relaxed) {
+                    f(entry, slot);
+                }
+            }
+
+            pos = (pos + 1) & POS_MASK;
+        }
+    }
+
+    /// Calls `f` with the items in the queue, from the head to the tail, without popping them.
+    ///
+    /// Items that are still being pushed are left out. Consumers wait until this returns.
+    pub fn with_items<R, F: FnOnce(&[&T]) -> R>(&self, f: F) -> R {
+        let frozen = self.freeze();
+        let mut items = Vec::new();
+
+        self.for_each_item(&frozen, |_, slot| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            items.push(
+                slot.value
+                    .with_mut(|slot| unsafe { (*slot).assume_init_ref() }),
+            );
+        });
+
+        f(&items)
+    }
+
+    /// Removes the items for which `pred` returns `true` and returns them.
+    ///
+    /// The slots of removed items are freed once consumers reach them, like abandoned
+    /// reservations. Items that are still being pushed are kept. Consumers wait while `pred` runs.
+    pub fn remove_if<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Vec<T> {
+        let frozen = self.freeze();
+        let mut removed = Vec::new();
+        #[cfg(feature = "seq")]
+        let mut lost = 0;
+
+        self.for_each_item(&frozen, |_, slot| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            let value = slot.value.with_mut(|value| unsafe {
+                if pred((*value).assume_init_ref()) {
+                    Some(value.read().assume_init())
+                } else {
+                    None
+                }
+            });
+
+            // The items lost before a removed item are counted before the next item instead.
+            // SAFETY: The slot can't be popped while the head is frozen.
+            #[cfg(feature = "seq")]
+            slot.lost.with_mut(|slot| unsafe {
+                if value.is_some() {
+                    lost += *slot;
+                } else {
+                    *slot += mem::replace(&mut lost, 0);
+                }
+            });
+
+            if let Some(value) = value {
+                self.mark_removed(slot);
+                removed.push(value);
+            }
+        });
+
+        #[cfg(feature = "seq")]
+        self.lost.fetch_add(lost, Ordering::Relaxed);
+        drop(frozen);
+        removed
+    }
+
+    /// Records the number of items in the queue after a push.
+    #[cfg(feature = "metrics")]
+    fn record_push(&self) {
+        self.metrics.occupancy(self.len().max(1));
+    }
+
+    /// Returns the number of items in the queue.
+    pub fn len(&self) -> usize {
+        // The allocated ring counts positions that were skipped, and the free ring counts the
+        // slots that are being pushed into or popped from as taken, so take the smaller count.
+        let cap = self.capacity();
+        let free = self.free.len().min(cap);
+        self.allocated.len().min(cap - free)
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Returns the capacity of the queue.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue.
+    pub fn close(&self) -> bool {
+        self.allocated.tail.fetch_or(CLOSED, Ordering::SeqCst) & CLOSED == 0
+    }
+
+    /// Returns `true` if the queue is closed.
+    pub fn is_closed(&self) -> bool {
+        load!(self.allocated.tail) & CLOSED != 0
+    }
+}
+
+/// Returns `true` if the cycle bits `a` come before the cycle bits `b`.
+fn before_cycle(a: usize, b: usize) -> bool {
+    (a.wrapping_sub(b) as isize) < 0
+}
+
+/// A guard that keeps the items of an [`Scq`] in place.
+struct Frozen<'a, T> {
+    /// The frozen queue.
+    queue: &'a Scq<T>,
+
+    /// The position of the head, without the flag.
+    head: usize,
+}
+
+impl<T> Drop for Frozen<'_, T> {
+    fn drop(&mut self) {
+        self.queue
+            .allocated
+            .head
+            .fetch_and(!FROZEN, Ordering::Release);
+    }
+}
+
+impl<T> Instrumented for Scq<T> {
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity())
+    }
+}
+
+impl<T> Drop for Scq<T> {
+    fn drop(&mut self) {
+        // Pop every item that is left and drop it.
+        while let Some(index) = self.take_item(true) {
+            // SAFETY: The slot holds an item, and we have exclusive access to the queue.
+            self.slots[index].value.with_mut(|slot| unsafe {
+                let value = &mut *slot;
+                value.as_mut_ptr().drop_in_place();
+            });
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Popping from whichever of several queues has an item.
+
+use core::fmt;
+
+use crate::{ConcurrentQueue, PopError};
+
+/// Attempts to pop an item from the first of `queues` that has one.
+///
+/// Returns the item along with the index of the queue it came from. Queues earlier in the slice
+/// are always tried first, so a busy queue can starve the ones after it. Use [`Select::fair`] to
+/// take turns instead.
+///
+/// If every queue is empty, an error is returned, which is [`PopError::Closed`] only if every
+/// queue is closed as well. Like [`ConcurrentQueue::pop()`], this never waits for an item.
+///
+/// # Examples
+///
+/// ```
+/// use concurrent_queue::{pop_any, ConcurrentQueue, PopError};
+///
+/// let control = ConcurrentQueue::unbounded();
+/// let data = ConcurrentQueue::unbounded();
+///
+/// data.push("data").unwrap();
+/// control.push("stop").unwrap();
+///
+/// // The control queue comes first.
+/// assert_eq!(pop_any(&[&control, &data]), Ok((0, "stop")));
+/// assert_eq!(pop_any(&[&control, &data]), Ok((1, "data")));
+/// assert_eq!(pop_any(&[&control, &data]), Err(PopError::Empty));
+/// ```
+pub fn pop_any<T>(queues: &[&ConcurrentQueue<T>]) -> Result<(usize, T), PopError> {
+    Select::biased(queues).pop()
+}
+
+/// Pops items from several queues, either in order of preference or taking turns.
+///
+/// # Examples
+///
+/// ```
+/// use concurrent_queue::{ConcurrentQueue, Select};
+///
+/// let a = ConcurrentQueue::unbounded();
+/// let b = ConcurrentQueue::unbounded();
+/// for i in 0..3 {
+///     a.push(i).unwrap();
+///     b.push(i + 10).unwrap();
+/// }
+///
+/// let queues = [&a, &b];
+/// let mut select = Select::fair(&queues);
+/// let items: Vec<_> = std::iter::from_fn(|| select.pop().ok()).collect();
+/// assert_eq!(items, [(0, 0), (1, 10), (0, 1), (1, 11), (0, 2), (1, 12)]);
+/// ```
+pub struct Select<'a, T> {
+    /// The queues to pop from.
+    queues: &'a [&'a ConcurrentQueue<T>],
+
+    /// Whether the queues take turns.
+    fair: bool,
+
+    /// The index of the queue to try first.
+    next: usize,
+}
+
+impl<'a, T> Select<'a, T> {
+    /// Creates a selector that always tries the queues in the order they are given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, Select};
+    ///
+    /// let a = ConcurrentQueue::unbounded();
+    /// let b = ConcurrentQueue::unbounded();
+    /// a.push(1).unwrap();
+    /// a.push(2).unwrap();
+    /// b.push(3).unwrap();
+    ///
+    /// let queues = [&a, &b];
+    /// let mut select = Select::biased(&queues);
+    /// assert_eq!(select.pop(), Ok((0, 1)));
+    /// assert_eq!(select.pop(), Ok((0, 2)));
+    /// assert_eq!(select.pop(), Ok((1, 3)));
+    /// ```
+    pub fn biased(queues: &'a [&'a ConcurrentQueue<T>]) -> Select<'a, T> {
+        Select {
+            queues,
+            fair: false,
+            next: 0,
+        }
+    }
+
+    /// Creates a selector that takes turns between the queues.
+    ///
+    /// After an item is popped from a queue, the following queue is tried first the next time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, Select};
+    ///
+    /// let a = ConcurrentQueue::unbounded();
+    /// let b = ConcurrentQueue::unbounded();
+    /// a.push(1).unwrap();
+    /// a.push(2).unwrap();
+    /// b.push(3).unwrap();
+    ///
+    /// let queues = [&a, &b];
+    /// let mut select = Select::fair(&queues);
+    /// assert_eq!(select.pop(), Ok((0, 1)));
+    /// assert_eq!(select.pop(), Ok((1, 3)));
+    /// assert_eq!(select.pop(), Ok((0, 2)));
+    /// ```
+    pub fn fair(queues: &'a [&'a ConcurrentQueue<T>]) -> Select<'a, T> {
+        Select {
+            queues,
+            fair: true,
+            next: 0,
+        }
+    }
+
+    /// Attempts to pop an item from one of the queues.
+    ///
+    /// Returns the item along with the index of the queue it came from. If every queue is empty,
+    /// an error is returned, which is [`PopError::Closed`] only if every queue is closed as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::{ConcurrentQueue, PopError, Select};
+    ///
+    /// let a = ConcurrentQueue::<i32>::unbounded();
+    /// let b = ConcurrentQueue::unbounded();
+    /// b.push(1).unwrap();
+    /// a.close();
+    ///
+    /// let queues = [&a, &b];
+    /// let mut select = Select::fair(&queues);
+    /// assert_eq!(select.pop(), Ok((1, 1)));
+    /// assert_eq!(select.pop(), Err(PopError::Empty));
+    ///
+    /// b.close();
+    /// assert_eq!(select.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&mut self) -> Result<(usize, T), PopError> {
+        let count = self.queues.len();
+        let mut closed = true;
+
+        for offset in 0..count {
+            let index = (self.next + offset) % count;
+            match self.queues[index].pop() {
+                Ok(value) => {
+                    if self.fair {
+                        self.next = (index + 1) % count;
+                    }
+                    return Ok((index, value));
+                }
+                Err(PopError::Empty) => closed = false,
+                Err(PopError::Closed) => {}
+            }
+        }
+
+        if closed {
+            Err(PopError::Closed)
+        } else {
+            Err(PopError::Empty)
+        }
+    }
+}
+
+impl<T> fmt::Debug for Select<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Select")
+            .field("queues", &self.queues.len())
+            .field("fair", &self.fair)
+            .field("next", &self.next)
+            .finish()
+    }
+}
@@ -0,0 +1,91 @@
+//! `serde` support for [`ConcurrentQueue`], enabled by the `serde` feature.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::ConcurrentQueue;
+
+/// The serialized form of a queue.
+#[derive(serde::Serialize)]
+#[serde(rename = "ConcurrentQueue")]
+struct QueueRef<'a, T> {
+    capacity: Option<usize>,
+    closed: bool,
+    items: &'a [&'a T],
+}
+
+/// The deserialized form of a queue.
+#[derive(serde::Deserialize)]
+#[serde(rename = "ConcurrentQueue")]
+struct Queue<T> {
+    capacity: Option<usize>,
+    closed: bool,
+    items: Vec<T>,
+}
+
+/// Consumers wait until the items are serialized, so that they make up a consistent snapshot, as
+/// with [`ConcurrentQueue::snapshot()`]. The serializer must not pop from the queue or inspect it
+/// with another method that holds consumers back, which would deadlock.
+impl<T: Serialize> Serialize for ConcurrentQueue<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.with_items(|items| {
+            QueueRef {
+                capacity: self.capacity(),
+                closed: self.is_closed(),
+                items,
+            }
+            .serialize(serializer)
+        })
+    }
+}
+
+/// The queue is created with [`ConcurrentQueue::bounded()`] or [`ConcurrentQueue::unbounded()`], so
+/// it uses [`OverflowPolicy::Reject`](crate::OverflowPolicy::Reject) and has no name, whatever the
+/// policy and name of the serialized queue were.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ConcurrentQueue<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Queue {
+            capacity,
+            closed,
+            items,
+        } = Queue::deserialize(deserializer)?;
+
+        let queue = match capacity {
+            Some(0) => {
+                return Err(de::Error::invalid_value(
+                    de::Unexpected::Unsigned(0),
+                    &"a positive capacity",
+                ))
+            }
+            Some(cap) if items.len() > cap => {
+                return Err(de::Error::invalid_length(items.len(), &Capacity(cap)))
+            }
+            Some(cap) => ConcurrentQueue::bounded(cap),
+            None => ConcurrentQueue::unbounded(),
+        };
+
+        for item in items {
+            // The queue is new and has room for every item.
+            if queue.push(item).is_err() {
+                unreachable!();
+            }
+        }
+
+        if closed {
+            queue.close();
+        }
+        Ok(queue)
+    }
+}
+
+/// The number of items expected by a bounded queue.
+struct Capacity(usize);
+
+impl de::Expected for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at most {} items", self.0)
+    }
+}
@@ -0,0 +1,421 @@
+//! A queue spread over several shards to reduce contention.
+//!
+//! A [`ConcurrentQueue`] has a single head and a single tail that every producer and consumer
+//! competes for. With many threads, these become a bottleneck. A [`ShardedQueue`] keeps several
+//! queues, called shards, and spreads pushes and pops over them according to a [`ShardPolicy`].
+//!
+//! In exchange, the queue is only FIFO within each shard: items in different shards may be popped
+//! in any order. With [`ShardPolicy::Thread`], the items a thread pushes are popped in the order
+//! they were pushed as long as its shard has room for them.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::sharded::ShardedQueue;
+//!
+//! let q = ShardedQueue::unbounded(4);
+//!
+//! q.push(1).unwrap();
+//! q.push(2).unwrap();
+//! assert_eq!(q.len(), 2);
+//!
+//! // Both items were pushed by this thread, so they are in the same shard.
+//! assert_eq!(q.pop(), Ok(1));
+//! assert_eq!(q.pop(), Ok(2));
+//! ```
+
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::fmt;
+#[cfg(loom)]
+use loom::thread_local;
+#[cfg(not(loom))]
+use std::thread_local;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// How a [`ShardedQueue`] picks the shard to push into or pop from.
+///
+/// Whatever the policy, a push moves on to the other shards if the picked one is full, and a pop
+/// moves on to the other shards if the picked one is empty.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ShardPolicy {
+    /// Each thread pushes into and pops from a shard of its own, shared with other threads only
+    /// if there are more threads than shards.
+    ///
+    /// This is the default.
+    Thread,
+
+    /// Two shards are picked at random, and a push goes into the shorter one while a pop takes
+    /// from the longer one.
+    ///
+    /// This keeps the shards balanced even if some threads push or pop much more than others.
+    ChoiceOfTwo,
+}
+
+impl Default for ShardPolicy {
+    fn default() -> Self {
+        ShardPolicy::Thread
+    }
+}
+
+/// A concurrent queue spread over several shards.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct ShardedQueue<T> {
+    /// The shards.
+    shards: Box<[ConcurrentQueue<T>]>,
+
+    /// How shards are picked.
+    policy: ShardPolicy,
+}
+
+impl<T> ShardedQueue<T> {
+    /// Creates a new queue with `shards` shards, each holding at most `cap` items.
+    ///
+    /// # Panics
+    ///
+    /// If the number of shards or the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::<i32>::bounded(8, 100);
+    /// ```
+    pub fn bounded(shards: usize, cap: usize) -> ShardedQueue<T> {
+        ShardedQueue::new(shards, || ConcurrentQueue::bounded(cap))
+    }
+
+    /// Creates a new queue with `shards` shards of unlimited capacity.
+    ///
+    /// # Panics
+    ///
+    /// If the number of shards is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(8);
+    /// ```
+    pub fn unbounded(shards: usize) -> ShardedQueue<T> {
+        ShardedQueue::new(shards, ConcurrentQueue::unbounded)
+    }
+
+    /// Creates a queue with `shards` shards built by `shard`.
+    fn new(shards: usize, shard: impl FnMut() -> ConcurrentQueue<T>) -> ShardedQueue<T> {
+        assert!(shards > 0, "number of shards must be positive");
+
+        ShardedQueue {
+            shards: core::iter::repeat_with(shard).take(shards).collect(),
+            policy: ShardPolicy::default(),
+        }
+    }
+
+    /// Sets how shards are picked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::{ShardPolicy, ShardedQueue};
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(8).with_policy(ShardPolicy::ChoiceOfTwo);
+    /// assert_eq!(q.policy(), ShardPolicy::ChoiceOfTwo);
+    /// ```
+    pub fn with_policy(mut self, policy: ShardPolicy) -> ShardedQueue<T> {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns how shards are picked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::{ShardPolicy, ShardedQueue};
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(8);
+    /// assert_eq!(q.policy(), ShardPolicy::Thread);
+    /// ```
+    pub fn policy(&self) -> ShardPolicy {
+        self.policy
+    }
+
+    /// Attempts to push an item into the queue.
+    ///
+    /// If every shard is full or the queue is closed, the item is returned back as an error.
+    ///
+    /// A shard closed by a [`close()`][`ShardedQueue::close()`] that is still in progress is
+    /// skipped like a full one, so [`PushError::Closed`] is only returned once
+    /// [`is_closed()`][`ShardedQueue::is_closed()`] is `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    /// use concurrent_queue::PushError;
+    ///
+    /// let q = ShardedQueue::bounded(2, 1);
+    ///
+    /// // Once this thread's shard is full, items go into the other one.
+    /// q.push(10).unwrap();
+    /// q.push(20).unwrap();
+    /// assert_eq!(q.push(30), Err(PushError::Full(30)));
+    ///
+    /// q.close();
+    /// assert_eq!(q.push(40), Err(PushError::Closed(40)));
+    /// ```
+    pub fn push(&self, mut value: T) -> Result<(), PushError<T>> {
+        let count = self.shards.len();
+        let first = match self.policy {
+            ShardPolicy::Thread => thread_index() % count,
+            ShardPolicy::ChoiceOfTwo => {
+                let (a, b) = (random(count), random(count));
+                if self.shards[a].len() <= self.shards[b].len() {
+                    a
+                } else {
+                    b
+                }
+            }
+        };
+
+        for offset in 0..count {
+            match self.shards[(first + offset) % count].push(value) {
+                Ok(()) => return Ok(()),
+                Err(PushError::Full(v) | PushError::Closed(v)) => value = v,
+            }
+        }
+
+        if self.is_closed() {
+            Err(PushError::Closed(value))
+        } else {
+            Err(PushError::Full(value))
+        }
+    }
+
+    /// Attempts to pop an item from the queue.
+    ///
+    /// If every shard is empty, an error is returned, which is [`PopError::Closed`] once the queue
+    /// is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    /// use concurrent_queue::PopError;
+    ///
+    /// let q = ShardedQueue::unbounded(4);
+    /// q.push(10).unwrap();
+    /// q.close();
+    ///
+    /// assert_eq!(q.pop(), Ok(10));
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        let count = self.shards.len();
+        let first = match self.policy {
+            ShardPolicy::Thread => thread_index() % count,
+            ShardPolicy::ChoiceOfTwo => {
+                let (a, b) = (random(count), random(count));
+                if self.shards[a].len() >= self.shards[b].len() {
+                    a
+                } else {
+                    b
+                }
+            }
+        };
+
+        let mut closed = true;
+        for offset in 0..count {
+            match self.shards[(first + offset) % count].pop() {
+                Ok(value) => return Ok(value),
+                Err(PopError::Empty) => closed = false,
+                Err(PopError::Closed) => {}
+            }
+        }
+
+        if closed {
+            Err(PopError::Closed)
+        } else {
+            Err(PopError::Empty)
+        }
+    }
+
+    /// Returns the number of shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(8);
+    /// assert_eq!(q.shards(), 8);
+    /// ```
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of items in the queue, across all shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::unbounded(4);
+    /// q.push(1).unwrap();
+    /// q.push(2).unwrap();
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(ConcurrentQueue::len).sum()
+    }
+
+    /// Returns `true` if every shard is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::unbounded(4);
+    /// assert!(q.is_empty());
+    ///
+    /// q.push(1).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(ConcurrentQueue::is_empty)
+    }
+
+    /// Returns `true` if every shard is full.
+    ///
+    /// An unbounded queue is never full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::bounded(2, 1);
+    /// q.push(1).unwrap();
+    /// assert!(!q.is_full());
+    ///
+    /// q.push(2).unwrap();
+    /// assert!(q.is_full());
+    /// ```
+    pub fn is_full(&self) -> bool {
+        self.shards.iter().all(ConcurrentQueue::is_full)
+    }
+
+    /// Returns the total capacity of the shards, or `None` if they are unbounded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// assert_eq!(ShardedQueue::<i32>::bounded(4, 8).capacity(), Some(32));
+    /// assert_eq!(ShardedQueue::<i32>::unbounded(4).capacity(), None);
+    /// ```
+    pub fn capacity(&self) -> Option<usize> {
+        self.shards[0].capacity().map(|cap| cap * self.shards.len())
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// When a queue is closed, no more items can be pushed into any shard, but the remaining items
+    /// can still be popped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(4);
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    /// ```
+    pub fn close(&self) -> bool {
+        // The first shard is closed last, so once it is closed, so is every other shard.
+        for shard in &self.shards[1..] {
+            shard.close();
+        }
+        self.shards[0].close()
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::sharded::ShardedQueue;
+    ///
+    /// let q = ShardedQueue::<i32>::unbounded(4);
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.shards[0].is_closed()
+    }
+}
+
+impl<T> fmt::Debug for ShardedQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedQueue")
+            .field("shards", &self.shards())
+            .field("policy", &self.policy)
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// The index handed out to the next thread that picks a shard.
+#[cfg(not(loom))]
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(loom)]
+loom::lazy_static! {
+    /// The index handed out to the next thread that picks a shard.
+    static ref NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+}
+
+thread_local! {
+    /// The index of the current thread and the state of its random number generator.
+    static LOCAL: (usize, Cell<u64>) = {
+        let index = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+
+        // Spread out the bits of the index with SplitMix64, so that threads get unrelated seeds.
+        let mut seed = (index as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (index, Cell::new((seed ^ (seed >> 31)) | 1))
+    };
+}
+
+/// Returns the index of the current thread.
+fn thread_index() -> usize {
+    LOCAL.with(|local| local.0)
+}
+
+/// Returns a random number below `n`.
+fn random(n: usize) -> usize {
+    LOCAL.with(|local| {
+        // Xorshift64*.
+        let mut x = local.1.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        local.1.set(x);
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % n
+    })
+}
@@ -1,20 +1,171 @@
-use core::mem::MaybeUninit;
+use alloc::{vec, vec::Vec};
+use core::mem::{self, MaybeUninit};
 use core::ptr;
+#[cfg(feature = "latency")]
+use core::time::Duration;
 
+use crate::metrics::{Instrumented, Metrics, Spin, Timestamp};
 use crate::sync::atomic::{AtomicUsize, Ordering};
 use crate::sync::cell::UnsafeCell;
 #[allow(unused_imports)]
 use crate::sync::prelude::*;
-use crate::{busy_wait, ForcePushError, PopError, PushError};
+use crate::{PopError, PushError, TryPopError, TryPushError};
 
 const LOCKED: usize = 1 << 0;
 const PUSHED: usize = 1 << 1;
 const CLOSED: usize = 1 << 2;
+// Set along with `LOCKED` while the slot is held by a `WriteSlot` returned from `reserve` or a
+// `ReadSlot` returned from `pop_ref`.
+const HELD: usize = 1 << 3;
+
+/// A slot reserved for writing by [`Single::reserve`].
+///
+/// The slot stays locked until it is committed or dropped, and is marked as held so that producers
+/// don't wait for it.
+pub struct WriteSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Single<T>,
+}
+
+impl<T> WriteSlot<'_, T> {
+    /// Returns the uninitialized value in the slot.
+    pub fn value_mut(&mut self) -> &mut MaybeUninit<T> {
+        // SAFETY: We have locked the state.
+        self.queue.slot.with_mut(|slot| unsafe { &mut *slot })
+    }
+
+    /// Returns the sequence number of the value written into the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: We have locked the state.
+        self.queue.seq.with_mut(|seq| unsafe { *seq })
+    }
+
+    /// Writes a value into the slot and publishes it.
+    pub fn write(mut self, value: T) {
+        self.value_mut().write(value);
+        // SAFETY: The value was just initialized.
+        unsafe { self.commit() }
+    }
+
+    /// Publishes the value in the slot.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    pub unsafe fn commit(self) {
+        // SAFETY: We have locked the state.
+        #[cfg(feature = "seq")]
+        self.queue.seq.with_mut(|seq| *seq += 1);
+        self.queue.metrics.push();
+        #[cfg(feature = "metrics")]
+        self.queue.metrics.occupancy(1);
+        self.queue.pushed_at.set();
+        self.queue
+            .state
+            .fetch_and(!(LOCKED | HELD), Ordering::Release);
+        mem::forget(self);
+    }
+}
+
+impl<T> Drop for WriteSlot<'_, T> {
+    fn drop(&mut self) {
+        // The reservation uses up a sequence number even though nothing is pushed.
+        // SAFETY: We have locked the state.
+        #[cfg(feature = "seq")]
+        self.queue.seq.with_mut(|seq| unsafe { *seq += 1 });
+
+        // Unlock and empty the slot.
+        self.queue
+            .state
+            .fetch_and(!(LOCKED | PUSHED | HELD), Ordering::Release);
+    }
+}
+
+/// A slot claimed for reading by [`Single::pop_ref`].
+///
+/// The slot stays locked until this is dropped, and is marked as held so that producers don't wait
+/// for it.
+pub struct ReadSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Single<T>,
+
+    /// The number of values displaced right before the value in the slot.
+    #[cfg(feature = "seq")]
+    lost: u64,
+}
+
+impl<T> ReadSlot<'_, T> {
+    /// Returns the sequence number of the value in the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: We have locked the state, and at least one value has been pushed.
+        self.queue.seq.with_mut(|seq| unsafe { *seq - 1 })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value(&self) -> &T {
+        // SAFETY: The slot is initialized and we have locked the state.
+        self.queue
+            .slot
+            .with_mut(|slot| unsafe { (*slot).assume_init_ref() })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The slot is initialized and we have locked the state.
+        self.queue
+            .slot
+            .with_mut(|slot| unsafe { (*slot).assume_init_mut() })
+    }
+
+    /// Moves the value out of the slot and unlocks it.
+    pub fn into_inner(self) -> T {
+        // SAFETY: The slot is initialized and we have locked the state.
+        let value = self
+            .queue
+            .slot
+            .with_mut(|slot| unsafe { slot.read().assume_init() });
+        self.queue
+            .state
+            .fetch_and(!(LOCKED | HELD), Ordering::Release);
+        mem::forget(self);
+        value
+    }
+}
+
+impl<T> Drop for ReadSlot<'_, T> {
+    fn drop(&mut self) {
+        // Drop the value and unlock.
+        self.queue.slot.with_mut(|slot| unsafe {
+            let value = &mut *slot;
+            value.as_mut_ptr().drop_in_place();
+        });
+        self.queue
+            .state
+            .fetch_and(!(LOCKED | HELD), Ordering::Release);
+    }
+}
 
 /// A single-element queue.
 pub struct Single<T> {
     state: AtomicUsize,
     slot: UnsafeCell<MaybeUninit<T>>,
+
+    /// The number of values pushed so far, only accessed while the state is locked.
+    #[cfg(feature = "seq")]
+    seq: UnsafeCell<u64>,
+
+    /// The number of values displaced since the last pop, only accessed while the state is
+    /// locked.
+    #[cfg(feature = "seq")]
+    lost: UnsafeCell<u64>,
+
+    /// The time the value was pushed, only written while the state is locked.
+    pushed_at: Timestamp,
+
+    /// Counters for the operations on the queue.
+    metrics: Metrics,
 }
 
 impl<T> Single<T> {
@@ -23,35 +174,81 @@ impl<T> Single<T> {
         Single {
             state: AtomicUsize::new(0),
             slot: UnsafeCell::new(MaybeUninit::uninit()),
+            #[cfg(feature = "seq")]
+            seq: UnsafeCell::new(0),
+            #[cfg(feature = "seq")]
+            lost: UnsafeCell::new(0),
+            pushed_at: Timestamp::new(),
+            metrics: Metrics::new(),
         }
     }
 
     /// Attempts to push an item into the queue.
     pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        // The slot is written right away, so it doesn't need to be marked as held.
+        match self.lock(0) {
+            Ok(slot) => {
+                slot.write(value);
+                Ok(())
+            }
+            Err(PushError::Full(())) => Err(PushError::Full(value)),
+            Err(PushError::Closed(())) => Err(PushError::Closed(value)),
+        }
+    }
+
+    /// Reserves the slot for writing.
+    pub fn reserve(&self) -> Result<WriteSlot<'_, T>, PushError<()>> {
+        self.lock(HELD)
+    }
+
+    /// Locks and fills the slot, setting `held` in the state along with it.
+    fn lock(&self, held: usize) -> Result<WriteSlot<'_, T>, PushError<()>> {
         // Lock and fill the slot.
         let state = self
             .state
-            .compare_exchange(0, LOCKED | PUSHED, Ordering::SeqCst, Ordering::SeqCst)
+            .compare_exchange(
+                0,
+                LOCKED | PUSHED | held,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
             .unwrap_or_else(|x| x);
 
         if state == 0 {
-            // Write the value and unlock.
-            self.slot.with_mut(|slot| unsafe {
-                slot.write(MaybeUninit::new(value));
-            });
-            self.state.fetch_and(!LOCKED, Ordering::Release);
-            Ok(())
+            Ok(WriteSlot { queue: self })
         } else if state & CLOSED != 0 {
-            Err(PushError::Closed(value))
+            Err(PushError::Closed(()))
         } else {
-            Err(PushError::Full(value))
+            self.metrics.full();
+            Err(PushError::Full(()))
         }
     }
 
+    /// Reserves the slot for writing, reporting `Busy` if it is locked.
+    pub fn reserve_nowait(&self) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        // A locked slot is in the middle of a push or a pop, unless it is held.
+        if self.state.load(Ordering::SeqCst) & (LOCKED | HELD) == LOCKED {
+            return Err(TryPushError::Busy(()));
+        }
+        self.reserve().map_err(TryPushError::from)
+    }
+
+    /// Reserves the slot if `max` is not zero, returning it in a batch of one.
+    pub fn reserve_batch(&self, max: usize) -> Result<Vec<WriteSlot<'_, T>>, PushError<()>> {
+        if max == 0 {
+            return Ok(Vec::new());
+        }
+        self.reserve().map(|slot| vec![slot])
+    }
+
     /// Attempts to push an item into the queue, displacing another if necessary.
-    pub fn force_push(&self, value: T) -> Result<Option<T>, ForcePushError<T>> {
+    ///
+    /// If the slot is held by a [`ReadSlot`] or a [`WriteSlot`], `Busy` is returned instead of
+    /// waiting for the guard to be dropped.
+    pub fn force_push(&self, value: T) -> Result<Option<T>, TryPushError<T>> {
         // Attempt to lock the slot.
         let mut state = 0;
+        let mut spin = Spin::new(self);
 
         loop {
             // Lock the slot.
@@ -61,7 +258,12 @@ impl<T> Single<T> {
                 .unwrap_or_else(|x| x);
 
             if prev & CLOSED != 0 {
-                return Err(ForcePushError(value));
+                return Err(TryPushError::Closed(value));
+            }
+
+            // Don't wait for the slot to be released.
+            if prev & HELD != 0 {
+                return Err(TryPushError::Busy(value));
             }
 
             if prev == state {
@@ -80,9 +282,21 @@ impl<T> Single<T> {
                             ptr::replace(slot, MaybeUninit::new(value)).assume_init()
                         })
                     };
+                    self.metrics.displace();
+                    // SAFETY: We have locked the state.
+                    #[cfg(feature = "seq")]
+                    self.lost.with_mut(|lost| unsafe { *lost += 1 });
                     Some(prev_value)
                 };
 
+                // SAFETY: We have locked the state.
+                #[cfg(feature = "seq")]
+                self.seq.with_mut(|seq| unsafe { *seq += 1 });
+                self.metrics.push();
+                #[cfg(feature = "metrics")]
+                self.metrics.occupancy(1);
+                self.pushed_at.set();
+
                 // We can unlock the slot now.
                 self.state.fetch_and(!LOCKED, Ordering::Release);
 
@@ -92,10 +306,11 @@ impl<T> Single<T> {
 
             // Try to go for the current (pushed) state.
             if prev & LOCKED == 0 {
+                self.metrics.cas_retry();
                 state = prev;
             } else {
                 // State is locked.
-                busy_wait();
+                spin.wait();
                 state = prev & !LOCKED;
             }
         }
@@ -103,7 +318,96 @@ impl<T> Single<T> {
 
     /// Attempts to pop an item from the queue.
     pub fn pop(&self) -> Result<T, PopError> {
+        self.claim(false)
+            .map(ReadSlot::into_inner)
+            .map_err(TryPopError::into_pop_error)
+    }
+
+    /// Pops the item into `f` if `max` is not zero, returning the number of items popped.
+    ///
+    /// An item that is still being written is not waited for.
+    pub fn pop_batch<F: FnMut(T)>(&self, max: usize, mut f: F) -> usize {
+        if max == 0 {
+            return 0;
+        }
+
+        let mut state = self.state.load(Ordering::SeqCst);
+        while state & (LOCKED | PUSHED) == PUSHED {
+            // Lock and empty the slot.
+            match self.state.compare_exchange_weak(
+                state,
+                (state | LOCKED) & !PUSHED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    self.metrics.pop();
+                    self.metrics.pop_latency(&self.pushed_at);
+                    f(self.read_slot().into_inner());
+                    return 1;
+                }
+                Err(s) => {
+                    self.metrics.cas_retry();
+                    state = s;
+                }
+            }
+        }
+
+        0
+    }
+
+    /// Attempts to pop an item from the queue, reporting `Busy` instead of waiting.
+    pub fn pop_nowait(&self) -> Result<T, TryPopError> {
+        self.claim(true).map(ReadSlot::into_inner)
+    }
+
+    /// Attempts to pop an item from the queue, leaving it in the slot until the guard is dropped.
+    pub fn pop_ref(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+
+        // Let producers know that the slot is still in use.
+        self.state.fetch_or(HELD, Ordering::Relaxed);
+        Ok(slot)
+    }
+
+    /// Attempts to pop an item from the queue along with its sequence number.
+    #[cfg(feature = "seq")]
+    pub fn pop_seq(&self) -> Result<(u64, T), PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+        Ok((slot.seq(), slot.into_inner()))
+    }
+
+    /// Attempts to pop an item from the queue along with the number of items displaced right
+    /// before it.
+    #[cfg(feature = "seq")]
+    pub fn pop_with_loss(&self) -> Result<(T, u64), PopError> {
+        let slot = self.claim(false).map_err(TryPopError::into_pop_error)?;
+        let lost = slot.lost;
+        Ok((slot.into_inner(), lost))
+    }
+
+    /// Wraps the slot, which was just locked and emptied, in a guard.
+    ///
+    /// The count of displaced values starts over with the next value.
+    fn read_slot(&self) -> ReadSlot<'_, T> {
+        ReadSlot {
+            queue: self,
+            // SAFETY: We have locked the state.
+            #[cfg(feature = "seq")]
+            lost: self
+                .lost
+                .with_mut(|lost| unsafe { mem::replace(&mut *lost, 0) }),
+        }
+    }
+
+    /// Locks and empties the slot.
+    ///
+    /// If the slot is locked, this waits until it is unlocked, or reports `Busy` if `nowait` is
+    /// set.
+    fn claim(&self, nowait: bool) -> Result<ReadSlot<'_, T>, TryPopError> {
         let mut state = PUSHED;
+        let mut spin = Spin::new(self);
+
         loop {
             // Lock and empty the slot.
             let prev = self
@@ -117,31 +421,109 @@ impl<T> Single<T> {
                 .unwrap_or_else(|x| x);
 
             if prev == state {
-                // Read the value and unlock.
-                let value = self
-                    .slot
-                    .with_mut(|slot| unsafe { slot.read().assume_init() });
-                self.state.fetch_and(!LOCKED, Ordering::Release);
-                return Ok(value);
+                // The slot stays locked until the guard is dropped.
+                self.metrics.pop();
+                self.metrics.pop_latency(&self.pushed_at);
+                return Ok(self.read_slot());
             }
 
             if prev & PUSHED == 0 {
                 if prev & CLOSED == 0 {
-                    return Err(PopError::Empty);
+                    return Err(TryPopError::Empty);
                 } else {
-                    return Err(PopError::Closed);
+                    return Err(TryPopError::Closed);
                 }
             }
 
             if prev & LOCKED == 0 {
+                self.metrics.cas_retry();
                 state = prev;
+            } else if nowait {
+                return Err(TryPopError::Busy);
             } else {
-                busy_wait();
+                spin.wait();
                 state = prev & !LOCKED;
             }
         }
     }
 
+    /// Returns how long the item in the queue has been in it.
+    #[cfg(feature = "latency")]
+    pub fn oldest_item_age(&self) -> Option<Duration> {
+        // The value is only visible once it has been pushed and unlocked.
+        if self.state.load(Ordering::Acquire) & (LOCKED | PUSHED) == PUSHED {
+            Some(self.pushed_at.age())
+        } else {
+            None
+        }
+    }
+
+    /// Locks the slot if it holds an item that isn't being written, leaving the item in it.
+    fn lock_item(&self) -> Option<Locked<'_, T>> {
+        let mut state = self.state.load(Ordering::SeqCst);
+
+        loop {
+            if state & (LOCKED | PUSHED) != PUSHED {
+                return None;
+            }
+
+            match self.state.compare_exchange(
+                state,
+                state | LOCKED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    return Some(Locked {
+                        queue: self,
+                        clear: LOCKED,
+                    })
+                }
+                Err(s) => {
+                    self.metrics.cas_retry();
+                    state = s;
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with the item in the queue, if there is one, without popping it.
+    ///
+    /// The item is left out if it is still being written. Consumers wait until this returns.
+    pub fn with_items<R, F: FnOnce(&[&T]) -> R>(&self, f: F) -> R {
+        match self.lock_item() {
+            // SAFETY: The slot is initialized and we have locked the state.
+            Some(_locked) => f(&[self
+                .slot
+                .with_mut(|slot| unsafe { (*slot).assume_init_ref() })]),
+            None => f(&[]),
+        }
+    }
+
+    /// Removes the item in the queue if `pred` returns `true` and returns it.
+    ///
+    /// The item is kept if it is still being written. Consumers wait while `pred` runs.
+    pub fn remove_if<F: FnOnce(&T) -> bool>(&self, pred: F) -> Vec<T> {
+        if let Some(mut locked) = self.lock_item() {
+            // SAFETY: The slot is initialized and we have locked the state.
+            let value = self.slot.with_mut(|value| unsafe {
+                if pred((*value).assume_init_ref()) {
+                    Some(value.read().assume_init())
+                } else {
+                    None
+                }
+            });
+
+            if let Some(value) = value {
+                // Empty the slot as we unlock it.
+                locked.clear = LOCKED | PUSHED;
+                drop(locked);
+                return vec![value];
+            }
+        }
+        Vec::new()
+    }
+
     /// Returns the number of items in the queue.
     pub fn len(&self) -> usize {
         usize::from(self.state.load(Ordering::SeqCst) & PUSHED != 0)
@@ -171,12 +553,46 @@ impl<T> Single<T> {
     }
 }
 
+/// A guard that unlocks a [`Single`] queue locked by [`Single::lock_item`].
+struct Locked<'a, T> {
+    /// The locked queue.
+    queue: &'a Single<T>,
+
+    /// The bits to clear in the state when unlocking.
+    clear: usize,
+}
+
+impl<T> Drop for Locked<'_, T> {
+    fn drop(&mut self) {
+        self.queue.state.fetch_and(!self.clear, Ordering::Release);
+    }
+}
+
+impl<T> Instrumented for Single<T> {
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
 impl<T> Drop for Single<T> {
     fn drop(&mut self) {
         // Drop the value in the slot.
-        let Self { state, slot } = self;
+        let Self { state, slot, .. } = self;
         state.with_mut(|state| {
-            if *state & PUSHED != 0 {
+            // The slot is still locked if it was reserved but never written.
+            if *state & (LOCKED | PUSHED) == PUSHED {
                 slot.with_mut(|slot| unsafe {
                     let value = &mut *slot;
                     value.as_mut_ptr().drop_in_place();
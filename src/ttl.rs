@@ -0,0 +1,389 @@
+//! A queue whose items expire after a deadline.
+//!
+//! Every item in a [`TtlQueue`] is pushed with a deadline. Items whose deadline has passed are
+//! never handed to consumers: [`pop`](TtlQueue::pop) skips over them and passes them to the
+//! handler set by [`with_expiry_handler`](TtlQueue::with_expiry_handler), if there is one.
+//!
+//! This module is only available with the `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrent_queue::ttl::TtlQueue;
+//! use concurrent_queue::PopError;
+//! use std::time::{Duration, Instant};
+//!
+//! let q = TtlQueue::unbounded();
+//!
+//! q.push_until("stale", Instant::now()).unwrap();
+//! q.push_with_ttl("fresh", Duration::from_secs(60)).unwrap();
+//!
+//! // The stale item is skipped.
+//! assert_eq!(q.pop(), Ok("fresh"));
+//! assert_eq!(q.pop(), Err(PopError::Empty));
+//! assert_eq!(q.expired_count(), 1);
+//! ```
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::panic::{RefUnwindSafe, UnwindSafe};
+use core::time::Duration;
+
+use std::time::Instant;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use crate::{ConcurrentQueue, PopError, PushError};
+
+/// An item in a [`TtlQueue`].
+struct Entry<T> {
+    /// The time after which the item expires, or `None` if it never does.
+    deadline: Option<Instant>,
+
+    /// The item.
+    value: T,
+}
+
+impl<T> Entry<T> {
+    /// Returns `true` if the item has expired by `now`.
+    fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.map_or(false, |deadline| deadline <= now)
+    }
+}
+
+/// A concurrent queue whose items expire after a deadline.
+///
+/// See the [module-level documentation](self) for more details.
+pub struct TtlQueue<T> {
+    /// The underlying queue.
+    inner: ConcurrentQueue<Entry<T>>,
+
+    /// Receives items that expired while queued.
+    expire: Option<Box<dyn Fn(T) + Send + Sync>>,
+
+    /// The number of items that expired while queued.
+    expired: AtomicUsize,
+}
+
+impl<T> UnwindSafe for TtlQueue<T> {}
+impl<T> RefUnwindSafe for TtlQueue<T> {}
+
+impl<T> TtlQueue<T> {
+    /// Creates a new bounded queue.
+    ///
+    /// The queue allocates enough space for `cap` items, whether they have expired or not.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero, this constructor will panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::<i32>::bounded(100);
+    /// ```
+    pub fn bounded(cap: usize) -> TtlQueue<T> {
+        TtlQueue::from_queue(ConcurrentQueue::bounded(cap))
+    }
+
+    /// Creates a new unbounded queue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::<i32>::unbounded();
+    /// ```
+    pub fn unbounded() -> TtlQueue<T> {
+        TtlQueue::from_queue(ConcurrentQueue::unbounded())
+    }
+
+    /// Wraps an empty queue.
+    fn from_queue(inner: ConcurrentQueue<Entry<T>>) -> TtlQueue<T> {
+        TtlQueue {
+            inner,
+            expire: None,
+            expired: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sets a handler that receives items that expired while queued.
+    ///
+    /// Without a handler, expired items are simply dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Instant;
+    ///
+    /// let expired = Arc::new(Mutex::new(Vec::new()));
+    /// let q = TtlQueue::unbounded().with_expiry_handler({
+    ///     let expired = expired.clone();
+    ///     move |item| expired.lock().unwrap().push(item)
+    /// });
+    ///
+    /// q.push_until(1, Instant::now()).unwrap();
+    /// assert!(q.pop().is_err());
+    /// assert_eq!(*expired.lock().unwrap(), [1]);
+    /// ```
+    pub fn with_expiry_handler<F>(mut self, handler: F) -> TtlQueue<T>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        self.expire = Some(Box::new(handler));
+        self
+    }
+
+    /// Attempts to push an item that expires after `ttl`.
+    ///
+    /// If the queue is full or closed, the item is returned back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use std::time::Duration;
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push_with_ttl(10, Duration::from_secs(1)).unwrap();
+    /// assert_eq!(q.pop(), Ok(10));
+    /// ```
+    pub fn push_with_ttl(&self, value: T, ttl: Duration) -> Result<(), PushError<T>> {
+        self.push_entry(Instant::now().checked_add(ttl), value)
+    }
+
+    /// Attempts to push an item that expires at `deadline`.
+    ///
+    /// If the queue is full or closed, the item is returned back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use concurrent_queue::PopError;
+    /// use std::time::Instant;
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push_until(10, Instant::now()).unwrap();
+    /// assert_eq!(q.pop(), Err(PopError::Empty));
+    /// ```
+    pub fn push_until(&self, value: T, deadline: Instant) -> Result<(), PushError<T>> {
+        self.push_entry(Some(deadline), value)
+    }
+
+    /// Attempts to push an item that never expires.
+    ///
+    /// If the queue is full or closed, the item is returned back as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::bounded(1);
+    /// q.push(10).unwrap();
+    /// assert!(q.push(20).is_err());
+    /// ```
+    pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        self.push_entry(None, value)
+    }
+
+    /// Pushes an item with the given deadline into the underlying queue.
+    fn push_entry(&self, deadline: Option<Instant>, value: T) -> Result<(), PushError<T>> {
+        self.inner
+            .push(Entry { deadline, value })
+            .map_err(|err| match err {
+                PushError::Full(entry) => PushError::Full(entry.value),
+                PushError::Closed(entry) => PushError::Closed(entry.value),
+            })
+    }
+
+    /// Attempts to pop an item that has not expired.
+    ///
+    /// Expired items at the head of the queue are passed to the expiry handler along the way. If
+    /// there is no item left, an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use concurrent_queue::PopError;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push_until(1, Instant::now()).unwrap();
+    /// q.push_with_ttl(2, Duration::from_secs(60)).unwrap();
+    /// q.close();
+    ///
+    /// assert_eq!(q.pop(), Ok(2));
+    /// assert_eq!(q.pop(), Err(PopError::Closed));
+    /// ```
+    pub fn pop(&self) -> Result<T, PopError> {
+        loop {
+            let entry = self.inner.pop()?;
+            if entry.is_expired(Instant::now()) {
+                self.expire(entry.value);
+            } else {
+                return Ok(entry.value);
+            }
+        }
+    }
+
+    /// Removes every expired item from the queue and returns how many there were.
+    ///
+    /// [`pop`](Self::pop) only drops expired items as it reaches them. This passes all of them to
+    /// the expiry handler at once, keeping the order of the other items. See
+    /// [`ConcurrentQueue::retain`] for how it interacts with other operations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push(1).unwrap();
+    /// q.push_until(2, Instant::now()).unwrap();
+    /// q.push_with_ttl(3, Duration::from_secs(60)).unwrap();
+    ///
+    /// assert_eq!(q.purge_expired(), 1);
+    /// assert_eq!(q.pop(), Ok(1));
+    /// assert_eq!(q.pop(), Ok(3));
+    /// ```
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let expired = self.inner.remove_if(|entry| entry.is_expired(now));
+        let count = expired.len();
+
+        // The expiry handler runs after consumers are let through again.
+        for entry in expired {
+            self.expire(entry.value);
+        }
+        count
+    }
+
+    /// Counts an expired item and passes it to the expiry handler.
+    fn expire(&self, value: T) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+        if let Some(expire) = &self.expire {
+            expire(value);
+        }
+    }
+
+    /// Returns the number of items that expired while queued.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use std::time::Instant;
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push_until(1, Instant::now()).unwrap();
+    /// q.push_until(2, Instant::now()).unwrap();
+    /// assert_eq!(q.expired_count(), 0);
+    ///
+    /// assert!(q.pop().is_err());
+    /// assert_eq!(q.expired_count(), 2);
+    /// ```
+    pub fn expired_count(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed) as u64
+    }
+
+    /// Returns the number of items in the queue, including expired ones that have not been
+    /// dropped yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    /// use std::time::Instant;
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// q.push(1).unwrap();
+    /// q.push_until(2, Instant::now()).unwrap();
+    /// assert_eq!(q.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the queue holds no items, expired or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::unbounded();
+    /// assert!(q.is_empty());
+    ///
+    /// q.push(1).unwrap();
+    /// assert!(!q.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the capacity of the queue, or `None` if it is unbounded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// assert_eq!(TtlQueue::<i32>::bounded(7).capacity(), Some(7));
+    /// assert_eq!(TtlQueue::<i32>::unbounded().capacity(), None);
+    /// ```
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    /// Closes the queue.
+    ///
+    /// Returns `true` if this call closed the queue, or `false` if it was already closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::<i32>::unbounded();
+    /// assert!(q.close());
+    /// assert!(!q.close());
+    /// ```
+    pub fn close(&self) -> bool {
+        self.inner.close()
+    }
+
+    /// Returns `true` if the queue is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrent_queue::ttl::TtlQueue;
+    ///
+    /// let q = TtlQueue::<i32>::unbounded();
+    /// assert!(!q.is_closed());
+    /// q.close();
+    /// assert!(q.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl<T> fmt::Debug for TtlQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtlQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .field("is_closed", &self.is_closed())
+            .field("expired", &self.expired_count())
+            .finish()
+    }
+}
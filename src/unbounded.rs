@@ -1,34 +1,46 @@
-use alloc::boxed::Box;
-use core::mem::MaybeUninit;
+use alloc::{boxed::Box, vec::Vec};
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
 use core::ptr;
+#[cfg(feature = "latency")]
+use core::time::Duration;
 
 use crossbeam_utils::CachePadded;
 
 use crate::const_fn;
+use crate::metrics::{trace_event, Instrumented, Metrics, Spin, Timestamp};
 use crate::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
 use crate::sync::cell::UnsafeCell;
 #[allow(unused_imports)]
 use crate::sync::prelude::*;
-use crate::{busy_wait, PopError, PushError};
+use crate::{PopError, PushError, TryPopError, TryPushError};
 
 // Bits indicating the state of a slot:
 // * If a value has been written into the slot, `WRITE` is set.
 // * If a value has been read from the slot, `READ` is set.
 // * If the block is being destroyed, `DESTROY` is set.
+// * If the slot was reserved but never written into, or its value was removed, `SKIP` is set along
+//   with `WRITE`.
+// * If a consumer gave up waiting for a value to be written into the slot, `ORPHAN` is set. The
+//   producer then releases the slot and pushes the value again.
 const WRITE: usize = 1;
 const READ: usize = 2;
 const DESTROY: usize = 4;
+const SKIP: usize = 8;
+const ORPHAN: usize = 16;
 
 // Each block covers one "lap" of indices.
 const LAP: usize = 32;
 // The maximum number of items a block can hold.
 const BLOCK_CAP: usize = LAP - 1;
 // How many lower bits are reserved for metadata.
-const SHIFT: usize = 1;
+const SHIFT: usize = 2;
 // Has two different purposes:
 // * If set in head, indicates that the block is not the last one.
 // * If set in tail, indicates that the queue is closed.
 const MARK_BIT: usize = 1;
+// If set in head, indicates that consumers must wait because the slots are being inspected.
+const FROZEN: usize = 2;
 
 /// A slot in a block.
 struct Slot<T> {
@@ -37,6 +49,9 @@ struct Slot<T> {
 
     /// The state of the slot.
     state: AtomicUsize,
+
+    /// The time the value was pushed.
+    pushed_at: Timestamp,
 }
 
 impl<T> Slot<T> {
@@ -45,6 +60,7 @@ impl<T> Slot<T> {
     const UNINIT: Slot<T> = Slot {
         value: UnsafeCell::new(MaybeUninit::uninit()),
         state: AtomicUsize::new(0),
+        pushed_at: Timestamp::new(),
     };
 
     #[cfg(not(loom))]
@@ -68,13 +84,14 @@ impl<T> Slot<T> {
         repeat_31!(Slot {
             value: UnsafeCell::new(MaybeUninit::uninit()),
             state: AtomicUsize::new(0),
+            pushed_at: Timestamp::new(),
         })
     }
 
     /// Waits until a value is written into the slot.
-    fn wait_write(&self) {
+    fn wait_write(&self, spin: &mut Spin<'_, Unbounded<T>>) {
         while self.state.load(Ordering::Acquire) & WRITE == 0 {
-            busy_wait();
+            spin.wait();
         }
     }
 }
@@ -88,6 +105,13 @@ struct Block<T> {
 
     /// Slots for values.
     slots: [Slot<T>; BLOCK_CAP],
+
+    /// The sequence number of the first slot in the block.
+    ///
+    /// This is set before the block is installed, and counted separately from the positions in the
+    /// queue because those wrap around sooner on 32-bit targets.
+    #[cfg(feature = "seq")]
+    start: u64,
 }
 
 impl<T> Block<T> {
@@ -96,17 +120,37 @@ impl<T> Block<T> {
         Block {
             next: AtomicPtr::new(ptr::null_mut()),
             slots: Slot::uninit_block(),
+            #[cfg(feature = "seq")]
+            start: 0,
         }
     }
 
     /// Waits until the next pointer is set.
-    fn wait_next(&self) -> *mut Block<T> {
+    fn wait_next(&self, spin: &mut Spin<'_, Unbounded<T>>) -> *mut Block<T> {
         loop {
             let next = self.next.load(Ordering::Acquire);
             if !next.is_null() {
                 return next;
             }
-            busy_wait();
+            spin.wait();
+        }
+    }
+
+    /// Marks the slot at `offset` as read, destroying the block if needed.
+    unsafe fn release(this: *mut Block<T>, offset: usize) {
+        // Destroy the block if we've reached the end, or if another thread wanted to destroy but
+        // couldn't because we were busy reading from the slot.
+        if offset + 1 == BLOCK_CAP {
+            Block::destroy(this, 0);
+        } else if (*this)
+            .slots
+            .get_unchecked(offset)
+            .state
+            .fetch_or(READ, Ordering::AcqRel)
+            & DESTROY
+            != 0
+        {
+            Block::destroy(this, offset + 1);
         }
     }
 
@@ -131,6 +175,222 @@ impl<T> Block<T> {
     }
 }
 
+/// A slot reserved for writing by [`Unbounded::reserve`].
+///
+/// If it is dropped without being committed, `SKIP` is set in the slot so that consumers skip
+/// over it.
+pub struct WriteSlot<'a, T> {
+    /// The queue the slot belongs to.
+    queue: &'a Unbounded<T>,
+
+    /// The block containing the slot.
+    block: *mut Block<T>,
+
+    /// The offset of the slot in the block.
+    offset: usize,
+}
+
+impl<'a, T> WriteSlot<'a, T> {
+    /// Creates a guard for the reserved slot at position `index`, which is in `block`.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been reserved and the block must still be alive.
+    unsafe fn new(queue: &'a Unbounded<T>, block: *mut Block<T>, index: usize) -> Self {
+        WriteSlot {
+            queue,
+            block,
+            offset: index % LAP,
+        }
+    }
+
+    /// Returns the slot.
+    fn slot(&self) -> &Slot<T> {
+        // SAFETY: The block is not destroyed until the slot is released.
+        unsafe { (*self.block).slots.get_unchecked(self.offset) }
+    }
+
+    /// Returns the uninitialized value in the slot.
+    pub fn value_mut(&mut self) -> &mut MaybeUninit<T> {
+        // SAFETY: We have exclusive access to the slot until it is committed or dropped.
+        self.slot().value.with_mut(|slot| unsafe { &mut *slot })
+    }
+
+    /// Returns the sequence number of the value written into the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: The block is not destroyed until the slot is released.
+        unsafe { (*self.block).start + self.offset as u64 }
+    }
+
+    /// Writes a value into the slot and publishes it.
+    pub fn write(mut self, value: T) {
+        self.value_mut().write(value);
+        // SAFETY: The value was just initialized.
+        unsafe { self.commit() }
+    }
+
+    /// Writes a value into the slot and publishes it, returning the sequence number it ended up
+    /// with.
+    #[cfg(feature = "seq")]
+    pub fn write_seq(mut self, value: T) -> u64 {
+        self.value_mut().write(value);
+        // SAFETY: The value was just initialized.
+        unsafe { self.publish(Self::seq) }
+    }
+
+    /// Publishes the value in the slot.
+    ///
+    /// If the consumer of the slot gave up waiting for it, the value is moved to a new slot at the
+    /// tail, even if the queue was closed in the meantime. Consumers don't report the queue as
+    /// closed until that is done. This repeats for as long as consumers reach the new slot before
+    /// the value is published in it, which only takes a few steps, so it doesn't loop for long
+    /// unless consumers keep overtaking this producer.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    pub unsafe fn commit(self) {
+        self.publish(|_| ());
+    }
+
+    /// Publishes the value in the slot, returning what `f` reads from the slot it ends up in.
+    ///
+    /// `f` is called right before the value is published in each slot, since the block may be
+    /// destroyed as soon as a consumer takes the value out.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    unsafe fn publish<R>(self, f: impl Fn(&Self) -> R) -> R {
+        self.queue.metrics.push();
+        #[cfg(feature = "metrics")]
+        self.queue.record_push();
+
+        let mut this = self;
+        let mut moved = 0;
+        loop {
+            this.slot().pushed_at.set();
+            let result = f(&this);
+            if this.slot().state.fetch_or(WRITE, Ordering::Release) & ORPHAN == 0 {
+                // The value is at the tail now, so the consumers that skipped it can settle.
+                if moved > 0 {
+                    this.queue.unsettled.fetch_sub(moved, Ordering::SeqCst);
+                }
+                mem::forget(this);
+                return result;
+            }
+            this = this.move_to_tail();
+            moved += 1;
+        }
+    }
+
+    /// Moves the value in an orphaned slot to a new slot at the tail, releasing the old one.
+    ///
+    /// # Safety
+    ///
+    /// The value in the slot must be initialized.
+    unsafe fn move_to_tail(self) -> WriteSlot<'a, T> {
+        let (queue, block, offset) = (self.queue, self.block, self.offset);
+        let value = self.slot().value.with_mut(|slot| slot.read());
+        mem::forget(self);
+        Block::release(block, offset);
+
+        trace_event!(TRACE, queue, "pushing an orphaned item again");
+
+        // The value was already accepted, so push it even if the queue was closed since. A forced
+        // reservation ignores the closed bit, and without `nowait` it waits instead of reporting
+        // `Busy`, so it always succeeds.
+        let (block, index, _) = match queue.reserve_run(1, true, false) {
+            Ok(run) => run,
+            Err(_) => unreachable!("a forced reservation failed"),
+        };
+        let mut slot = WriteSlot::new(queue, block, index);
+        *slot.value_mut() = value;
+        slot
+    }
+}
+
+impl<T> Drop for WriteSlot<'_, T> {
+    fn drop(&mut self) {
+        // Let the consumer of this slot know that there is nothing in it. If the consumer already
+        // gave up on it, release the slot ourselves, and there is no value left to wait for.
+        if self.slot().state.fetch_or(WRITE | SKIP, Ordering::AcqRel) & ORPHAN != 0 {
+            self.queue.unsettled.fetch_sub(1, Ordering::SeqCst);
+            // SAFETY: The consumer left the slot to us.
+            unsafe { Block::release(self.block, self.offset) };
+        }
+    }
+}
+
+/// A slot claimed for reading by [`Unbounded::pop_ref`].
+///
+/// The slot is not marked as read until this is dropped, which keeps its block alive.
+pub struct ReadSlot<'a, T> {
+    /// The block containing the slot.
+    block: *mut Block<T>,
+
+    /// The offset of the slot in the block.
+    offset: usize,
+
+    /// The queue the slot belongs to.
+    _marker: PhantomData<&'a Unbounded<T>>,
+}
+
+impl<T> ReadSlot<'_, T> {
+    /// Returns the slot.
+    fn slot(&self) -> &Slot<T> {
+        // SAFETY: The block is not destroyed until the slot is released.
+        unsafe { (*self.block).slots.get_unchecked(self.offset) }
+    }
+
+    /// Returns the sequence number of the value in the slot.
+    #[cfg(feature = "seq")]
+    pub fn seq(&self) -> u64 {
+        // SAFETY: The block is not destroyed until the slot is released.
+        unsafe { (*self.block).start + self.offset as u64 }
+    }
+
+    /// Returns the value in the slot.
+    pub fn value(&self) -> &T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot()
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_ref() })
+    }
+
+    /// Returns the value in the slot.
+    pub fn value_mut(&mut self) -> &mut T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot()
+            .value
+            .with_mut(|slot| unsafe { (*slot).assume_init_mut() })
+    }
+
+    /// Moves the value out of the slot and releases it.
+    pub fn into_inner(self) -> T {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        let value = self
+            .slot()
+            .value
+            .with_mut(|slot| unsafe { slot.read().assume_init() });
+        unsafe { Block::release(self.block, self.offset) };
+        mem::forget(self);
+        value
+    }
+}
+
+impl<T> Drop for ReadSlot<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The slot is initialized and we have exclusive access to it.
+        self.slot().value.with_mut(|slot| unsafe {
+            let value = &mut *slot;
+            value.as_mut_ptr().drop_in_place();
+        });
+        unsafe { Block::release(self.block, self.offset) };
+    }
+}
+
 /// A position in a queue.
 struct Position<T> {
     /// The index in the queue.
@@ -147,13 +407,28 @@ pub struct Unbounded<T> {
 
     /// The tail of the queue.
     tail: CachePadded<Position<T>>,
+
+    /// Counters for the operations on the queue.
+    metrics: Metrics,
+
+    /// Whether consumers skip over slots that are still being written instead of waiting.
+    nonblocking: bool,
+
+    /// The number of consumers claiming a slot, plus the number of skipped items that are not
+    /// pushed again yet.
+    ///
+    /// Only used if `nonblocking` is set. A closed queue is not reported as closed until this
+    /// drops to zero, since a skipped item still has to show up at the tail.
+    unsettled: CachePadded<AtomicUsize>,
 }
 
 impl<T> Unbounded<T> {
     const_fn!(
         const_if: #[cfg(not(loom))];
         /// Creates a new unbounded queue.
-        pub const fn new() -> Unbounded<T> {
+        ///
+        /// If `nonblocking` is set, pops never wait for producers.
+        pub const fn new(nonblocking: bool) -> Unbounded<T> {
             Unbounded {
                 head: CachePadded::new(Position {
                     block: AtomicPtr::new(ptr::null_mut()),
@@ -163,20 +438,84 @@ impl<T> Unbounded<T> {
                     block: AtomicPtr::new(ptr::null_mut()),
                     index: AtomicUsize::new(0),
                 }),
+                metrics: Metrics::new(),
+                nonblocking,
+                unsettled: CachePadded::new(AtomicUsize::new(0)),
             }
         }
     );
 
     /// Pushes an item into the queue.
     pub fn push(&self, value: T) -> Result<(), PushError<T>> {
+        match self.reserve() {
+            Ok(slot) => {
+                slot.write(value);
+                Ok(())
+            }
+            Err(PushError::Full(())) => Err(PushError::Full(value)),
+            Err(PushError::Closed(())) => Err(PushError::Closed(value)),
+        }
+    }
+
+    /// Reserves a slot at the tail of the queue.
+    pub fn reserve(&self) -> Result<WriteSlot<'_, T>, PushError<()>> {
+        self.reserve_inner(false)
+            .map_err(TryPushError::into_push_error)
+    }
+
+    /// Reserves a slot at the tail of the queue, reporting `Busy` instead of waiting.
+    pub fn reserve_nowait(&self) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        self.reserve_inner(true)
+    }
+
+    /// Reserves a slot at the tail of the queue.
+    fn reserve_inner(&self, nowait: bool) -> Result<WriteSlot<'_, T>, TryPushError<()>> {
+        let (block, index, _) = self.reserve_run(1, false, nowait)?;
+
+        // SAFETY: The block is alive until the reserved slot is released.
+        Ok(unsafe { WriteSlot::new(self, block, index) })
+    }
+
+    /// Reserves up to `max` slots at the tail of the queue, moving the tail once per block.
+    pub fn reserve_batch(&self, max: usize) -> Result<Vec<WriteSlot<'_, T>>, PushError<()>> {
+        let mut slots = Vec::with_capacity(max);
+
+        while slots.len() < max {
+            let (block, index, count) = match self.reserve_run(max - slots.len(), false, false) {
+                Ok(run) => run,
+                Err(err) if slots.is_empty() => return Err(err.into_push_error()),
+                Err(_) => break,
+            };
+
+            // SAFETY: The block is alive until the reserved slots are released.
+            slots.extend(
+                (index..index + count).map(|index| unsafe { WriteSlot::new(self, block, index) }),
+            );
+        }
+
+        Ok(slots)
+    }
+
+    /// Reserves up to `max` consecutive slots in one block, returning the block, the position of
+    /// the first slot and the number of slots.
+    ///
+    /// If `force` is set, slots are reserved even if the queue is closed. If `nowait` is set,
+    /// `Busy` is returned instead of waiting for the next block to be installed.
+    fn reserve_run(
+        &self,
+        max: usize,
+        force: bool,
+        nowait: bool,
+    ) -> Result<(*mut Block<T>, usize, usize), TryPushError<()>> {
         let mut tail = self.tail.index.load(Ordering::Acquire);
         let mut block = self.tail.block.load(Ordering::Acquire);
         let mut next_block = None;
+        let mut spin = Spin::new(self);
 
         loop {
             // Check if the queue is closed.
-            if tail & MARK_BIT != 0 {
-                return Err(PushError::Closed(value));
+            if tail & MARK_BIT != 0 && !force {
+                return Err(TryPushError::Closed(()));
             }
 
             // Calculate the offset of the index into the block.
@@ -184,15 +523,21 @@ impl<T> Unbounded<T> {
 
             // If we reached the end of the block, wait until the next one is installed.
             if offset == BLOCK_CAP {
-                busy_wait();
+                if nowait {
+                    return Err(TryPushError::Busy(()));
+                }
+                spin.wait();
                 tail = self.tail.index.load(Ordering::Acquire);
                 block = self.tail.block.load(Ordering::Acquire);
                 continue;
             }
 
+            // Don't reserve past the end of the block.
+            let count = max.min(BLOCK_CAP - offset);
+
             // If we're going to have to install the next block, allocate it in advance in order to
             // make the wait for other threads as short as possible.
-            if offset + 1 == BLOCK_CAP && next_block.is_none() {
+            if offset + count == BLOCK_CAP && next_block.is_none() {
                 next_block = Some(Box::new(Block::<T>::new()));
             }
 
@@ -209,7 +554,9 @@ impl<T> Unbounded<T> {
                 {
                     self.head.block.store(new, Ordering::Release);
                     block = new;
+                    trace_event!(TRACE, self, "allocated the first block");
                 } else {
+                    self.metrics.cas_retry();
                     next_block = unsafe { Some(Box::from_raw(new)) };
                     tail = self.tail.index.load(Ordering::Acquire);
                     block = self.tail.block.load(Ordering::Acquire);
@@ -217,7 +564,7 @@ impl<T> Unbounded<T> {
                 }
             }
 
-            let new_tail = tail + (1 << SHIFT);
+            let new_tail = tail + (count << SHIFT);
 
             // Try advancing the tail forward.
             match self.tail.index.compare_exchange_weak(
@@ -228,22 +575,22 @@ impl<T> Unbounded<T> {
             ) {
                 Ok(_) => unsafe {
                     // If we've reached the end of the block, install the next one.
-                    if offset + 1 == BLOCK_CAP {
+                    if offset + count == BLOCK_CAP {
                         let next_block = Box::into_raw(next_block.unwrap());
+                        #[cfg(feature = "seq")]
+                        {
+                            (*next_block).start = (*block).start + BLOCK_CAP as u64;
+                        }
                         self.tail.block.store(next_block, Ordering::Release);
-                        self.tail.index.fetch_add(1 << SHIFT, Ordering::Release);
                         (*block).next.store(next_block, Ordering::Release);
+                        self.tail.index.fetch_add(1 << SHIFT, Ordering::Release);
+                        trace_event!(TRACE, self, "allocated a new block");
                     }
 
-                    // Write the value into the slot.
-                    let slot = (*block).slots.get_unchecked(offset);
-                    slot.value.with_mut(|slot| {
-                        slot.write(MaybeUninit::new(value));
-                    });
-                    slot.state.fetch_or(WRITE, Ordering::Release);
-                    return Ok(());
+                    return Ok((block, tail >> SHIFT, count));
                 },
                 Err(t) => {
+                    self.metrics.cas_retry();
                     tail = t;
                     block = self.tail.block.load(Ordering::Acquire);
                 }
@@ -253,16 +600,49 @@ impl<T> Unbounded<T> {
 
     /// Pops an item from the queue.
     pub fn pop(&self) -> Result<T, PopError> {
+        self.pop_ref().map(ReadSlot::into_inner)
+    }
+
+    /// Pops an item from the queue, reporting `Busy` instead of waiting.
+    pub fn pop_nowait(&self) -> Result<T, TryPopError> {
+        self.claim(true).map(ReadSlot::into_inner)
+    }
+
+    /// Pops an item from the queue, leaving it in its slot until the guard is dropped.
+    pub fn pop_ref(&self) -> Result<ReadSlot<'_, T>, PopError> {
+        self.claim(false).map_err(TryPopError::into_pop_error)
+    }
+
+    /// Attempts to pop an item from the queue along with its sequence number.
+    #[cfg(feature = "seq")]
+    pub fn pop_seq(&self) -> Result<(u64, T), PopError> {
+        let slot = self.pop_ref()?;
+        Ok((slot.seq(), slot.into_inner()))
+    }
+
+    /// Claims the slot at the head of the queue.
+    ///
+    /// If `nowait` is set, `Busy` is returned instead of waiting for other threads. In a plain
+    /// queue, that includes a slot whose value is still being written, which is left in place.
+    fn claim(&self, nowait: bool) -> Result<ReadSlot<'_, T>, TryPopError> {
         let mut head = self.head.index.load(Ordering::Acquire);
         let mut block = self.head.block.load(Ordering::Acquire);
+        let mut spin = Spin::new(self);
 
         loop {
             // Calculate the offset of the index into the block.
             let offset = (head >> SHIFT) % LAP;
 
-            // If we reached the end of the block, wait until the next one is installed.
-            if offset == BLOCK_CAP {
-                busy_wait();
+            // If we reached the end of the block, wait until the next one is installed. If the
+            // head is frozen, wait until it is thawed.
+            if offset == BLOCK_CAP || head & FROZEN != 0 {
+                if self.nonblocking {
+                    return Err(TryPopError::Empty);
+                }
+                if nowait {
+                    return Err(TryPopError::Busy);
+                }
+                spin.wait();
                 head = self.head.index.load(Ordering::Acquire);
                 block = self.head.block.load(Ordering::Acquire);
                 continue;
@@ -272,43 +652,79 @@ impl<T> Unbounded<T> {
 
             if new_head & MARK_BIT == 0 {
                 crate::full_fence();
-                let tail = self.tail.index.load(Ordering::Relaxed);
+                let tail = self.tail.index.load(Ordering::Acquire);
 
                 // If the tail equals the head, that means the queue is empty.
                 if head >> SHIFT == tail >> SHIFT {
-                    // Check if the queue is closed.
-                    if tail & MARK_BIT != 0 {
-                        return Err(PopError::Closed);
+                    // Check if the queue is closed and no skipped item is on its way back.
+                    if tail & MARK_BIT != 0 && self.is_settled(tail) {
+                        return Err(TryPopError::Closed);
                     } else {
-                        return Err(PopError::Empty);
+                        return Err(TryPopError::Empty);
                     }
                 }
 
                 // If head and tail are not in the same block, set `MARK_BIT` in head.
                 if (head >> SHIFT) / LAP != (tail >> SHIFT) / LAP {
                     new_head |= MARK_BIT;
+                } else if offset + 1 == BLOCK_CAP {
+                    // The last slot can't be claimed until the tail moves into the next block,
+                    // which means the next block is installed. Until then, the producer of the
+                    // slot is still in the middle of a push.
+                    if self.nonblocking {
+                        return Err(TryPopError::Empty);
+                    }
+                    if nowait {
+                        return Err(TryPopError::Busy);
+                    }
+                    spin.wait();
+                    head = self.head.index.load(Ordering::Acquire);
+                    block = self.head.block.load(Ordering::Acquire);
+                    continue;
                 }
             }
 
             // The block can be null here only if the first push operation is in progress.
             if block.is_null() {
-                busy_wait();
+                if self.nonblocking {
+                    return Err(TryPopError::Empty);
+                }
+                if nowait {
+                    return Err(TryPopError::Busy);
+                }
+                spin.wait();
                 head = self.head.index.load(Ordering::Acquire);
                 block = self.head.block.load(Ordering::Acquire);
                 continue;
             }
 
-            // Try moving the head index forward.
-            match self.head.index.compare_exchange_weak(
-                head,
-                new_head,
-                Ordering::SeqCst,
-                Ordering::Acquire,
-            ) {
+            // Try moving the head index forward. A plain queue can't leave a claimed slot to its
+            // producer, so without waiting, only claim a slot whose value is written. A
+            // nonblocking queue counts the claim before the head moves, so that no consumer sees
+            // the queue as closed and drained while the slot may still be skipped.
+            let moved = if nowait && !self.nonblocking {
+                self.advance_if_written(head, new_head, block, offset)?
+            } else {
+                if self.nonblocking {
+                    self.unsettled.fetch_add(1, Ordering::SeqCst);
+                }
+                let moved = self.head.index.compare_exchange_weak(
+                    head,
+                    new_head,
+                    Ordering::SeqCst,
+                    Ordering::Acquire,
+                );
+                if moved.is_err() && self.nonblocking {
+                    self.unsettled.fetch_sub(1, Ordering::SeqCst);
+                }
+                moved
+            };
+
+            match moved {
                 Ok(_) => unsafe {
                     // If we've reached the end of the block, move to the next one.
                     if offset + 1 == BLOCK_CAP {
-                        let next = (*block).wait_next();
+                        let next = (*block).wait_next(&mut spin);
                         let mut next_index = (new_head & !MARK_BIT).wrapping_add(1 << SHIFT);
                         if !(*next).next.load(Ordering::Relaxed).is_null() {
                             next_index |= MARK_BIT;
@@ -318,22 +734,37 @@ impl<T> Unbounded<T> {
                         self.head.index.store(next_index, Ordering::Release);
                     }
 
-                    // Read the value.
+                    // Claim the value, unless the slot's reservation was abandoned. If the value
+                    // is still being written, either wait for it or leave the slot to its
+                    // producer and move on. The producer settles the claim once the value is
+                    // pushed again or abandoned.
                     let slot = (*block).slots.get_unchecked(offset);
-                    slot.wait_write();
-                    let value = slot.value.with_mut(|slot| slot.read().assume_init());
-
-                    // Destroy the block if we've reached the end, or if another thread wanted to
-                    // destroy but couldn't because we were busy reading from the slot.
-                    if offset + 1 == BLOCK_CAP {
-                        Block::destroy(block, 0);
-                    } else if slot.state.fetch_or(READ, Ordering::AcqRel) & DESTROY != 0 {
-                        Block::destroy(block, offset + 1);
+                    if !self.nonblocking {
+                        slot.wait_write(&mut spin);
+                    } else if slot.state.fetch_or(ORPHAN, Ordering::AcqRel) & WRITE == 0 {
+                        trace_event!(TRACE, self, "skipped a slot that is still being written");
+                        head = self.head.index.load(Ordering::Acquire);
+                        block = self.head.block.load(Ordering::Acquire);
+                        continue;
+                    } else {
+                        self.unsettled.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    if slot.state.load(Ordering::Acquire) & SKIP == 0 {
+                        self.metrics.pop();
+                        self.metrics.pop_latency(&slot.pushed_at);
+                        return Ok(ReadSlot {
+                            block,
+                            offset,
+                            _marker: PhantomData,
+                        });
                     }
 
-                    return Ok(value);
+                    Block::release(block, offset);
+                    head = self.head.index.load(Ordering::Acquire);
+                    block = self.head.block.load(Ordering::Acquire);
                 },
                 Err(h) => {
+                    self.metrics.cas_retry();
                     head = h;
                     block = self.head.block.load(Ordering::Acquire);
                 }
@@ -341,6 +772,338 @@ impl<T> Unbounded<T> {
         }
     }
 
+    /// Returns `true` if no skipped item can be pushed again at `tail`, the closed tail that a
+    /// consumer found the head at.
+    ///
+    /// A skipped item moves the tail before its consumer's claim is settled, so the tail is
+    /// checked again after the count.
+    fn is_settled(&self, tail: usize) -> bool {
+        !self.nonblocking
+            || (self.unsettled.load(Ordering::SeqCst) == 0
+                && self.tail.index.load(Ordering::SeqCst) == tail)
+    }
+
+    /// Moves the head from `head` to `new_head` like a compare-and-swap, but reports `Busy` instead
+    /// if the value in the slot at `offset` in `block` is still being written.
+    ///
+    /// The head is frozen while the slot is checked, which keeps the block alive. At the end of
+    /// the block, `Busy` is also reported until the next block is installed.
+    fn advance_if_written(
+        &self,
+        head: usize,
+        new_head: usize,
+        block: *mut Block<T>,
+        offset: usize,
+    ) -> Result<Result<usize, usize>, TryPopError> {
+        if let Err(h) = self.head.index.compare_exchange_weak(
+            head,
+            head | FROZEN,
+            Ordering::SeqCst,
+            Ordering::Acquire,
+        ) {
+            return Ok(Err(h));
+        }
+        let frozen = Frozen {
+            queue: self,
+            block,
+            index: head,
+        };
+
+        // SAFETY: The block is alive while the head is frozen.
+        let ready = unsafe {
+            (*block)
+                .slots
+                .get_unchecked(offset)
+                .state
+                .load(Ordering::Acquire)
+                & WRITE
+                != 0
+                && (offset + 1 < BLOCK_CAP || !(*block).next.load(Ordering::Acquire).is_null())
+        };
+        if !ready {
+            return Err(TryPopError::Busy);
+        }
+
+        // Move the head, which also thaws it.
+        self.head.index.store(new_head, Ordering::SeqCst);
+        mem::forget(frozen);
+        Ok(Ok(head))
+    }
+
+    /// Pops up to `max` items from the head of the queue, moving the head once per block.
+    ///
+    /// Stops at the first item that is still being written instead of waiting for it. Returns the
+    /// number of items passed to `f`.
+    pub fn pop_batch<F: FnMut(T)>(&self, max: usize, mut f: F) -> usize {
+        let mut count = 0;
+
+        loop {
+            let (popped, end_of_block) = self.pop_run(max - count, &mut f);
+            count += popped;
+
+            if !end_of_block || count == max {
+                return count;
+            }
+        }
+    }
+
+    /// Pops up to `max` items from the block at the head of the queue into `f`.
+    ///
+    /// Returns the number of items popped and whether the end of the block was reached.
+    fn pop_run<F: FnMut(T)>(&self, max: usize, f: &mut F) -> (usize, bool) {
+        let frozen = if self.nonblocking {
+            match self.try_freeze() {
+                Some(frozen) => frozen,
+                None => return (0, false),
+            }
+        } else {
+            self.freeze()
+        };
+        let block = frozen.block;
+        let head = frozen.index;
+
+        // The block can be null here only if the first push operation is in progress.
+        if block.is_null() {
+            return (0, false);
+        }
+
+        let tail = self.tail.index.load(Ordering::SeqCst) >> SHIFT;
+        let start = (head >> SHIFT) % LAP;
+
+        // Count the written slots from the head onwards, including skipped ones.
+        let mut count = 0;
+        let mut end = start;
+        while end < BLOCK_CAP && (head >> SHIFT) + (end - start) != tail && count < max {
+            // SAFETY: The block is alive while the head is frozen.
+            let state = unsafe { (*block).slots.get_unchecked(end) }
+                .state
+                .load(Ordering::Acquire);
+            if state & WRITE == 0 {
+                break;
+            }
+            if state & SKIP == 0 {
+                count += 1;
+            }
+            end += 1;
+        }
+
+        if end == start {
+            return (0, false);
+        }
+
+        // Move the head past the claimed slots, which also thaws it. If head and tail are not in
+        // the same block, set `MARK_BIT` in head.
+        let mut new_head = head + ((end - start) << SHIFT);
+        if (head >> SHIFT) / LAP != tail / LAP {
+            new_head |= MARK_BIT;
+        }
+        self.head.index.store(new_head, Ordering::SeqCst);
+        mem::forget(frozen);
+
+        unsafe {
+            // If we've reached the end of the block, move to the next one.
+            if end == BLOCK_CAP {
+                let next = (*block).wait_next(&mut Spin::new(self));
+                let mut next_index = (new_head & !MARK_BIT).wrapping_add(1 << SHIFT);
+                if !(*next).next.load(Ordering::Relaxed).is_null() {
+                    next_index |= MARK_BIT;
+                }
+
+                self.head.block.store(next, Ordering::Release);
+                self.head.index.store(next_index, Ordering::Release);
+            }
+
+            // Skipped slots are released without reading them. The block is alive until its last
+            // claimed slot is released.
+            for offset in start..end {
+                let slot = (*block).slots.get_unchecked(offset);
+                if slot.state.load(Ordering::Acquire) & SKIP == 0 {
+                    self.metrics.pop();
+                    self.metrics.pop_latency(&slot.pushed_at);
+                    let value = slot.value.with_mut(|slot| slot.read().assume_init());
+                    Block::release(block, offset);
+                    f(value);
+                } else {
+                    Block::release(block, offset);
+                }
+            }
+        }
+
+        (count, end == BLOCK_CAP)
+    }
+
+    /// Records the number of items in the queue after a push.
+    #[cfg(feature = "metrics")]
+    fn record_push(&self) {
+        let head = self.head.index.load(Ordering::Relaxed) >> SHIFT;
+        let tail = self.tail.index.load(Ordering::Relaxed) >> SHIFT;
+
+        // The indices may be read out of order, leaving the head ahead of the tail.
+        let diff = tail.wrapping_sub(head) & (usize::MAX >> SHIFT);
+        if diff > usize::MAX >> (SHIFT + 1) {
+            return;
+        }
+
+        // Leave out the ends of blocks, which don't hold items.
+        let len = diff - (head % LAP + diff) / LAP;
+        self.metrics.occupancy(len.max(1));
+    }
+
+    /// Stops consumers from moving the head until the returned guard is dropped.
+    ///
+    /// While the head is frozen, the block it points to and all blocks after it stay alive.
+    fn freeze(&self) -> Frozen<'_, T> {
+        let mut spin = Spin::new(self);
+
+        // Wait until other inspections are done and the head is not between blocks.
+        loop {
+            match self.try_freeze() {
+                Some(frozen) => return frozen,
+                None => spin.wait(),
+            }
+        }
+    }
+
+    /// Freezes the head like [`freeze`](Self::freeze), or returns `None` instead of waiting if
+    /// another inspection is in progress or the head is between blocks.
+    fn try_freeze(&self) -> Option<Frozen<'_, T>> {
+        let mut head = self.head.index.load(Ordering::Acquire);
+
+        loop {
+            if head & FROZEN != 0 || (head >> SHIFT) % LAP == BLOCK_CAP {
+                return None;
+            }
+
+            match self.head.index.compare_exchange_weak(
+                head,
+                head | FROZEN,
+                Ordering::SeqCst,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    return Some(Frozen {
+                        queue: self,
+                        block: self.head.block.load(Ordering::Acquire),
+                        index: head,
+                    });
+                }
+                Err(h) => {
+                    self.metrics.cas_retry();
+                    head = h;
+                }
+            }
+        }
+    }
+
+    /// Returns how long the oldest item in the queue has been in it.
+    ///
+    /// Slots that are still being written or are skipped are passed over. Consumers free blocks as
+    /// they move past them, so the head is frozen to keep the blocks alive while the timestamp is
+    /// looked up. Consumers only wait for that lookup.
+    #[cfg(feature = "latency")]
+    pub fn oldest_item_age(&self) -> Option<Duration> {
+        let frozen = self.freeze();
+        let tail = self.tail.index.load(Ordering::SeqCst) >> SHIFT;
+        let mut head = frozen.index >> SHIFT;
+        let mut block = frozen.block;
+
+        // The block can be null here only if the first push operation is in progress.
+        while head != tail && !block.is_null() {
+            let offset = head % LAP;
+            head = head.wrapping_add(1);
+
+            // Move on to the next block, if it has been installed yet.
+            if offset == BLOCK_CAP {
+                // SAFETY: The blocks after the head are alive while it is frozen.
+                block = unsafe { (*block).next.load(Ordering::Acquire) };
+                continue;
+            }
+
+            // SAFETY: The blocks after the head are alive while it is frozen.
+            let slot = unsafe { (*block).slots.get_unchecked(offset) };
+            if slot.state.load(Ordering::Acquire) & (WRITE | SKIP) == WRITE {
+                return Some(slot.pushed_at.age());
+            }
+        }
+
+        None
+    }
+
+    /// Calls `f` on every written slot between the frozen head and the tail.
+    ///
+    /// Slots that are still being written or are skipped are left out.
+    fn for_each_written<F: FnMut(&Slot<T>)>(&self, frozen: &Frozen<'_, T>, mut f: F) {
+        let tail = self.tail.index.load(Ordering::SeqCst) >> SHIFT;
+        let mut head = frozen.index >> SHIFT;
+        let mut block = frozen.block;
+
+        // The block can be null here only if the first push operation is in progress.
+        while head != tail && !block.is_null() {
+            let offset = head % LAP;
+            head = head.wrapping_add(1);
+
+            // Move on to the next block, if it has been installed yet.
+            if offset == BLOCK_CAP {
+                // SAFETY: The blocks after the head are alive while it is frozen.
+                block = unsafe { (*block).next.load(Ordering::Acquire) };
+                continue;
+            }
+
+            // SAFETY: The blocks after the head are alive while it is frozen.
+            let slot = unsafe { (*block).slots.get_unchecked(offset) };
+            if slot.state.load(Ordering::Acquire) & (WRITE | SKIP) == WRITE {
+                f(slot);
+            }
+        }
+    }
+
+    /// Calls `f` with the items in the queue, from the head to the tail, without popping them.
+    ///
+    /// Items that are still being written are left out. Consumers wait until this returns.
+    pub fn with_items<R, F: FnOnce(&[&T]) -> R>(&self, f: F) -> R {
+        let frozen = self.freeze();
+        let mut items = Vec::new();
+
+        self.for_each_written(&frozen, |slot| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            items.push(
+                slot.value
+                    .with_mut(|slot| unsafe { (*slot).assume_init_ref() }),
+            );
+        });
+
+        f(&items)
+    }
+
+    /// Removes the items for which `pred` returns `true` and returns them.
+    ///
+    /// The slots of removed items are skipped by consumers, like abandoned reservations. Items
+    /// that are still being written are kept. Consumers wait while `pred` runs.
+    pub fn remove_if<F: FnMut(&T) -> bool>(&self, mut pred: F) -> Vec<T> {
+        let frozen = self.freeze();
+        let mut removed = Vec::new();
+
+        self.for_each_written(&frozen, |slot| {
+            // SAFETY: The slot is initialized, and it can't be popped while the head is frozen.
+            let value = slot.value.with_mut(|value| unsafe {
+                if pred((*value).assume_init_ref()) {
+                    Some(value.read().assume_init())
+                } else {
+                    None
+                }
+            });
+
+            if let Some(value) = value {
+                // Mark the slot so that consumers skip over it.
+                slot.state.fetch_or(SKIP, Ordering::Release);
+                removed.push(value);
+            }
+        });
+
+        drop(frozen);
+        removed
+    }
+
     /// Returns the number of items in the queue.
     pub fn len(&self) -> usize {
         loop {
@@ -403,9 +1166,45 @@ impl<T> Unbounded<T> {
     }
 }
 
+/// A guard that keeps the head of an [`Unbounded`] queue in place.
+struct Frozen<'a, T> {
+    /// The frozen queue.
+    queue: &'a Unbounded<T>,
+
+    /// The block the head points to.
+    block: *mut Block<T>,
+
+    /// The index of the head.
+    index: usize,
+}
+
+impl<T> Drop for Frozen<'_, T> {
+    fn drop(&mut self) {
+        self.queue.head.index.fetch_and(!FROZEN, Ordering::Release);
+    }
+}
+
+impl<T> Instrumented for Unbounded<T> {
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+}
+
 impl<T> Drop for Unbounded<T> {
     fn drop(&mut self) {
-        let Self { head, tail } = self;
+        let Self { head, tail, .. } = self;
         let Position { index: head, block } = &mut **head;
 
         head.with_mut(|&mut mut head| {
@@ -420,13 +1219,19 @@ impl<T> Drop for Unbounded<T> {
                         let offset = (head >> SHIFT) % LAP;
 
                         if offset < BLOCK_CAP {
-                            // Drop the value in the slot.
+                            // Drop the value in the slot, unless it is skipped or was never
+                            // written.
                             block.with_mut(|block| {
-                                let slot = (**block).slots.get_unchecked(offset);
-                                slot.value.with_mut(|slot| {
-                                    let value = &mut *slot;
-                                    value.as_mut_ptr().drop_in_place();
-                                });
+                                let slot = (**block).slots.get_unchecked_mut(offset);
+                                if slot
+                                    .state
+                                    .with_mut(|&mut state| state & (WRITE | SKIP) == WRITE)
+                                {
+                                    slot.value.with_mut(|slot| {
+                                        let value = &mut *slot;
+                                        value.as_mut_ptr().drop_in_place();
+                                    });
+                                }
                             });
                         } else {
                             // Deallocate the block and move to the next one.
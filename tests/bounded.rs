@@ -1,11 +1,13 @@
 #![allow(clippy::bool_assert_comparison)]
 
-use concurrent_queue::{ConcurrentQueue, ForcePushError, PopError, PushError};
+use concurrent_queue::{
+    ConcurrentQueue, ForcePushError, OverflowPolicy, PopError, PushError, TryPopError, TryPushError,
+};
 
 #[cfg(not(target_family = "wasm"))]
 use easy_parallel::Parallel;
 #[cfg(not(target_family = "wasm"))]
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 #[cfg(target_family = "wasm")]
 use wasm_bindgen_test::wasm_bindgen_test as test;
@@ -162,6 +164,840 @@ fn force_push() {
     assert_eq!(q.pop(), Err(PopError::Closed));
 }
 
+#[test]
+fn reserve() {
+    let q = ConcurrentQueue::bounded(2);
+
+    let mut slot = q.reserve().unwrap();
+    slot.value_mut().write(1);
+    unsafe { slot.commit() };
+    q.reserve().unwrap().write(2);
+
+    assert_eq!(q.reserve().err(), Some(PushError::Full(())));
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(2));
+
+    q.close();
+    assert_eq!(q.reserve().err(), Some(PushError::Closed(())));
+}
+
+#[test]
+fn reserve_abandon() {
+    let q = ConcurrentQueue::bounded(3);
+
+    for i in 0..10 {
+        q.push(i).unwrap();
+        drop(q.reserve().unwrap());
+        assert_eq!(q.len(), 2);
+
+        // Consumers skip the abandoned slot.
+        assert_eq!(q.pop(), Ok(i));
+        assert_eq!(q.pop(), Err(PopError::Empty));
+    }
+
+    // Displacing an abandoned slot displaces nothing.
+    drop(q.reserve().unwrap());
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    assert_eq!(q.force_push(3), Ok(None));
+    assert_eq!(q.force_push(4), Ok(Some(1)));
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Ok(3));
+    assert_eq!(q.pop(), Ok(4));
+
+    // Abandoned slots are not dropped with the queue.
+    let q = ConcurrentQueue::<String>::bounded(3);
+    q.push("hello".to_string()).unwrap();
+    drop(q.reserve().unwrap());
+    q.push("world".to_string()).unwrap();
+}
+
+#[test]
+fn pop_ref() {
+    let q = ConcurrentQueue::bounded(2);
+    q.push(vec![1]).unwrap();
+    q.push(vec![2]).unwrap();
+
+    let mut first = q.pop_ref().unwrap();
+    first.push(10);
+    assert_eq!(*first, [1, 10]);
+    assert_eq!(q.len(), 1);
+
+    // The held slot is still occupied.
+    assert_eq!(q.push(vec![3]), Err(PushError::Full(vec![3])));
+
+    let second = q.pop_ref().unwrap();
+    assert_eq!(second.into_inner(), [2]);
+    assert_eq!(q.push(vec![3]), Err(PushError::Full(vec![3])));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    drop(first);
+    assert_eq!(q.push(vec![3]), Ok(()));
+    assert_eq!(q.push(vec![4]), Ok(()));
+    assert_eq!(q.pop(), Ok(vec![3]));
+    assert_eq!(q.pop(), Ok(vec![4]));
+    assert_eq!(q.pop_ref().err(), Some(PopError::Empty));
+}
+
+#[test]
+fn force_push_held() {
+    let q = ConcurrentQueue::bounded(2);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+
+    // The slot at the tail is held, so there is nothing to displace.
+    let first = q.pop_ref().unwrap();
+    assert_eq!(q.try_force_push(3), Err(TryPushError::Busy(3)));
+    assert!(!q.is_closed());
+    assert!(q.reserve().is_err());
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Full(3)));
+
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.try_force_push(3), Err(TryPushError::Busy(3)));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    drop(first);
+    assert_eq!(q.force_push(4), Ok(None));
+    assert_eq!(q.pop(), Ok(4));
+
+    // The overflow policy doesn't wait either.
+    let q = ConcurrentQueue::bounded_with_policy(2, OverflowPolicy::DropOldest);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    let first = q.pop_ref().unwrap();
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(*first, 1);
+}
+
+#[test]
+fn force_push_reserved() {
+    let q = ConcurrentQueue::bounded(2);
+    let slot = q.reserve().unwrap();
+    q.push(1).unwrap();
+
+    // The slot at the tail is reserved, so there is nothing to displace.
+    assert_eq!(q.try_force_push(2), Err(TryPushError::Busy(2)));
+    assert!(!q.is_closed());
+
+    slot.write(0);
+    assert_eq!(q.force_push(2), Ok(Some(0)));
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(2));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn force_push_waits_for_guard() {
+    let q = ConcurrentQueue::bounded(2);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    let held = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            let first = q.pop_ref().unwrap();
+            held.store(true, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert_eq!(*first, 1);
+        })
+        .add(|| {
+            while !held.load(Ordering::SeqCst) {
+                std::hint::spin_loop();
+            }
+
+            // The slot at the tail is free again once the guard is dropped.
+            assert_eq!(q.force_push(3), Ok(None));
+        })
+        .run();
+
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Ok(3));
+}
+
+#[test]
+fn overflow_policy() {
+    use std::sync::{Arc, Mutex};
+
+    let q = ConcurrentQueue::bounded_with_policy(3, OverflowPolicy::Reject);
+    for i in 0..3 {
+        q.push(i).unwrap();
+    }
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+
+    for (policy, kept) in [
+        (OverflowPolicy::DropOldest, [2, 3, 4]),
+        (OverflowPolicy::DropNewest, [0, 1, 2]),
+    ] {
+        for cap in [1, 3] {
+            let evicted = Arc::new(Mutex::new(Vec::new()));
+            let q = ConcurrentQueue::bounded_with_policy(cap, policy).with_eviction_handler({
+                let evicted = evicted.clone();
+                move |i| evicted.lock().unwrap().push(i)
+            });
+
+            for i in 0..5 {
+                assert_eq!(q.push(i), Ok(()));
+            }
+
+            let kept = match policy {
+                OverflowPolicy::DropOldest => &kept[3 - cap..],
+                _ => &kept[..cap],
+            };
+            let mut evicted = evicted.lock().unwrap().clone();
+            evicted.sort();
+            assert_eq!(
+                evicted,
+                (0..5).filter(|i| !kept.contains(i)).collect::<Vec<_>>()
+            );
+            assert_eq!(q.try_iter().collect::<Vec<_>>(), kept);
+
+            q.close();
+            assert_eq!(q.push(5), Err(PushError::Closed(5)));
+        }
+    }
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn pop_with_loss() {
+    let q = ConcurrentQueue::bounded(3);
+
+    for i in 0..10 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.pop_with_loss(), Ok((7, 7)));
+    assert_eq!(q.pop_with_loss(), Ok((8, 0)));
+
+    // Items popped by other methods aren't lost.
+    assert_eq!(q.pop(), Ok(9));
+    q.push(10).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((10, 0)));
+    assert_eq!(q.pop_with_loss(), Err(PopError::Empty));
+
+    // Neither are abandoned reservations.
+    drop(q.reserve().unwrap());
+    q.push(11).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((11, 0)));
+
+    // Items dropped by the overflow policy are.
+    let q = ConcurrentQueue::bounded_with_policy(3, OverflowPolicy::DropOldest);
+    for i in 0..5 {
+        q.push(i).unwrap();
+    }
+    assert_eq!(q.pop_with_loss(), Ok((2, 2)));
+    assert_eq!(q.pop_with_loss(), Ok((3, 0)));
+
+    // Items lost before an item popped by another method aren't reported again.
+    assert_eq!(q.pop(), Ok(4));
+    for i in 5..9 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.pop(), Ok(6));
+    assert_eq!(q.pop_with_loss(), Ok((7, 0)));
+    assert_eq!(q.steal_into(&ConcurrentQueue::unbounded(), 1), 1);
+    q.push(9).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((9, 0)));
+
+    // Items displaced after a pop are reported with the item that follows them.
+    let q = ConcurrentQueue::bounded(2);
+    q.push(0).unwrap();
+    q.push(1).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((0, 0)));
+    q.force_push(2).unwrap();
+    q.force_push(3).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((2, 1)));
+    assert_eq!(q.pop_with_loss(), Ok((3, 0)));
+
+    // The count is carried past abandoned reservations.
+    q.push(4).unwrap();
+    drop(q.reserve().unwrap());
+    assert_eq!(q.force_push(5), Ok(Some(4)));
+    assert_eq!(q.pop_with_loss(), Ok((5, 1)));
+
+    // And past items removed from the queue, even when stealing.
+    for i in 6..10 {
+        q.force_push(i).unwrap();
+    }
+    q.retain(|_| false);
+    assert_eq!(q.steal_into(&ConcurrentQueue::unbounded(), 2), 0);
+    q.push(10).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((10, 2)));
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn seq() {
+    let q = ConcurrentQueue::bounded(3);
+
+    for i in 0..10 {
+        assert_eq!(q.push_seq(i), Ok(i as u64));
+        assert_eq!(q.pop_seq(), Ok((i as u64, i)));
+    }
+
+    // Abandoned reservations and displaced items leave gaps.
+    drop(q.reserve().unwrap());
+    assert_eq!(q.push_seq(10), Ok(11));
+    for i in 11..14 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.pop_seq(), Ok((12, 11)));
+    assert_eq!(q.pop_ref().unwrap().seq(), 13);
+    assert_eq!(q.pop_seq(), Ok((14, 13)));
+    assert_eq!(q.pop_seq(), Err(PopError::Empty));
+
+    assert_eq!(q.push_seq(14), Ok(15));
+    q.close();
+    assert_eq!(q.push_seq(15), Err(PushError::Closed(15)));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[cfg(feature = "seq")]
+#[test]
+fn seq_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 2;
+
+    let q = ConcurrentQueue::<usize>::bounded(3);
+    let v = (0..COUNT * THREADS)
+        .map(|_| AtomicUsize::new(0))
+        .collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            let mut last = None;
+            for _ in 0..COUNT {
+                let (seq, _) = loop {
+                    if let Ok(item) = q.pop_seq() {
+                        break item;
+                    }
+                };
+                // Each consumer sees sequence numbers in increasing order.
+                assert!(last < Some(seq));
+                last = Some(seq);
+                v[seq as usize].fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                while q.push_seq(i).is_err() {}
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn high_water_mark() {
+    let q = ConcurrentQueue::bounded(5);
+    assert_eq!(q.high_water_mark(), 0);
+
+    // Go around the buffer a few times.
+    for i in 0..20 {
+        q.push(i).unwrap();
+        q.push(i).unwrap();
+        q.pop().unwrap();
+        q.pop().unwrap();
+    }
+    assert_eq!(q.high_water_mark(), 2);
+
+    for i in 0..5 {
+        q.push(i).unwrap();
+        assert_eq!(q.high_water_mark(), (i + 1).max(2));
+    }
+
+    while q.pop().is_ok() {}
+    q.reset_high_water_mark();
+    assert_eq!(q.high_water_mark(), 0);
+
+    for i in 0..10 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.high_water_mark(), 5);
+}
+
+#[test]
+fn debug_items() {
+    let q = ConcurrentQueue::bounded(3);
+    assert_eq!(format!("{:?}", q.debug_items()), "[]");
+
+    for i in 0..5 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(format!("{:?}", q.debug_items()), "[2, 3, 4]");
+    assert_eq!(q.len(), 3);
+    assert!(q.is_full());
+
+    // Abandoned and pending reservations are left out.
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Ok(3));
+    drop(q.reserve().unwrap());
+    let slot = q.reserve().unwrap();
+    assert_eq!(format!("{:?}", q.debug_items()), "[4]");
+    slot.write(5);
+    assert_eq!(format!("{:?}", q.debug_items()), "[4, 5]");
+
+    // The queue works as before.
+    assert_eq!(q.pop(), Ok(4));
+    assert_eq!(q.pop(), Ok(5));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+}
+
+#[test]
+fn snapshot() {
+    let q = ConcurrentQueue::bounded(3);
+    assert_eq!(q.snapshot(), Vec::<String>::new());
+
+    for i in 0..5 {
+        q.force_push(i.to_string()).unwrap();
+    }
+    assert_eq!(q.snapshot(), ["2", "3", "4"]);
+
+    // Held and pending slots are left out.
+    let slot = q.pop_ref().unwrap();
+    assert_eq!(q.snapshot(), ["3", "4"]);
+    drop(slot);
+    let reserved = q.reserve().unwrap();
+    assert_eq!(q.snapshot(), ["3", "4"]);
+    reserved.write("5".to_string());
+    assert_eq!(q.snapshot(), ["3", "4", "5"]);
+
+    // Nothing was taken out of the queue.
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), ["3", "4", "5"]);
+}
+
+#[test]
+fn inspect_reentrant() {
+    use std::fmt;
+
+    // An item that uses the queue it is in while it is formatted or cloned.
+    struct Item(&'static ConcurrentQueue<Item>, usize);
+
+    impl fmt::Debug for Item {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            // Consumers are held back, but producers are not.
+            assert!(matches!(self.0.try_pop_nowait(), Err(TryPopError::Busy)));
+            self.0.push(Item(self.0, self.1 + 10)).unwrap();
+            write!(f, "{}", self.1)
+        }
+    }
+
+    impl Clone for Item {
+        fn clone(&self) -> Self {
+            assert!(matches!(self.0.try_pop_nowait(), Err(TryPopError::Busy)));
+            Item(self.0, self.1)
+        }
+    }
+
+    let q: &'static ConcurrentQueue<Item> = Box::leak(Box::new(ConcurrentQueue::bounded(4)));
+    q.push(Item(q, 1)).unwrap();
+    q.push(Item(q, 2)).unwrap();
+
+    assert_eq!(format!("{:?}", q.debug_items()), "[1, 2]");
+    assert_eq!(q.len(), 4);
+    let items = q.snapshot();
+    assert_eq!(
+        items.iter().map(|item| item.1).collect::<Vec<_>>(),
+        [1, 2, 11, 12]
+    );
+    assert_eq!(q.len(), 4);
+    assert_eq!(q.pop().map(|item| item.1), Ok(1));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn debug_items_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::bounded(3);
+    let done = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                loop {
+                    match q.pop() {
+                        Ok(x) => {
+                            assert_eq!(x, i);
+                            break;
+                        }
+                        Err(_) => continue,
+                    }
+                }
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .add(|| {
+            while !done.load(Ordering::SeqCst) {
+                // The items are always in order.
+                let view = format!("{:?}", q.debug_items());
+                let items: Vec<usize> = view
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split(", ")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse().unwrap())
+                    .collect();
+                assert!(items.windows(2).all(|w| w[0] < w[1]));
+            }
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn snapshot_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::bounded(3);
+    let popped = AtomicUsize::new(0);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                loop {
+                    if let Ok(x) = q.pop() {
+                        assert_eq!(x, i);
+                        popped.store(i + 1, Ordering::SeqCst);
+                        break;
+                    }
+                }
+            }
+        })
+        .add(|| {
+            while popped.load(Ordering::SeqCst) < COUNT {
+                let before = popped.load(Ordering::SeqCst);
+                let items = q.snapshot();
+                let after = popped.load(Ordering::SeqCst);
+
+                // With one producer, the snapshot is a run of consecutive items that were not
+                // popped before it was taken. The consumer may be holding one more item that it
+                // hasn't counted yet.
+                if let Some(&first) = items.first() {
+                    assert!(first >= before);
+                    assert!(first <= after + 1);
+                }
+                assert!(items.windows(2).all(|w| w[0] + 1 == w[1]));
+            }
+        })
+        .run();
+}
+
+#[test]
+fn retain() {
+    let q = ConcurrentQueue::bounded(4);
+    for i in 0..6 {
+        q.force_push(i.to_string()).unwrap();
+    }
+
+    // Removed items keep their slots until consumers reach them.
+    q.retain(|s| s != "3");
+    assert_eq!(q.len(), 4);
+    assert_eq!(
+        q.push("6".to_string()),
+        Err(PushError::Full("6".to_string()))
+    );
+    assert_eq!(q.snapshot(), ["2", "4", "5"]);
+
+    // Removed slots are not displaced again.
+    assert_eq!(q.force_push("6".to_string()), Ok(Some("2".to_string())));
+    assert_eq!(q.force_push("7".to_string()), Ok(None));
+    assert_eq!(q.remove_if(|s| s == "5" || s == "7"), ["5", "7"]);
+
+    assert_eq!(q.pop().as_deref(), Ok("4"));
+    assert_eq!(q.pop().as_deref(), Ok("6"));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn retain_reentrant() {
+    // An item that pops the oldest one when it is dropped.
+    struct Item(&'static ConcurrentQueue<Item>, usize);
+
+    impl Drop for Item {
+        fn drop(&mut self) {
+            if self.1 == 1 {
+                assert_eq!(self.0.pop().map(|item| item.1), Ok(0));
+            }
+        }
+    }
+
+    let q: &'static ConcurrentQueue<Item> = Box::leak(Box::new(ConcurrentQueue::bounded(4)));
+    for i in 0..3 {
+        assert!(q.push(Item(q, i)).is_ok());
+    }
+
+    q.retain(|item| {
+        // Consumers are held back, but producers are not.
+        assert!(matches!(q.try_pop_nowait(), Err(TryPopError::Busy)));
+        if item.1 == 0 {
+            assert!(q.push(Item(q, 3)).is_ok());
+        }
+        item.1 != 1
+    });
+
+    // The removed item is dropped after consumers are let through.
+    assert_eq!(q.pop().map(|item| item.1), Ok(2));
+    assert_eq!(q.pop().map(|item| item.1), Ok(3));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn nowait() {
+    let q = ConcurrentQueue::bounded(2);
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+
+    // The head slot is still being written.
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.try_push_nowait(2), Ok(()));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+    slot.write(1);
+
+    // The head is frozen while the queue is being inspected.
+    q.retain(|_| {
+        assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+        true
+    });
+
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Full(3)));
+    assert_eq!(q.try_pop_nowait(), Ok(1));
+    assert_eq!(q.try_pop_nowait(), Ok(2));
+
+    q.close();
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Closed(3)));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Closed));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn nowait_mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 4;
+
+    let q = ConcurrentQueue::<usize>::bounded(3);
+    let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            for _ in 0..COUNT {
+                let n = loop {
+                    if let Ok(x) = q.try_pop_nowait() {
+                        break x;
+                    }
+                };
+                v[n].fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                let mut value = i;
+                while let Err(err) = q.try_push_nowait(value) {
+                    value = err.into_inner();
+                }
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), THREADS);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn retain_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::bounded(3);
+    let done = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+        .add(|| {
+            // Odd items may or may not be removed before they are popped, but even items are
+            // all popped in order.
+            let mut last = None;
+            let mut even = 0;
+            while even < COUNT {
+                if let Ok(x) = q.pop() {
+                    assert!(last < Some(x));
+                    if x % 2 == 0 {
+                        assert_eq!(x, even);
+                        even += 2;
+                    }
+                    last = Some(x);
+                }
+            }
+        })
+        .add(|| {
+            while !done.load(Ordering::SeqCst) {
+                for x in q.remove_if(|x| x % 2 == 1) {
+                    assert_eq!(x % 2, 1);
+                }
+            }
+        })
+        .run();
+}
+
+#[test]
+fn steal_into() {
+    let src = ConcurrentQueue::bounded(8);
+    let dest = ConcurrentQueue::bounded(4);
+    for i in 0..7 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(src.pop(), Ok(0));
+
+    // Half of the items, rounded up, but no more than `max`.
+    assert_eq!(src.steal_into(&dest, 2), 2);
+    assert_eq!(src.steal_into(&dest, 10), 2);
+    assert_eq!(src.len(), 2);
+    assert_eq!(dest.snapshot(), [1, 2, 3, 4]);
+
+    // Nothing is lost when the destination is full.
+    assert_eq!(src.steal_into(&dest, 10), 0);
+    assert_eq!(dest.pop(), Ok(1));
+    assert_eq!(src.steal_into(&dest, 10), 1);
+    assert_eq!(src.snapshot(), [6]);
+
+    // Both queues wrap around.
+    for i in 7..13 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(dest.try_iter().collect::<Vec<_>>(), [2, 3, 4, 5]);
+    assert_eq!(src.steal_into(&dest, 10), 4);
+    assert_eq!(src.try_iter().collect::<Vec<_>>(), [10, 11, 12]);
+    assert_eq!(dest.try_iter().collect::<Vec<_>>(), [6, 7, 8, 9]);
+
+    // Nothing is lost when the destination is closed.
+    src.push(13).unwrap();
+    dest.close();
+    assert_eq!(src.steal_into(&dest, 10), 0);
+    assert_eq!(src.pop(), Ok(13));
+    assert_eq!(src.steal_into(&dest, 10), 0);
+}
+
+#[test]
+fn steal_into_skipped() {
+    let src = ConcurrentQueue::bounded(8);
+    let dest = ConcurrentQueue::bounded(8);
+
+    // Abandoned and removed slots are passed over without counting them.
+    src.push(0).unwrap();
+    drop(src.reserve().unwrap());
+    for i in 1..6 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(src.remove_if(|&i| i == 1), [1]);
+    assert_eq!(src.steal_into(&dest, 2), 2);
+    assert_eq!(dest.snapshot(), [0, 2]);
+
+    // An item that is still being written stops the batch, and leftover slots are skipped.
+    let slot = dest.reserve().unwrap();
+    dest.push(10).unwrap();
+    assert_eq!(dest.steal_into(&src, 10), 2);
+    slot.write(3);
+    assert_eq!(src.try_iter().collect::<Vec<_>>(), [3, 4, 5, 0, 2]);
+    assert_eq!(dest.try_iter().collect::<Vec<_>>(), [3, 10]);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn steal_into_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THIEVES: usize = 3;
+
+    let src = ConcurrentQueue::bounded(16);
+    let producers = AtomicUsize::new(2);
+
+    let mut popped: Vec<usize> = Parallel::new()
+        .each(0..2, |p| {
+            for i in (p..COUNT).step_by(2) {
+                while src.push(i).is_err() {}
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                src.close();
+            }
+            Vec::new()
+        })
+        .each(0..THIEVES, |_| {
+            // Items from each producer arrive in the order they were pushed.
+            let local = ConcurrentQueue::bounded(4);
+            let mut last = [None; 2];
+            let mut popped = Vec::new();
+            loop {
+                src.steal_into(&local, 3);
+                let mut got = false;
+                while let Ok(i) = local.pop() {
+                    assert!(last[i % 2] < Some(i));
+                    last[i % 2] = Some(i);
+                    popped.push(i);
+                    got = true;
+                }
+                if !got && src.is_closed() && src.is_empty() {
+                    break popped;
+                }
+            }
+        })
+        .run()
+        .into_iter()
+        .flatten()
+        .collect();
+
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn steal_into_both_ways() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let a = ConcurrentQueue::bounded(8);
+    let b = ConcurrentQueue::bounded(8);
+    for i in 0..6 {
+        a.push(i).unwrap();
+        b.push(i + 6).unwrap();
+    }
+
+    // Stealing in opposite directions at the same time neither loses items nor gets stuck.
+    Parallel::new()
+        .add(|| {
+            for _ in 0..COUNT {
+                a.steal_into(&b, 4);
+            }
+        })
+        .add(|| {
+            for _ in 0..COUNT {
+                b.steal_into(&a, 4);
+            }
+        })
+        .run();
+
+    let mut items: Vec<_> = a.try_iter().chain(b.try_iter()).collect();
+    items.sort_unstable();
+    assert_eq!(items, (0..12).collect::<Vec<_>>());
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[test]
 fn spsc() {
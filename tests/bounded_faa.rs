@@ -0,0 +1,623 @@
+#![allow(clippy::bool_assert_comparison)]
+
+use concurrent_queue::{
+    ConcurrentQueue, ForcePushError, PopError, PushError, TryPopError, TryPushError,
+};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = ConcurrentQueue::bounded_faa(2);
+
+    q.push(7).unwrap();
+    assert_eq!(q.pop(), Ok(7));
+
+    q.push(8).unwrap();
+    assert_eq!(q.pop(), Ok(8));
+    assert!(q.pop().is_err());
+}
+
+#[test]
+fn capacity() {
+    for i in 1..10 {
+        let q = ConcurrentQueue::bounded_faa(i);
+        assert_eq!(q.capacity(), Some(i));
+
+        // The capacity is exact, even if it is not a power of two.
+        for j in 0..i {
+            assert_eq!(q.push(j), Ok(()));
+        }
+        assert_eq!(q.push(i), Err(PushError::Full(i)));
+    }
+}
+
+#[test]
+#[should_panic(expected = "capacity must be positive")]
+fn zero_capacity() {
+    let _ = ConcurrentQueue::<i32>::bounded_faa(0);
+}
+
+#[test]
+fn len_empty_full() {
+    let q = ConcurrentQueue::bounded_faa(2);
+
+    assert_eq!(q.len(), 0);
+    assert_eq!(q.is_empty(), true);
+    assert_eq!(q.is_full(), false);
+
+    q.push(()).unwrap();
+
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.is_empty(), false);
+    assert_eq!(q.is_full(), false);
+
+    q.push(()).unwrap();
+
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.is_empty(), false);
+    assert_eq!(q.is_full(), true);
+
+    q.pop().unwrap();
+
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.is_empty(), false);
+    assert_eq!(q.is_full(), false);
+}
+
+#[test]
+fn wrap_around() {
+    let q = ConcurrentQueue::bounded_faa(3);
+
+    // Go around the rings many times, with failed pops in between.
+    for i in 0..1000 {
+        q.push(i).unwrap();
+        q.push(i + 1).unwrap();
+        assert_eq!(q.pop(), Ok(i));
+        assert_eq!(q.pop(), Ok(i + 1));
+        assert_eq!(q.pop(), Err(PopError::Empty));
+        assert_eq!(q.len(), 0);
+    }
+
+    for i in 0..3 {
+        q.push(i).unwrap();
+    }
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), [0, 1, 2]);
+}
+
+#[test]
+fn close() {
+    let q = ConcurrentQueue::bounded_faa(2);
+    assert_eq!(q.push(10), Ok(()));
+
+    assert!(!q.is_closed());
+    assert!(q.close());
+
+    assert!(q.is_closed());
+    assert!(!q.close());
+
+    assert_eq!(q.push(20), Err(PushError::Closed(20)));
+    assert_eq!(q.pop(), Ok(10));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn close_reserved() {
+    let q = ConcurrentQueue::bounded_faa(2);
+    let slot = q.reserve().unwrap();
+    q.close();
+
+    // The queue is not reported as closed while a slot is reserved.
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    slot.write(1);
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn force_push() {
+    let q = ConcurrentQueue::<i32>::bounded_faa(5);
+
+    for i in 1..=5 {
+        assert_eq!(q.force_push(i), Ok(None));
+    }
+
+    assert!(!q.is_closed());
+    for i in 6..=10 {
+        assert_eq!(q.force_push(i), Ok(Some(i - 5)));
+    }
+    assert_eq!(q.pop(), Ok(6));
+    assert_eq!(q.force_push(11), Ok(None));
+    for i in 12..=15 {
+        assert_eq!(q.force_push(i), Ok(Some(i - 5)));
+    }
+
+    assert!(q.close());
+    assert_eq!(q.force_push(40), Err(ForcePushError(40)));
+    for i in 11..=15 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn reserve() {
+    let q = ConcurrentQueue::bounded_faa(2);
+
+    let mut slot = q.reserve().unwrap();
+    slot.value_mut().write(1);
+    unsafe { slot.commit() };
+    q.reserve().unwrap().write(2);
+
+    assert_eq!(q.reserve().err(), Some(PushError::Full(())));
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(2));
+
+    q.close();
+    assert_eq!(q.reserve().err(), Some(PushError::Closed(())));
+}
+
+#[test]
+fn reserve_abandon() {
+    let q = ConcurrentQueue::bounded_faa(3);
+
+    for i in 0..10 {
+        q.push(i).unwrap();
+        drop(q.reserve().unwrap());
+        assert_eq!(q.len(), 2);
+
+        // Consumers skip the abandoned slot and free it.
+        assert_eq!(q.pop(), Ok(i));
+        assert_eq!(q.pop(), Err(PopError::Empty));
+        assert_eq!(q.len(), 0);
+    }
+
+    // Displacing an abandoned slot displaces nothing.
+    drop(q.reserve().unwrap());
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    assert!(q.is_full());
+    assert_eq!(q.force_push(3), Ok(None));
+    assert_eq!(q.force_push(4), Ok(Some(1)));
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Ok(3));
+    assert_eq!(q.pop(), Ok(4));
+
+    // The slot of an item that is written later on comes after items pushed in the meantime.
+    let slot = q.reserve().unwrap();
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+    slot.write(3);
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+
+    // Abandoned slots are not dropped with the queue.
+    let q = ConcurrentQueue::<String>::bounded_faa(3);
+    q.push("hello".to_string()).unwrap();
+    drop(q.reserve().unwrap());
+    q.push("world".to_string()).unwrap();
+}
+
+#[test]
+fn force_push_held() {
+    let q = ConcurrentQueue::bounded_faa(2);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+
+    // Items that aren't held can still be displaced.
+    let first = q.pop_ref().unwrap();
+    assert_eq!(q.force_push(3), Ok(Some(2)));
+
+    // Once every slot is held, there is nothing to displace.
+    let second = q.pop_ref().unwrap();
+    assert_eq!(q.try_force_push(4), Err(TryPushError::Busy(4)));
+    assert!(!q.is_closed());
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    drop(first);
+    drop(second);
+    assert_eq!(q.force_push(5), Ok(None));
+    assert_eq!(q.pop(), Ok(5));
+}
+
+#[test]
+fn force_push_reserved() {
+    let q = ConcurrentQueue::bounded_faa(2);
+
+    // Once every slot is reserved, there is nothing to displace.
+    let first = q.reserve().unwrap();
+    let second = q.reserve().unwrap();
+    assert_eq!(q.try_force_push(1), Err(TryPushError::Busy(1)));
+    assert!(!q.is_closed());
+
+    first.write(2);
+    assert_eq!(q.force_push(3), Ok(Some(2)));
+    drop(second);
+    assert_eq!(q.pop(), Ok(3));
+}
+
+#[test]
+fn pop_ref() {
+    let q = ConcurrentQueue::bounded_faa(2);
+    q.push(vec![1]).unwrap();
+    q.push(vec![2]).unwrap();
+
+    let mut first = q.pop_ref().unwrap();
+    first.push(10);
+    assert_eq!(*first, [1, 10]);
+    assert_eq!(q.len(), 1);
+
+    // The held slot is still occupied.
+    assert_eq!(q.push(vec![3]), Err(PushError::Full(vec![3])));
+
+    let second = q.pop_ref().unwrap();
+    assert_eq!(second.into_inner(), [2]);
+    assert_eq!(q.push(vec![3]), Ok(()));
+    assert_eq!(q.push(vec![4]), Err(PushError::Full(vec![4])));
+
+    drop(first);
+    assert_eq!(q.push(vec![4]), Ok(()));
+    assert_eq!(q.pop(), Ok(vec![3]));
+    assert_eq!(q.pop(), Ok(vec![4]));
+    assert_eq!(q.pop_ref().err(), Some(PopError::Empty));
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn seq() {
+    let q = ConcurrentQueue::bounded_faa(3);
+
+    for i in 0..10 {
+        assert_eq!(q.push_seq(i), Ok(i as u64));
+        assert_eq!(q.pop_seq(), Ok((i as u64, i)));
+    }
+
+    // Abandoned reservations and displaced items leave gaps.
+    drop(q.reserve().unwrap());
+    assert_eq!(q.push_seq(10), Ok(11));
+    for i in 11..14 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.pop_seq(), Ok((12, 11)));
+    assert_eq!(q.pop_ref().unwrap().seq(), 13);
+    assert_eq!(q.pop_seq(), Ok((14, 13)));
+    assert_eq!(q.pop_seq(), Err(PopError::Empty));
+
+    assert_eq!(q.push_seq(14), Ok(15));
+    q.close();
+    assert_eq!(q.push_seq(15), Err(PushError::Closed(15)));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn high_water_mark() {
+    let q = ConcurrentQueue::bounded_faa(5);
+    assert_eq!(q.high_water_mark(), 0);
+
+    for i in 0..20 {
+        q.push(i).unwrap();
+        q.push(i).unwrap();
+        q.pop().unwrap();
+        q.pop().unwrap();
+    }
+    assert_eq!(q.high_water_mark(), 2);
+
+    for i in 0..5 {
+        q.push(i).unwrap();
+        assert_eq!(q.high_water_mark(), (i + 1).max(2));
+    }
+
+    while q.pop().is_ok() {}
+    q.reset_high_water_mark();
+    assert_eq!(q.high_water_mark(), 0);
+
+    for i in 0..10 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.high_water_mark(), 5);
+}
+
+#[test]
+fn snapshot() {
+    let q = ConcurrentQueue::bounded_faa(3);
+    assert_eq!(q.snapshot(), Vec::<String>::new());
+
+    for i in 0..5 {
+        q.force_push(i.to_string()).unwrap();
+    }
+    assert_eq!(q.snapshot(), ["2", "3", "4"]);
+    assert_eq!(format!("{:?}", q.debug_items()), r#"["2", "3", "4"]"#);
+
+    // Held and pending slots are left out.
+    let slot = q.pop_ref().unwrap();
+    assert_eq!(q.snapshot(), ["3", "4"]);
+    drop(slot);
+    let reserved = q.reserve().unwrap();
+    assert_eq!(q.snapshot(), ["3", "4"]);
+    reserved.write("5".to_string());
+    assert_eq!(q.snapshot(), ["3", "4", "5"]);
+
+    // Nothing was taken out of the queue.
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), ["3", "4", "5"]);
+}
+
+#[test]
+fn retain() {
+    let q = ConcurrentQueue::bounded_faa(4);
+    for i in 0..6 {
+        q.force_push(i.to_string()).unwrap();
+    }
+
+    // Removed items keep their slots until consumers reach them.
+    q.retain(|s| s != "3");
+    assert_eq!(q.len(), 4);
+    assert_eq!(
+        q.push("6".to_string()),
+        Err(PushError::Full("6".to_string()))
+    );
+    assert_eq!(q.snapshot(), ["2", "4", "5"]);
+
+    // Removed slots are not displaced again.
+    assert_eq!(q.force_push("6".to_string()), Ok(Some("2".to_string())));
+    assert_eq!(q.force_push("7".to_string()), Ok(None));
+    assert_eq!(q.remove_if(|s| s == "5" || s == "7"), ["5", "7"]);
+
+    assert_eq!(q.pop().as_deref(), Ok("4"));
+    assert_eq!(q.pop().as_deref(), Ok("6"));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert!(q.is_empty());
+
+    // The same goes for a removed item at the head.
+    let q = ConcurrentQueue::bounded_faa(2);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+    q.retain(|x| *x != 1);
+    assert_eq!(q.len(), 2);
+    assert!(q.is_full());
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.push(3), Ok(()));
+    assert_eq!(q.push(4), Ok(()));
+    q.retain(|x| *x != 3);
+    assert_eq!(q.force_push(5), Ok(None));
+    assert_eq!(q.pop(), Ok(4));
+    assert_eq!(q.pop(), Ok(5));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn nowait() {
+    let q = ConcurrentQueue::bounded_faa(2);
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+    assert_eq!(q.try_push_nowait(1), Ok(()));
+    assert_eq!(q.try_push_nowait(2), Ok(()));
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Full(3)));
+
+    // The head is frozen while the queue is being inspected.
+    q.retain(|_| {
+        assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+        true
+    });
+
+    assert_eq!(q.try_pop_nowait(), Ok(1));
+    assert_eq!(q.try_pop_nowait(), Ok(2));
+
+    q.close();
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Closed(3)));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Closed));
+}
+
+#[test]
+fn steal_into() {
+    let src = ConcurrentQueue::bounded_faa(8);
+    let dest = ConcurrentQueue::bounded_faa(4);
+    for i in 0..7 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(src.pop(), Ok(0));
+
+    assert_eq!(src.steal_into(&dest, 2), 2);
+    assert_eq!(src.steal_into(&dest, 10), 2);
+    assert_eq!(src.len(), 2);
+    assert_eq!(dest.snapshot(), [1, 2, 3, 4]);
+
+    // Nothing is lost when the destination is full or closed.
+    assert_eq!(src.steal_into(&dest, 10), 0);
+    dest.close();
+    assert_eq!(src.steal_into(&dest, 10), 0);
+    assert_eq!(src.try_iter().collect::<Vec<_>>(), [5, 6]);
+
+    // Items can be moved to and from other kinds of queues.
+    let other = ConcurrentQueue::bounded(4);
+    assert_eq!(dest.steal_into(&other, 10), 2);
+    assert_eq!(other.steal_into(&src, 10), 1);
+    assert_eq!(src.snapshot(), [1]);
+    assert_eq!(other.snapshot(), [2]);
+    assert_eq!(dest.snapshot(), [3, 4]);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn spsc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 100_000 };
+
+    let q = ConcurrentQueue::bounded_faa(3);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                loop {
+                    if let Ok(x) = q.pop() {
+                        assert_eq!(x, i);
+                        break;
+                    }
+                }
+            }
+            assert!(q.pop().is_err());
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 4;
+
+    let q = ConcurrentQueue::<usize>::bounded_faa(3);
+    let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            for _ in 0..COUNT {
+                let n = loop {
+                    if let Ok(x) = q.pop() {
+                        break x;
+                    }
+                };
+                v[n].fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), THREADS);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc_close() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 3;
+
+    let q = ConcurrentQueue::<usize>::bounded_faa(2);
+    let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+    let producers = AtomicUsize::new(THREADS);
+
+    Parallel::new()
+        .each(0..THREADS, |_| loop {
+            match q.pop() {
+                Ok(n) => {
+                    v[n].fetch_add(1, Ordering::SeqCst);
+                }
+                Err(PopError::Closed) => break,
+                Err(PopError::Empty) => {}
+            }
+        })
+        .each(0..THREADS, |p| {
+            // Mix pushes and reservations, which are published even if the queue gets closed.
+            for i in (p..COUNT).step_by(THREADS) {
+                if i % 2 == 0 {
+                    while q.push(i).is_err() {}
+                } else {
+                    loop {
+                        if let Ok(slot) = q.reserve() {
+                            slot.write(i);
+                            break;
+                        }
+                    }
+                }
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn drops() {
+    const RUNS: usize = if cfg!(miri) { 10 } else { 100 };
+    const STEPS: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    struct DropCounter;
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    for _ in 0..RUNS {
+        let steps = fastrand::usize(..STEPS);
+        let additional = fastrand::usize(..50);
+
+        DROPS.store(0, Ordering::SeqCst);
+        let q = ConcurrentQueue::bounded_faa(50);
+
+        Parallel::new()
+            .add(|| {
+                for _ in 0..steps {
+                    while q.pop().is_err() {}
+                }
+            })
+            .add(|| {
+                for _ in 0..steps {
+                    while q.push(DropCounter).is_err() {
+                        DROPS.fetch_sub(1, Ordering::SeqCst);
+                    }
+                }
+            })
+            .run();
+
+        for _ in 0..additional {
+            q.push(DropCounter).unwrap();
+        }
+
+        assert_eq!(DROPS.load(Ordering::SeqCst), steps);
+        drop(q);
+        assert_eq!(DROPS.load(Ordering::SeqCst), steps + additional);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn linearizable() {
+    const COUNT: usize = if cfg!(miri) { 500 } else { 1_000 };
+    const THREADS: usize = 4;
+
+    let q = ConcurrentQueue::bounded_faa(THREADS);
+
+    Parallel::new()
+        .each(0..THREADS / 2, |_| {
+            for _ in 0..COUNT {
+                while q.push(0).is_err() {}
+                q.pop().unwrap();
+            }
+        })
+        .each(0..THREADS / 2, |_| {
+            for _ in 0..COUNT {
+                if q.force_push(0).unwrap().is_none() {
+                    q.pop().unwrap();
+                }
+            }
+        })
+        .run();
+}
@@ -0,0 +1,261 @@
+use concurrent_queue::broadcast::{Broadcast, PopError};
+use concurrent_queue::PushError;
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = Broadcast::new(2);
+    let mut a = q.subscribe();
+    let mut b = q.subscribe();
+
+    q.push(7).unwrap();
+    assert_eq!(a.pop(), Ok(7));
+    assert_eq!(b.pop(), Ok(7));
+
+    q.push(8).unwrap();
+    assert_eq!(a.pop(), Ok(8));
+    assert_eq!(a.pop(), Err(PopError::Empty));
+    assert_eq!(b.pop(), Ok(8));
+    assert_eq!(b.pop(), Err(PopError::Empty));
+}
+
+#[test]
+fn capacity() {
+    for i in 1..10 {
+        let q = Broadcast::<i32>::new(i);
+        assert_eq!(q.capacity(), i);
+    }
+}
+
+#[test]
+#[should_panic(expected = "capacity must be positive")]
+fn zero_capacity() {
+    let _ = Broadcast::<i32>::new(0);
+}
+
+#[test]
+fn len_empty() {
+    let q = Broadcast::new(3);
+    let mut s = q.subscribe();
+
+    assert_eq!(s.len(), 0);
+    assert!(s.is_empty());
+
+    for i in 0..10 {
+        q.push(i).unwrap();
+        assert_eq!(s.len(), (i + 1).min(3));
+        assert!(!s.is_empty());
+    }
+
+    assert_eq!(s.pop(), Err(PopError::Lagged(7)));
+    assert_eq!(s.len(), 3);
+    for i in 7..10 {
+        assert_eq!(s.pop(), Ok(i));
+    }
+    assert_eq!(s.len(), 0);
+    assert!(s.is_empty());
+}
+
+#[test]
+fn lagged() {
+    for cap in 1..5 {
+        let q = Broadcast::new(cap);
+        let mut s = q.subscribe();
+
+        for round in 0..5 {
+            let start = round * 10;
+            for i in start..start + 10 {
+                q.push(i).unwrap();
+            }
+
+            assert_eq!(s.pop(), Err(PopError::Lagged(10 - cap as u64)));
+            for i in start + 10 - cap..start + 10 {
+                assert_eq!(s.pop(), Ok(i));
+            }
+            assert_eq!(s.pop(), Err(PopError::Empty));
+        }
+    }
+}
+
+#[test]
+fn subscribe_late() {
+    let q = Broadcast::new(3);
+    q.push(1).unwrap();
+
+    let mut a = q.subscribe();
+    q.push(2).unwrap();
+    let mut b = a.clone();
+    let mut c = q.subscribe();
+    q.push(3).unwrap();
+
+    assert_eq!(a.pop(), Ok(2));
+    assert_eq!(a.pop(), Ok(3));
+    assert_eq!(b.pop(), Ok(2));
+    assert_eq!(b.pop(), Ok(3));
+    assert_eq!(c.pop(), Ok(3));
+    assert_eq!(c.pop(), Err(PopError::Empty));
+}
+
+#[test]
+fn close() {
+    let q = Broadcast::new(2);
+    let mut s = q.subscribe();
+    assert_eq!(q.push(10), Ok(()));
+
+    assert!(!q.is_closed());
+    assert!(q.close());
+
+    assert!(q.is_closed());
+    assert!(!q.close());
+
+    assert_eq!(q.push(20), Err(PushError::Closed(20)));
+    assert_eq!(s.pop(), Ok(10));
+    assert_eq!(s.pop(), Err(PopError::Closed));
+
+    let mut s = q.subscribe();
+    assert_eq!(s.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn drops() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct DropCounter;
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let q = Broadcast::new(5);
+    let mut s = q.subscribe();
+    for _ in 0..3 {
+        q.push(DropCounter).unwrap();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+
+    // Popping clones the item.
+    drop(s.pop());
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+    // Overwriting drops the old item.
+    for _ in 0..5 {
+        q.push(DropCounter).unwrap();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+
+    // The subscriber keeps the items alive.
+    drop(q);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+    drop(s);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 9);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn spawn() {
+    let q = Broadcast::new(10);
+    let mut s = q.subscribe();
+
+    let handle = std::thread::spawn(move || {
+        let mut values = Vec::new();
+        loop {
+            match s.pop() {
+                Ok(i) => values.push(i),
+                Err(PopError::Empty) => std::thread::yield_now(),
+                Err(err) => {
+                    assert_eq!(err, PopError::Closed);
+                    return values;
+                }
+            }
+        }
+    });
+
+    for i in 0..5 {
+        q.push(i).unwrap();
+    }
+    q.close();
+    drop(q);
+
+    assert_eq!(handle.join().unwrap(), [0, 1, 2, 3, 4]);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn spmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 25_000 };
+    const THREADS: usize = 4;
+
+    let q = Broadcast::new(3);
+    let subscribers = (0..THREADS).map(|_| q.subscribe()).collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(subscribers, |mut s| {
+            let mut next = 0;
+            loop {
+                match s.pop() {
+                    Ok(i) => {
+                        assert!(i >= next);
+                        next = i + 1;
+                    }
+                    Err(PopError::Lagged(n)) => next += n as usize,
+                    Err(PopError::Empty) => {}
+                    Err(PopError::Closed) => break,
+                }
+            }
+            assert_eq!(next, COUNT);
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+            q.close();
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 25_000 };
+    const THREADS: usize = 4;
+
+    let q = Broadcast::<usize>::new(THREADS * COUNT);
+    let subscribers = (0..THREADS).map(|_| q.subscribe()).collect::<Vec<_>>();
+    let pushed = AtomicUsize::new(THREADS);
+
+    Parallel::new()
+        .each(subscribers, |mut s| {
+            let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+            loop {
+                match s.pop() {
+                    Ok(i) => {
+                        v[i].fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err(PopError::Lagged(_)) => unreachable!(),
+                    Err(PopError::Empty) => {}
+                    Err(PopError::Closed) => break,
+                }
+            }
+            for c in v {
+                assert_eq!(c.load(Ordering::SeqCst), THREADS);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+            if pushed.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .run();
+}
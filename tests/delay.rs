@@ -0,0 +1,143 @@
+#![cfg(feature = "std")]
+
+use concurrent_queue::delay::DelayQueue;
+use concurrent_queue::{PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+#[cfg(not(target_family = "wasm"))]
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = DelayQueue::unbounded();
+    let now = Instant::now();
+    let later = now + Duration::from_secs(60);
+
+    q.push_at(3, later).unwrap();
+    q.push_at(2, now).unwrap();
+    q.push_at(1, now - Duration::from_millis(1)).unwrap();
+    assert_eq!(q.len(), 3);
+    assert_eq!(q.next_ready_at(), Some(now - Duration::from_millis(1)));
+
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert_eq!(q.next_ready_at(), Some(later));
+    assert_eq!(q.len(), 1);
+}
+
+#[test]
+fn ties() {
+    let q = DelayQueue::unbounded();
+    let now = Instant::now();
+
+    for i in 0..10 {
+        q.push_at(i, now).unwrap();
+        if i == 4 {
+            // Items already moved into the timer heap stay ahead of later ones.
+            assert_eq!(q.next_ready_at(), Some(now));
+        }
+    }
+    for i in 0..10 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+}
+
+#[test]
+fn capacity() {
+    let q = DelayQueue::bounded(2);
+    let later = Instant::now() + Duration::from_secs(60);
+
+    q.push_at(1, later).unwrap();
+    q.push_after(2, Duration::ZERO).unwrap();
+    assert_eq!(q.push_at(3, later), Err(PushError::Full(3)));
+
+    // Popping a ready item makes room.
+    assert_eq!(q.pop(), Ok(2));
+    q.push_at(3, later).unwrap();
+    assert_eq!(q.len(), 2);
+}
+
+#[test]
+fn close() {
+    let q = DelayQueue::bounded(1);
+    q.push_at(1, Instant::now()).unwrap();
+
+    assert!(q.close());
+    assert_eq!(q.push_at(2, Instant::now()), Err(PushError::Closed(2)));
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+    assert_eq!(q.next_ready_at(), None);
+
+    // Delayed items keep the queue open for consumers.
+    let q = DelayQueue::unbounded();
+    q.push_after(1, Duration::from_secs(60)).unwrap();
+    q.close();
+    assert_eq!(q.pop(), Err(PopError::Empty));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn becomes_ready() {
+    const DELAY: Duration = Duration::from_millis(20);
+
+    let q = DelayQueue::unbounded();
+    q.push_after(1, DELAY).unwrap();
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    let at = q.next_ready_at().unwrap();
+    sleep(at.saturating_duration_since(Instant::now()));
+    assert_eq!(q.pop(), Ok(1));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 2;
+
+    let q = DelayQueue::bounded(10);
+    let popped = Mutex::new(Vec::new());
+    let producers = AtomicUsize::new(THREADS);
+    let start = Instant::now();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            let mut items = Vec::new();
+            loop {
+                match q.pop() {
+                    Ok(x) => items.push(x),
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            popped.lock().unwrap().extend(items);
+        })
+        .each(0..THREADS, |t| {
+            for i in (t..COUNT).step_by(THREADS) {
+                let at = start + Duration::from_micros(i as u64 % 50);
+                let mut value = i;
+                while let Err(err) = q.push_at(value, at) {
+                    value = err.into_inner();
+                }
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .run();
+
+    let mut popped = popped.into_inner().unwrap();
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+    assert!(q.is_empty());
+}
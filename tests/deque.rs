@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use concurrent_queue::deque::Worker;
+use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let w = Worker::new(4);
+    let s = w.stealer();
+
+    for i in 0..4 {
+        w.push(i).unwrap();
+    }
+    assert_eq!(w.push(4), Err(PushError::Full(4)));
+    assert_eq!(w.len(), 4);
+    assert_eq!(s.len(), 4);
+
+    assert_eq!(w.pop(), Ok(3));
+    assert_eq!(s.steal(), Ok(0));
+    assert_eq!(w.pop(), Ok(2));
+    assert_eq!(s.clone().steal(), Ok(1));
+    assert_eq!(w.pop(), Err(PopError::Empty));
+    assert_eq!(s.steal(), Err(PopError::Empty));
+    assert!(w.is_empty());
+}
+
+#[test]
+fn wrap_around() {
+    let w = Worker::new(3);
+    let s = w.stealer();
+
+    for i in 0..100 {
+        w.push(i).unwrap();
+        w.push(i + 1000).unwrap();
+        assert_eq!(s.steal(), Ok(i));
+        assert_eq!(w.pop(), Ok(i + 1000));
+    }
+    assert_eq!(w.capacity(), 3);
+    assert!(s.is_empty());
+}
+
+#[test]
+fn steal_batch_into() {
+    let w = Worker::new(8);
+    let s = w.stealer();
+    for i in 0..7 {
+        w.push(i).unwrap();
+    }
+
+    let q = ConcurrentQueue::unbounded();
+    assert_eq!(s.steal_batch_into(&q), 4);
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+
+    // Stealing stops when the destination is full.
+    let q = ConcurrentQueue::bounded(1);
+    assert_eq!(s.steal_batch_into(&q), 1);
+    assert_eq!(q.pop(), Ok(4));
+    assert_eq!(w.len(), 2);
+
+    // Nothing is lost when the destination is closed.
+    q.close();
+    assert_eq!(s.steal_batch_into(&q), 0);
+    assert_eq!(w.pop(), Ok(6));
+    assert_eq!(w.pop(), Ok(5));
+
+    // Nothing is stolen from an empty deque.
+    let q = ConcurrentQueue::unbounded();
+    assert_eq!(s.steal_batch_into(&q), 0);
+    assert!(q.is_empty());
+}
+
+#[test]
+fn drops() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq)]
+    struct DropCounter;
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let w = Worker::new(4);
+    let s = w.stealer();
+    for _ in 0..4 {
+        w.push(DropCounter).unwrap();
+    }
+    drop(s.steal());
+    drop(w.pop());
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+
+    // The remaining items are dropped with the last handle.
+    drop(w);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+    drop(s);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn stress() {
+    const COUNT: usize = if cfg!(miri) { 500 } else { 20_000 };
+    const STEALERS: usize = 2;
+
+    let w = Worker::new(8);
+    let s = w.stealer();
+    let global = ConcurrentQueue::unbounded();
+    let taken = &Mutex::new(Vec::new());
+    let done = &AtomicUsize::new(0);
+
+    Parallel::new()
+        .each(0..STEALERS, |i| {
+            let mut items = Vec::new();
+            while done.load(Ordering::SeqCst) == 0 || !s.is_empty() {
+                if i == 0 {
+                    s.steal_batch_into(&global);
+                } else if let Ok(x) = s.steal() {
+                    items.push(x);
+                }
+            }
+            taken.lock().unwrap().extend(items);
+        })
+        .add(move || {
+            let mut items = Vec::new();
+            for i in 0..COUNT {
+                let mut value = i;
+                while let Err(err) = w.push(value) {
+                    value = err.into_inner();
+                    if let Ok(x) = w.pop() {
+                        items.push(x);
+                    }
+                }
+                if i % 3 == 0 {
+                    if let Ok(x) = w.pop() {
+                        items.push(x);
+                    }
+                }
+            }
+            while let Ok(x) = w.pop() {
+                items.push(x);
+            }
+            done.store(1, Ordering::SeqCst);
+            taken.lock().unwrap().extend(items);
+        })
+        .run();
+
+    let mut taken = std::mem::take(&mut *taken.lock().unwrap());
+    taken.extend(global.try_iter());
+    taken.sort_unstable();
+    assert_eq!(taken, (0..COUNT).collect::<Vec<_>>());
+}
@@ -0,0 +1,152 @@
+#![cfg(feature = "std")]
+
+use concurrent_queue::lane::LaneQueue;
+use concurrent_queue::{PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = LaneQueue::new();
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    assert!(q.add_lane(1, 1));
+    assert!(q.add_bounded_lane(2, 3, 1));
+    assert!(!q.add_lane(2, 1));
+    assert_eq!(q.lanes(), 2);
+
+    q.push(&1, "a").unwrap();
+    q.push(&2, "b").unwrap();
+    assert_eq!(q.push(&3, "c"), Err(PushError::Closed("c")));
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.lane_len(&2), Some(1));
+    assert_eq!(q.lane_len(&3), None);
+
+    assert_eq!(q.pop(), Ok("a"));
+    assert_eq!(q.pop(), Ok("b"));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert!(q.is_empty());
+}
+
+#[test]
+fn weights() {
+    let q = LaneQueue::new();
+    q.add_lane("heavy", 3);
+    q.add_lane("light", 1);
+
+    for i in 0..8 {
+        q.push(&"heavy", i).unwrap();
+    }
+    for i in 100..102 {
+        q.push(&"light", i).unwrap();
+    }
+
+    let popped: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+    assert_eq!(popped, [0, 1, 2, 100, 3, 4, 5, 101, 6, 7]);
+}
+
+#[test]
+fn added_later() {
+    let q = LaneQueue::new();
+    q.add_lane("a", 1);
+    for i in 0..3 {
+        q.push(&"a", i).unwrap();
+    }
+    assert_eq!(q.pop(), Ok(0));
+
+    // A new lane joins the rotation after the current lane.
+    q.add_lane("b", 1);
+    q.push(&"b", 10).unwrap();
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(10));
+    assert_eq!(q.pop(), Ok(2));
+}
+
+#[test]
+fn capacity() {
+    let q = LaneQueue::new();
+    q.add_bounded_lane("small", 1, 1);
+    q.add_bounded_lane("large", 2, 1);
+
+    q.push(&"small", 1).unwrap();
+    assert_eq!(q.push(&"small", 2), Err(PushError::Full(2)));
+    q.push(&"large", 2).unwrap();
+    q.push(&"large", 3).unwrap();
+    assert_eq!(q.push(&"large", 4), Err(PushError::Full(4)));
+}
+
+#[test]
+fn close() {
+    let q = LaneQueue::new();
+    q.add_lane("a", 1);
+    q.add_lane("b", 1);
+    q.push(&"a", 1).unwrap();
+
+    // Closing a lane leaves the others open.
+    assert!(q.close_lane(&"a"));
+    assert!(!q.close_lane(&"a"));
+    assert!(!q.close_lane(&"c"));
+    assert_eq!(q.push(&"a", 2), Err(PushError::Closed(2)));
+    q.push(&"b", 3).unwrap();
+
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Ok(3));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    assert!(q.close());
+    assert!(!q.close());
+    assert!(!q.add_lane("c", 1));
+    assert_eq!(q.push(&"b", 4), Err(PushError::Closed(4)));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const LANES: usize = 3;
+
+    let q = LaneQueue::new();
+    for lane in 0..LANES {
+        q.add_bounded_lane(lane, 4, lane + 1);
+    }
+    let popped = Mutex::new(Vec::new());
+    let producers = AtomicUsize::new(LANES);
+
+    Parallel::new()
+        .each(0..2, |_| {
+            let mut items = Vec::new();
+            loop {
+                match q.pop() {
+                    Ok(x) => items.push(x),
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            popped.lock().unwrap().extend(items);
+        })
+        .each(0..LANES, |lane| {
+            for i in (lane..COUNT).step_by(LANES) {
+                let mut value = i;
+                while let Err(err) = q.push(&lane, value) {
+                    value = err.into_inner();
+                }
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .run();
+
+    let mut popped = popped.into_inner().unwrap();
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+}
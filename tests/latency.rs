@@ -0,0 +1,136 @@
+#![cfg(feature = "latency")]
+
+use concurrent_queue::{ConcurrentQueue, LatencyStats};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+const DELAY: Duration = Duration::from_millis(2);
+
+/// Lets a few items sit in the queue for a while, then returns the latency statistics.
+fn run(q: ConcurrentQueue<usize>) -> LatencyStats {
+    assert_eq!(q.latency_stats(), LatencyStats::default());
+    assert_eq!(q.oldest_item_age(), None);
+
+    q.push(1).unwrap();
+    sleep(DELAY);
+    assert!(q.oldest_item_age().unwrap() >= DELAY);
+    let _ = q.push(2);
+
+    // Reservations are not visible until they are committed.
+    while q.pop().is_ok() {}
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.oldest_item_age(), None);
+    drop(slot);
+    assert_eq!(q.oldest_item_age(), None);
+
+    let stats = q.latency_stats();
+    assert!(stats.max >= DELAY);
+    assert!(stats.total >= DELAY);
+    assert!(stats.mean().is_some());
+    stats
+}
+
+#[test]
+fn single() {
+    let stats = run(ConcurrentQueue::bounded(1));
+    assert_eq!(stats.count, 1);
+}
+
+#[test]
+fn bounded() {
+    let stats = run(ConcurrentQueue::bounded(3));
+    assert_eq!(stats.count, 2);
+}
+
+#[test]
+fn unbounded() {
+    let stats = run(ConcurrentQueue::unbounded());
+    assert_eq!(stats.count, 2);
+}
+
+#[test]
+fn bounded_faa() {
+    let stats = run(ConcurrentQueue::bounded_faa(3));
+    assert_eq!(stats.count, 2);
+}
+
+#[test]
+fn oldest_item_age_skips_removed() {
+    for q in [
+        ConcurrentQueue::bounded(4),
+        ConcurrentQueue::bounded_faa(4),
+        ConcurrentQueue::unbounded(),
+    ] {
+        // The item at the head was removed.
+        q.push(1).unwrap();
+        q.push(2).unwrap();
+        sleep(DELAY);
+        q.retain(|&i| i != 1);
+        assert!(q.oldest_item_age().unwrap() >= DELAY);
+        assert_eq!(q.pop(), Ok(2));
+        assert_eq!(q.oldest_item_age(), None);
+
+        // The reservation at the head was abandoned.
+        drop(q.reserve().unwrap());
+        q.push(3).unwrap();
+        sleep(DELAY);
+        assert!(q.oldest_item_age().unwrap() >= DELAY);
+        assert_eq!(q.pop(), Ok(3));
+        assert_eq!(q.oldest_item_age(), None);
+    }
+}
+
+#[test]
+fn histogram() {
+    let q = ConcurrentQueue::unbounded();
+    for i in 0..10 {
+        q.push(i).unwrap();
+    }
+    sleep(DELAY);
+    while q.pop().is_ok() {}
+
+    // Every item waited at least 2^10 microseconds.
+    let stats = q.latency_stats();
+    assert_eq!(stats.count, 10);
+    assert_eq!(stats.histogram[..10].iter().sum::<u64>(), 0);
+    assert_eq!(stats.histogram[10..].iter().sum::<u64>(), 10);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn oldest_item_age_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    for q in [
+        ConcurrentQueue::bounded(1),
+        ConcurrentQueue::bounded(3),
+        ConcurrentQueue::bounded_faa(3),
+        ConcurrentQueue::unbounded(),
+    ] {
+        Parallel::new()
+            .add(|| {
+                for _ in 0..COUNT {
+                    while q.pop().is_err() {}
+                }
+            })
+            .add(|| {
+                for i in 0..COUNT {
+                    while q.push(i).is_err() {}
+                }
+            })
+            .add(|| {
+                while q.latency_stats().count < COUNT as u64 {
+                    let _ = q.oldest_item_age();
+                }
+            })
+            .run();
+
+        assert_eq!(q.oldest_item_age(), None);
+    }
+}
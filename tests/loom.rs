@@ -213,7 +213,7 @@ impl Event {
     }
 }
 
-/// Wrapper to run tests on all three queues.
+/// Wrapper to run tests on all five queues.
 fn run_test<F: Fn(ConcurrentQueue<usize>, usize) + Send + Sync + Clone + 'static>(f: F) {
     // The length of a loom test seems to increase exponentially the higher this number is.
     const LIMIT: usize = 4;
@@ -228,8 +228,18 @@ fn run_test<F: Fn(ConcurrentQueue<usize>, usize) + Send + Sync + Clone + 'static
         fc(ConcurrentQueue::bounded(LIMIT / 2), LIMIT);
     });
 
+    let fc = f.clone();
+    loom::model(move || {
+        fc(ConcurrentQueue::bounded_faa(LIMIT / 2), LIMIT);
+    });
+
+    let fc = f.clone();
+    loom::model(move || {
+        fc(ConcurrentQueue::unbounded(), LIMIT);
+    });
+
     loom::model(move || {
-        f(ConcurrentQueue::unbounded(), LIMIT);
+        f(ConcurrentQueue::unbounded_nonblocking(), LIMIT);
     });
 }
 
@@ -305,3 +315,129 @@ fn spsc_force() {
         handle.join().unwrap();
     });
 }
+
+#[test]
+fn nonblocking_close() {
+    loom::model(|| {
+        let q = Arc::new(ConcurrentQueue::unbounded_nonblocking());
+
+        let handle = thread::spawn({
+            let q = q.clone();
+            move || q.push(1).is_ok()
+        });
+
+        // A pop can skip the item while it is being pushed, and the queue is closed before it is
+        // pushed again.
+        let mut values: Vec<_> = q.pop().ok().into_iter().collect();
+        q.close();
+
+        // The queue is only reported as closed once the item is popped.
+        loop {
+            match q.pop() {
+                Ok(value) => values.push(value),
+                Err(PopError::Empty) => thread::yield_now(),
+                Err(PopError::Closed) => break,
+            }
+        }
+
+        let pushed = handle.join().unwrap();
+        assert_eq!(values.len(), pushed as usize);
+    });
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn pop_with_loss() {
+    fn check(q: ConcurrentQueue<usize>) {
+        let q = Arc::new(q);
+
+        let handle = thread::spawn({
+            let q = q.clone();
+            move || {
+                for i in 0..3 {
+                    q.force_push(i).unwrap();
+                }
+            }
+        });
+
+        // Every gap between the popped items is reported right before the item after it, even
+        // if items are displaced while popping.
+        let mut next = 0;
+        let mut pop = || {
+            let (value, lost) = q.pop_with_loss()?;
+            assert_eq!(lost, (value - next) as u64);
+            next = value + 1;
+            Ok::<_, PopError>(())
+        };
+
+        let _ = pop();
+        handle.join().unwrap();
+        q.force_push(3).unwrap();
+        while pop().is_ok() {}
+        assert_eq!(next, 4);
+    }
+
+    loom::model(|| check(ConcurrentQueue::bounded(1)));
+    loom::model(|| check(ConcurrentQueue::bounded(2)));
+}
+
+#[test]
+fn deque() {
+    use concurrent_queue::deque::Worker;
+
+    loom::model(|| {
+        let w = Worker::new(2);
+        let s = w.stealer();
+
+        w.push(1).unwrap();
+        w.push(2).unwrap();
+
+        // Race a thief for the items, including the last one.
+        let handle = thread::spawn(move || s.steal().ok());
+        let mut values: Vec<_> = std::iter::from_fn(|| w.pop().ok()).collect();
+        values.extend(handle.join().unwrap());
+
+        values.sort_unstable();
+        assert_eq!(values, [1, 2]);
+
+        // The slots are free again once every item is taken.
+        w.push(3).unwrap();
+        w.push(4).unwrap();
+        assert_eq!(w.pop(), Ok(4));
+    });
+}
+
+#[test]
+fn broadcast() {
+    use concurrent_queue::broadcast::{Broadcast, PopError};
+
+    loom::model(|| {
+        let q = Broadcast::new(1);
+        let mut s = q.subscribe();
+
+        // The second push overwrites the first item, possibly while it is being read. The
+        // subscriber keeps the ring alive after the queue is dropped.
+        let handle = thread::spawn(move || {
+            q.push(1).unwrap();
+            q.push(2).unwrap();
+            q.close();
+        });
+
+        let mut values = Vec::new();
+        let mut missed = 0;
+        loop {
+            match s.pop() {
+                Ok(value) => values.push(value),
+                Err(PopError::Lagged(n)) => missed += n,
+                Err(PopError::Empty) => thread::yield_now(),
+                Err(PopError::Closed) => break,
+            }
+        }
+        handle.join().unwrap();
+
+        // Every item is either popped once, in order, or reported as missed.
+        assert_eq!(values.last(), Some(&2));
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(values.len() as u64 + missed, 2);
+    });
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "metrics")]
+
+use concurrent_queue::{ConcurrentQueue, OverflowPolicy, Stats};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+/// Pushes and pops a fixed sequence of items, then returns the counters.
+fn run(q: ConcurrentQueue<usize>) -> Stats {
+    assert_eq!(q.stats(), Stats::default());
+
+    for i in 0..5 {
+        let _ = q.push(i);
+    }
+    let _ = q.force_push(5);
+    while q.pop().is_ok() {}
+    let _ = q.pop_ref();
+
+    // Abandoned reservations are not pushes.
+    drop(q.reserve().unwrap());
+    let _ = q.pop();
+
+    q.stats()
+}
+
+#[test]
+fn single() {
+    let stats = run(ConcurrentQueue::bounded(1));
+    assert_eq!(stats.pushes, 2);
+    assert_eq!(stats.pops, 1);
+    assert_eq!(stats.full, 4);
+    assert_eq!(stats.displaced, 1);
+}
+
+#[test]
+fn bounded() {
+    let stats = run(ConcurrentQueue::bounded(3));
+    assert_eq!(stats.pushes, 4);
+    assert_eq!(stats.pops, 3);
+    assert_eq!(stats.full, 2);
+    assert_eq!(stats.displaced, 1);
+}
+
+#[test]
+fn unbounded() {
+    let stats = run(ConcurrentQueue::unbounded());
+    assert_eq!(stats.pushes, 6);
+    assert_eq!(stats.pops, 6);
+    assert_eq!(stats.full, 0);
+    assert_eq!(stats.displaced, 0);
+}
+
+#[test]
+fn overflow_policy() {
+    let q = ConcurrentQueue::bounded_with_policy(2, OverflowPolicy::DropNewest);
+    for i in 0..5 {
+        q.push(i).unwrap();
+    }
+
+    let stats = q.stats();
+    assert_eq!(stats.pushes, 2);
+    assert_eq!(stats.full, 3);
+}
+
+#[test]
+fn occupancy() {
+    let q = ConcurrentQueue::unbounded();
+    for i in 0..100 {
+        q.push(i).unwrap();
+    }
+
+    let mut expected = [0; 16];
+    expected[..7].copy_from_slice(&[1, 2, 4, 8, 16, 32, 37]);
+    assert_eq!(q.stats().occupancy, expected);
+
+    // Bounded queues fill up the buckets the same way.
+    let q = ConcurrentQueue::bounded(5);
+    for i in 0..5 {
+        q.push(i).unwrap();
+    }
+    q.force_push(5).unwrap();
+    assert_eq!(q.stats().occupancy[..4], [1, 2, 3, 0]);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn contention() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 2;
+
+    for q in [ConcurrentQueue::bounded(1), ConcurrentQueue::bounded(2)] {
+        Parallel::new()
+            .each(0..THREADS, |_| {
+                for _ in 0..COUNT {
+                    while q.pop().is_err() {}
+                }
+            })
+            .each(0..THREADS, |_| {
+                for i in 0..COUNT {
+                    while q.push(i).is_err() {}
+                }
+            })
+            .run();
+
+        let stats = q.stats();
+        assert_eq!(stats.pushes, (COUNT * THREADS) as u64);
+        assert_eq!(stats.pops, (COUNT * THREADS) as u64);
+    }
+}
@@ -0,0 +1,146 @@
+use concurrent_queue::priority::PriorityQueue;
+use concurrent_queue::{PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = PriorityQueue::unbounded(3);
+    for i in 0..9 {
+        q.push(i, i % 3).unwrap();
+    }
+    assert_eq!(q.len(), 9);
+    assert_eq!(q.len_at(2), 3);
+
+    let popped: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+    assert_eq!(popped, [2, 5, 8, 1, 4, 7, 0, 3, 6]);
+    assert!(q.is_empty());
+}
+
+#[test]
+fn capacity() {
+    let q = PriorityQueue::bounded(2, 2);
+    assert_eq!(q.capacity(), Some(2));
+
+    q.push(1, 0).unwrap();
+    q.push(2, 0).unwrap();
+    assert_eq!(q.push(3, 0), Err(PushError::Full(3)));
+
+    // Each level has its own capacity.
+    q.push(3, 1).unwrap();
+    assert_eq!(q.len(), 3);
+}
+
+#[test]
+fn close() {
+    let q = PriorityQueue::unbounded(3);
+    q.push(1, 0).unwrap();
+    q.push(2, 2).unwrap();
+
+    assert!(q.close());
+    assert!(q.is_closed());
+    assert!(!q.close());
+
+    for priority in 0..3 {
+        assert_eq!(q.push(3, priority), Err(PushError::Closed(3)));
+    }
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+#[should_panic(expected = "priority 2 is out of range for 2 levels")]
+fn out_of_range() {
+    let q = PriorityQueue::unbounded(2);
+    let _ = q.push(1, 2);
+}
+
+#[test]
+#[should_panic(expected = "priority 2 is out of range for 2 levels")]
+fn len_at_out_of_range() {
+    let q = PriorityQueue::<i32>::unbounded(2);
+    q.len_at(2);
+}
+
+#[test]
+fn aging() {
+    const INTERVAL: usize = 4;
+
+    let q = PriorityQueue::unbounded(3).with_aging(INTERVAL);
+    for i in 0..100 {
+        q.push(i, 2).unwrap();
+    }
+    q.push(100, 1).unwrap();
+    q.push(200, 0).unwrap();
+
+    let popped: Vec<_> = (0..3 * INTERVAL).map(|_| q.pop().unwrap()).collect();
+
+    // Aged pops start at the lowest level, then the next one up.
+    assert_eq!(popped[INTERVAL - 1], 200);
+    assert_eq!(popped[2 * INTERVAL - 1], 100);
+    assert!(popped[3 * INTERVAL - 1] < 100);
+
+    // Pops that find the queue empty take a turn too.
+    let q = PriorityQueue::unbounded(2).with_aging(2);
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    q.push(10, 1).unwrap();
+    q.push(20, 0).unwrap();
+    assert_eq!(q.pop(), Ok(20));
+
+    // Without aging, the lower levels wait.
+    let q = PriorityQueue::unbounded(2);
+    for i in 0..10 {
+        q.push(i, 1).unwrap();
+    }
+    q.push(100, 0).unwrap();
+    assert!((0..10).all(|_| q.pop().unwrap() < 100));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 10_000 };
+    const THREADS: usize = 2;
+
+    let q = PriorityQueue::bounded(3, 8).with_aging(8);
+    let popped = Mutex::new(Vec::new());
+    let producers = AtomicUsize::new(THREADS);
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            let mut items = Vec::new();
+            loop {
+                match q.pop() {
+                    Ok(x) => items.push(x),
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            popped.lock().unwrap().extend(items);
+        })
+        .each(0..THREADS, |t| {
+            for i in (t..COUNT).step_by(THREADS) {
+                let mut value = i;
+                while let Err(err) = q.push(value, i % 3) {
+                    value = err.into_inner();
+                }
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .run();
+
+    let mut popped = popped.into_inner().unwrap();
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+}
@@ -0,0 +1,115 @@
+use concurrent_queue::{pop_any, ConcurrentQueue, PopError, Select};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn biased() {
+    let a = ConcurrentQueue::bounded(1);
+    let b = ConcurrentQueue::bounded(5);
+    let c = ConcurrentQueue::unbounded();
+    let queues = [&a, &b, &c];
+
+    for i in 0..3 {
+        c.push(i + 20).unwrap();
+        b.push(i + 10).unwrap();
+    }
+    a.push(0).unwrap();
+
+    let popped: Vec<_> = std::iter::from_fn(|| pop_any(&queues).ok()).collect();
+    assert_eq!(
+        popped,
+        [(0, 0), (1, 10), (1, 11), (1, 12), (2, 20), (2, 21), (2, 22)]
+    );
+}
+
+#[test]
+fn fair() {
+    let a = ConcurrentQueue::unbounded();
+    let b = ConcurrentQueue::unbounded();
+    let c = ConcurrentQueue::unbounded();
+    let queues = [&a, &b, &c];
+
+    for i in 0..3 {
+        a.push(i).unwrap();
+    }
+    b.push(10).unwrap();
+    c.push(20).unwrap();
+    c.push(21).unwrap();
+
+    let mut select = Select::fair(&queues);
+    let popped: Vec<_> = std::iter::from_fn(|| select.pop().ok()).collect();
+    assert_eq!(popped, [(0, 0), (1, 10), (2, 20), (0, 1), (2, 21), (0, 2)]);
+}
+
+#[test]
+fn closed() {
+    let a = ConcurrentQueue::unbounded();
+    let b = ConcurrentQueue::unbounded();
+    let queues = [&a, &b];
+
+    a.push(1).unwrap();
+    a.close();
+    assert_eq!(pop_any(&queues), Ok((0, 1)));
+    assert_eq!(pop_any(&queues), Err(PopError::Empty));
+
+    // A closed queue with items left is not closed yet.
+    b.push(2).unwrap();
+    b.close();
+    assert_eq!(pop_any(&queues), Ok((1, 2)));
+    assert_eq!(pop_any(&queues), Err(PopError::Closed));
+
+    // So is an empty set of queues.
+    assert_eq!(pop_any::<i32>(&[]), Err(PopError::Closed));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let a = ConcurrentQueue::bounded(3);
+    let b = ConcurrentQueue::unbounded();
+    let popped = Mutex::new(Vec::new());
+
+    Parallel::new()
+        .each(0..2, |fair| {
+            let queues = [&a, &b];
+            let mut select = if fair == 1 {
+                Select::fair(&queues)
+            } else {
+                Select::biased(&queues)
+            };
+            let mut items = Vec::new();
+            loop {
+                match select.pop() {
+                    Ok((index, x)) => items.push((x % 2, index)),
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            popped.lock().unwrap().extend(items);
+        })
+        .add(|| {
+            for i in (0..COUNT).step_by(2) {
+                while a.push(i).is_err() {}
+            }
+            a.close();
+        })
+        .add(|| {
+            for i in (1..COUNT).step_by(2) {
+                b.push(i).unwrap();
+            }
+            b.close();
+        })
+        .run();
+
+    let popped = popped.into_inner().unwrap();
+    assert_eq!(popped.len(), COUNT);
+    assert!(popped.iter().all(|&(parity, index)| parity == index));
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "seq")]
+
+use concurrent_queue::{ConcurrentQueue, PopError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn abandoned_reservation() {
+    for q in [
+        ConcurrentQueue::bounded(1),
+        ConcurrentQueue::bounded(4),
+        ConcurrentQueue::bounded_faa(4),
+        ConcurrentQueue::unbounded(),
+        ConcurrentQueue::unbounded_nonblocking(),
+    ] {
+        assert_eq!(q.push_seq(10), Ok(0));
+        assert_eq!(q.pop_seq(), Ok((0, 10)));
+
+        // Every backend uses up a number for a reservation that is dropped.
+        drop(q.reserve().unwrap());
+        assert_eq!(q.push_seq(20), Ok(2));
+        assert_eq!(q.pop_seq(), Ok((2, 20)));
+        assert_eq!(q.pop_seq(), Err(PopError::Empty));
+    }
+}
+
+#[test]
+fn pop_with_loss() {
+    for q in [ConcurrentQueue::bounded(2), ConcurrentQueue::bounded_faa(2)] {
+        for i in 0..5 {
+            q.force_push(i).unwrap();
+        }
+        assert_eq!(q.pop_with_loss(), Ok((3, 3)));
+        assert_eq!(q.pop_with_loss(), Ok((4, 0)));
+        assert_eq!(q.pop_with_loss(), Err(PopError::Empty));
+
+        // Items lost before a removed item are reported with the next one.
+        for i in 5..9 {
+            q.force_push(i).unwrap();
+        }
+        q.retain(|&x| x != 7);
+        assert_eq!(q.pop_with_loss(), Ok((8, 2)));
+
+        // Even if it is pushed later.
+        for i in 9..12 {
+            q.force_push(i).unwrap();
+        }
+        q.retain(|_| false);
+        q.force_push(12).unwrap();
+        assert_eq!(q.pop_with_loss(), Ok((12, 1)));
+    }
+
+    let q = ConcurrentQueue::unbounded();
+    q.push(1).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((1, 0)));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn pop_with_loss_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 10_000 };
+
+    for q in [
+        ConcurrentQueue::bounded(1),
+        ConcurrentQueue::bounded(4),
+        ConcurrentQueue::bounded_faa(4),
+    ] {
+        Parallel::new()
+            .add(|| {
+                for i in 0..COUNT {
+                    q.force_push(i).unwrap();
+                }
+            })
+            .finish(|| {
+                let mut next = 0;
+                while next < COUNT {
+                    if let Ok((value, lost)) = q.pop_with_loss() {
+                        // Every gap is made of displaced items.
+                        assert_eq!(lost, (value - next) as u64);
+                        next = value + 1;
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "serde")]
+
+use concurrent_queue::{ConcurrentQueue, OverflowPolicy, PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+/// Serializes a queue into JSON and back.
+fn round_trip(q: &ConcurrentQueue<String>) -> (String, ConcurrentQueue<String>) {
+    let json = serde_json::to_string(q).unwrap();
+    let q = serde_json::from_str(&json).unwrap();
+    (json, q)
+}
+
+#[test]
+fn single() {
+    let q = ConcurrentQueue::bounded(1);
+    q.push("a".to_string()).unwrap();
+
+    let (json, r) = round_trip(&q);
+    assert_eq!(json, r#"{"capacity":1,"closed":false,"items":["a"]}"#);
+    assert_eq!(r.capacity(), Some(1));
+    assert_eq!(
+        r.push("b".to_string()),
+        Err(PushError::Full("b".to_string()))
+    );
+    assert_eq!(r.pop().as_deref(), Ok("a"));
+
+    // The original queue is left alone.
+    assert_eq!(q.pop().as_deref(), Ok("a"));
+}
+
+#[test]
+fn bounded() {
+    let q = ConcurrentQueue::bounded(3);
+    for i in 0..5 {
+        q.force_push(i.to_string()).unwrap();
+    }
+    q.close();
+
+    let (json, r) = round_trip(&q);
+    assert_eq!(
+        json,
+        r#"{"capacity":3,"closed":true,"items":["2","3","4"]}"#
+    );
+    assert!(r.is_closed());
+    assert_eq!(r.try_iter().collect::<Vec<_>>(), ["2", "3", "4"]);
+    assert_eq!(r.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn not_clone() {
+    /// An item that can be serialized but not cloned.
+    #[derive(Debug, serde::Serialize)]
+    struct Packet(u32);
+
+    let q = ConcurrentQueue::unbounded();
+    q.push(Packet(1)).unwrap();
+    q.push(Packet(2)).unwrap();
+
+    let json = serde_json::to_string(&q).unwrap();
+    assert_eq!(json, r#"{"capacity":null,"closed":false,"items":[1,2]}"#);
+    assert_eq!(q.len(), 2);
+}
+
+#[test]
+fn unbounded() {
+    let q = ConcurrentQueue::unbounded();
+    for i in 0..100 {
+        q.push(i.to_string()).unwrap();
+    }
+
+    let (json, r) = round_trip(&q);
+    assert!(json.starts_with(r#"{"capacity":null,"closed":false,"items":["0","1","#));
+    assert_eq!(r.capacity(), None);
+    assert!(!r.is_closed());
+    assert!(r.try_iter().eq(q.try_iter()));
+}
+
+#[test]
+fn settings() {
+    let q = ConcurrentQueue::bounded_with_policy(1, OverflowPolicy::DropOldest).with_name("q");
+    q.push("a".to_string()).unwrap();
+
+    // The overflow policy and the name are left out.
+    let (json, r) = round_trip(&q);
+    assert_eq!(json, r#"{"capacity":1,"closed":false,"items":["a"]}"#);
+    assert_eq!(r.overflow_policy(), OverflowPolicy::Reject);
+    assert_eq!(r.name(), None);
+    assert_eq!(
+        r.push("b".to_string()),
+        Err(PushError::Full("b".to_string()))
+    );
+}
+
+#[test]
+fn invalid() {
+    let err = serde_json::from_str::<ConcurrentQueue<i32>>(
+        r#"{"capacity":2,"closed":false,"items":[1,2,3]}"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid length 3, expected at most 2 items"
+    );
+
+    let err =
+        serde_json::from_str::<ConcurrentQueue<i32>>(r#"{"capacity":0,"closed":false,"items":[]}"#)
+            .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value: integer `0`, expected a positive capacity"
+    );
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::unbounded();
+    let done = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+        .add(|| {
+            for _ in 0..COUNT {
+                while q.pop().is_err() {}
+            }
+        })
+        .add(|| {
+            while !done.load(Ordering::SeqCst) {
+                // With one producer, every snapshot is a run of consecutive items.
+                let json = serde_json::to_string(&q).unwrap();
+                let r: ConcurrentQueue<usize> = serde_json::from_str(&json).unwrap();
+                let items: Vec<_> = r.try_iter().collect();
+                assert!(items.windows(2).all(|w| w[0] + 1 == w[1]));
+            }
+        })
+        .run();
+}
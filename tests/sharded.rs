@@ -0,0 +1,194 @@
+#![cfg(feature = "std")]
+
+use concurrent_queue::sharded::{ShardPolicy, ShardedQueue};
+use concurrent_queue::{PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Mutex;
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = ShardedQueue::unbounded(4);
+    for i in 0..10 {
+        q.push(i).unwrap();
+    }
+    assert_eq!(q.len(), 10);
+    assert_eq!(q.capacity(), None);
+
+    // Items pushed by one thread stay in order.
+    let popped: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+    assert_eq!(popped, (0..10).collect::<Vec<_>>());
+    assert!(q.is_empty());
+}
+
+#[test]
+fn capacity() {
+    for policy in [ShardPolicy::Thread, ShardPolicy::ChoiceOfTwo] {
+        let q = ShardedQueue::bounded(3, 2).with_policy(policy);
+        assert_eq!(q.capacity(), Some(6));
+
+        // Pushes move on to other shards until every one of them is full.
+        for i in 0..6 {
+            assert_eq!(q.push(i), Ok(()));
+        }
+        assert!(q.is_full());
+        assert_eq!(q.push(6), Err(PushError::Full(6)));
+
+        // Pops move on to other shards until every one of them is empty.
+        let mut popped: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+        popped.sort_unstable();
+        assert_eq!(popped, (0..6).collect::<Vec<_>>());
+        assert!(q.is_empty());
+    }
+}
+
+#[test]
+fn close() {
+    let q = ShardedQueue::unbounded(3).with_policy(ShardPolicy::ChoiceOfTwo);
+    q.push(1).unwrap();
+    q.push(2).unwrap();
+
+    assert!(q.close());
+    assert!(q.is_closed());
+    assert!(!q.close());
+    assert_eq!(q.push(3), Err(PushError::Closed(3)));
+
+    // Remaining items can still be popped.
+    let mut popped = vec![q.pop().unwrap(), q.pop().unwrap()];
+    popped.sort_unstable();
+    assert_eq!(popped, [1, 2]);
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn choice_of_two() {
+    let q = ShardedQueue::unbounded(4).with_policy(ShardPolicy::ChoiceOfTwo);
+    for i in 0..400 {
+        q.push(i).unwrap();
+    }
+    assert_eq!(q.len(), 400);
+
+    // Items from a single thread are spread over the shards, so they come out of order.
+    let popped: Vec<_> = std::iter::from_fn(|| q.pop().ok()).collect();
+    assert_ne!(popped, (0..400).collect::<Vec<_>>());
+
+    let mut sorted = popped;
+    sorted.sort_unstable();
+    assert_eq!(sorted, (0..400).collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic(expected = "number of shards must be positive")]
+fn zero_shards() {
+    ShardedQueue::<i32>::unbounded(0);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn thread_order() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const PRODUCERS: usize = 3;
+
+    let q = ShardedQueue::unbounded(2);
+    let producers = AtomicUsize::new(PRODUCERS);
+
+    Parallel::new()
+        .each(0..PRODUCERS, |p| {
+            for i in 0..COUNT {
+                q.push((p, i)).unwrap();
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                q.close();
+            }
+        })
+        .add(|| {
+            // Each producer's items stay in its own shard, so they arrive in order.
+            let mut next = [0; PRODUCERS];
+            loop {
+                match q.pop() {
+                    Ok((p, i)) => {
+                        assert_eq!(i, next[p]);
+                        next[p] += 1;
+                    }
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            assert_eq!(next, [COUNT; PRODUCERS]);
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn close_while_pushing() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    for _ in 0..COUNT / 100 {
+        let q = ShardedQueue::unbounded(4);
+
+        Parallel::new()
+            .add(|| {
+                q.close();
+            })
+            .add(|| {
+                for i in 0..COUNT {
+                    // A push is only rejected as closed once the whole queue is closed.
+                    if let Err(err) = q.push(i) {
+                        assert_eq!(err, PushError::Closed(i));
+                        assert!(q.is_closed());
+                    }
+                }
+            })
+            .run();
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THREADS: usize = 3;
+
+    for policy in [ShardPolicy::Thread, ShardPolicy::ChoiceOfTwo] {
+        let q = ShardedQueue::bounded(4, 2).with_policy(policy);
+        let popped = Mutex::new(Vec::new());
+        let producers = AtomicUsize::new(THREADS);
+
+        Parallel::new()
+            .each(0..THREADS, |_| {
+                let mut items = Vec::new();
+                loop {
+                    match q.pop() {
+                        Ok(x) => items.push(x),
+                        Err(PopError::Closed) => break,
+                        Err(PopError::Empty) => {}
+                    }
+                }
+                popped.lock().unwrap().extend(items);
+            })
+            .each(0..THREADS, |p| {
+                for i in (p..COUNT).step_by(THREADS) {
+                    let mut value = i;
+                    while let Err(err) = q.push(value) {
+                        value = err.into_inner();
+                    }
+                }
+                if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    q.close();
+                }
+            })
+            .run();
+
+        let mut popped = popped.into_inner().unwrap();
+        popped.sort_unstable();
+        assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+    }
+}
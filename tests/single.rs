@@ -1,6 +1,8 @@
 #![allow(clippy::bool_assert_comparison)]
 
-use concurrent_queue::{ConcurrentQueue, ForcePushError, PopError, PushError};
+use concurrent_queue::{
+    ConcurrentQueue, ForcePushError, PopError, PushError, TryPopError, TryPushError,
+};
 
 #[cfg(not(target_family = "wasm"))]
 use easy_parallel::Parallel;
@@ -80,6 +82,259 @@ fn force_push() {
     assert_eq!(q.pop(), Err(PopError::Closed));
 }
 
+#[test]
+fn reserve() {
+    let q = ConcurrentQueue::bounded(1);
+
+    let mut slot = q.reserve().unwrap();
+    slot.value_mut().write(1);
+    unsafe { slot.commit() };
+
+    assert_eq!(q.reserve().err(), Some(PushError::Full(())));
+    assert_eq!(q.pop(), Ok(1));
+
+    q.close();
+    assert_eq!(q.reserve().err(), Some(PushError::Closed(())));
+}
+
+#[test]
+fn reserve_abandon() {
+    let q = ConcurrentQueue::<String>::bounded(1);
+
+    drop(q.reserve().unwrap());
+    assert_eq!(q.len(), 0);
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    q.push("hello".to_string()).unwrap();
+    assert_eq!(q.pop().as_deref(), Ok("hello"));
+}
+
+#[test]
+fn force_push_held() {
+    let q = ConcurrentQueue::bounded(1);
+    q.push(1).unwrap();
+
+    // The slot is held, so there is nothing to displace.
+    let item = q.pop_ref().unwrap();
+    assert_eq!(q.try_force_push(2), Err(TryPushError::Busy(2)));
+    assert!(!q.is_closed());
+    assert!(q.reserve().is_err());
+    assert_eq!(q.try_push_nowait(2), Err(TryPushError::Full(2)));
+
+    drop(item);
+    assert_eq!(q.force_push(3), Ok(None));
+    assert_eq!(q.pop(), Ok(3));
+}
+
+#[test]
+fn force_push_reserved() {
+    let q = ConcurrentQueue::bounded(1);
+
+    // The slot is reserved, so there is nothing to displace.
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.try_force_push(1), Err(TryPushError::Busy(1)));
+    assert!(!q.is_closed());
+
+    slot.write(0);
+    assert_eq!(q.force_push(2), Ok(Some(0)));
+    assert_eq!(q.pop(), Ok(2));
+}
+
+#[test]
+fn pop_ref() {
+    let q = ConcurrentQueue::bounded(1);
+    q.push(vec![1]).unwrap();
+
+    let mut item = q.pop_ref().unwrap();
+    item.push(10);
+    assert_eq!(*item, [1, 10]);
+    assert_eq!(q.len(), 0);
+
+    // The held slot is still occupied.
+    assert_eq!(q.push(vec![2]), Err(PushError::Full(vec![2])));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    drop(item);
+    assert_eq!(q.push(vec![2]), Ok(()));
+    assert_eq!(q.pop_ref().unwrap().into_inner(), [2]);
+
+    q.close();
+    assert_eq!(q.pop_ref().err(), Some(PopError::Closed));
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn pop_with_loss() {
+    let q = ConcurrentQueue::bounded(1);
+
+    for i in 0..10 {
+        q.force_push(i).unwrap();
+    }
+    assert_eq!(q.pop_with_loss(), Ok((9, 9)));
+    assert_eq!(q.pop_with_loss(), Err(PopError::Empty));
+
+    q.push(10).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((10, 0)));
+
+    // Items lost before an item popped by another method aren't reported again.
+    q.push(11).unwrap();
+    q.force_push(12).unwrap();
+    assert_eq!(q.pop(), Ok(12));
+    q.push(13).unwrap();
+    q.force_push(14).unwrap();
+    drop(q.pop_ref().unwrap());
+    q.push(15).unwrap();
+    assert_eq!(q.pop_with_loss(), Ok((15, 0)));
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn seq() {
+    let q = ConcurrentQueue::bounded(1);
+
+    assert_eq!(q.push_seq(10), Ok(0));
+    assert_eq!(q.push_seq(20), Err(PushError::Full(20)));
+    assert_eq!(q.pop_seq(), Ok((0, 10)));
+
+    // Displaced items and abandoned reservations leave gaps.
+    q.force_push(20).unwrap();
+    q.force_push(30).unwrap();
+    assert_eq!(q.pop_ref().unwrap().seq(), 2);
+    drop(q.reserve().unwrap());
+    assert_eq!(q.push_seq(40), Ok(4));
+    assert_eq!(q.pop_seq(), Ok((4, 40)));
+    assert_eq!(q.pop_seq(), Err(PopError::Empty));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn high_water_mark() {
+    let q = ConcurrentQueue::bounded(1);
+    assert_eq!(q.high_water_mark(), 0);
+
+    q.push(1).unwrap();
+    q.pop().unwrap();
+    assert_eq!(q.high_water_mark(), 1);
+
+    q.reset_high_water_mark();
+    assert_eq!(q.high_water_mark(), 0);
+
+    q.force_push(2).unwrap();
+    assert_eq!(q.high_water_mark(), 1);
+}
+
+#[test]
+fn debug_items() {
+    let q = ConcurrentQueue::bounded(1).with_name("single");
+    assert_eq!(format!("{:?}", q.debug_items()), "[]");
+    assert_eq!(
+        format!("{:?}", q),
+        "ConcurrentQueue { name: Some(\"single\"), len: 0, capacity: Some(1), is_closed: false }"
+    );
+
+    q.push(7).unwrap();
+    assert_eq!(format!("{:?}", q.debug_items()), "[7]");
+
+    // Held slots are not in the queue.
+    let slot = q.pop_ref().unwrap();
+    assert_eq!(format!("{:?}", q.debug_items()), "[]");
+    drop(slot);
+
+    let slot = q.reserve().unwrap();
+    assert_eq!(format!("{:?}", q.debug_items()), "[]");
+    slot.write(8);
+    assert_eq!(format!("{:?}", q.debug_items()), "[8]");
+    assert_eq!(q.pop(), Ok(8));
+}
+
+#[test]
+fn snapshot() {
+    let q = ConcurrentQueue::bounded(1);
+    assert_eq!(q.snapshot(), Vec::<String>::new());
+
+    q.push("a".to_string()).unwrap();
+    q.close();
+    assert_eq!(q.snapshot(), ["a"]);
+    assert_eq!(q.pop().as_deref(), Ok("a"));
+    assert_eq!(q.snapshot(), Vec::<String>::new());
+}
+
+#[test]
+fn retain() {
+    let q = ConcurrentQueue::bounded(1);
+    q.push(1).unwrap();
+
+    q.retain(|&i| i == 1);
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.remove_if(|&i| i == 2), []);
+    assert_eq!(q.remove_if(|&i| i == 1), [1]);
+    assert!(q.is_empty());
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    // The queue can be used again.
+    q.push(2).unwrap();
+    q.retain(|_| false);
+    assert_eq!(q.push(3), Ok(()));
+    assert_eq!(q.pop(), Ok(3));
+}
+
+#[test]
+fn nowait() {
+    let q = ConcurrentQueue::bounded(1);
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+    assert_eq!(q.try_push_nowait(1), Ok(()));
+    assert_eq!(q.try_push_nowait(2), Err(TryPushError::Full(2)));
+
+    // The slot is locked while it is being inspected.
+    q.retain(|_| {
+        assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+        assert_eq!(q.try_push_nowait(2), Err(TryPushError::Busy(2)));
+        true
+    });
+
+    // The slot stays full while it is held.
+    let item = q.pop_ref().unwrap();
+    assert_eq!(q.try_push_nowait(2), Err(TryPushError::Full(2)));
+    assert_eq!(item.into_inner(), 1);
+
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+    slot.write(2);
+    assert_eq!(q.try_pop_nowait(), Ok(2));
+
+    q.close();
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Closed(3)));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Closed));
+}
+
+#[test]
+fn steal_into() {
+    let src = ConcurrentQueue::bounded(1);
+    let dest = ConcurrentQueue::bounded(1);
+    assert_eq!(src.steal_into(&dest, 1), 0);
+
+    src.push(1).unwrap();
+    assert_eq!(src.steal_into(&dest, 0), 0);
+    assert_eq!(src.steal_into(&dest, 1), 1);
+    assert!(src.is_empty());
+
+    // Nothing is lost when the destination is full or closed.
+    src.push(2).unwrap();
+    assert_eq!(src.steal_into(&dest, 1), 0);
+    assert_eq!(dest.pop(), Ok(1));
+    dest.close();
+    assert_eq!(src.steal_into(&dest, 1), 0);
+    assert_eq!(src.pop(), Ok(2));
+
+    // An item that is still being written is not waited for.
+    let q = ConcurrentQueue::unbounded();
+    let slot = src.reserve().unwrap();
+    assert_eq!(src.steal_into(&q, 1), 0);
+    slot.write(3);
+    assert_eq!(src.steal_into(&q, 1), 1);
+    assert_eq!(q.pop(), Ok(3));
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[test]
 fn spsc() {
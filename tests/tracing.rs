@@ -0,0 +1,249 @@
+#![cfg(feature = "tracing")]
+
+use concurrent_queue::{ConcurrentQueue, OverflowPolicy};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_family = "wasm"))]
+use std::thread::sleep;
+#[cfg(not(target_family = "wasm"))]
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+/// An event as seen by the subscriber.
+#[derive(Debug, Default, PartialEq)]
+struct Recorded {
+    level: Option<Level>,
+    message: String,
+    name: Option<String>,
+    capacity: Option<u64>,
+    len: Option<u64>,
+}
+
+impl Visit for Recorded {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.name = Some(value.to_string());
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "capacity" => self.capacity = Some(value),
+            "len" => self.len = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+}
+
+/// A subscriber that records every event.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+impl Recorder {
+    /// Takes the events recorded so far.
+    fn take(&self) -> Vec<Recorded> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut recorded = Recorded {
+            level: Some(*event.metadata().level()),
+            ..Recorded::default()
+        };
+        event.record(&mut recorded);
+        self.0.lock().unwrap().push(recorded);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// Runs `f` with a recorder installed, returning the events it emitted.
+fn record(f: impl FnOnce()) -> Vec<Recorded> {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), f);
+    recorder.take()
+}
+
+/// Builds the event we expect to be recorded.
+fn event(
+    level: Level,
+    message: &str,
+    name: Option<&str>,
+    capacity: Option<u64>,
+    len: u64,
+) -> Recorded {
+    Recorded {
+        level: Some(level),
+        message: message.to_string(),
+        name: name.map(str::to_string),
+        capacity,
+        len: Some(len),
+    }
+}
+
+#[test]
+fn close() {
+    let q = ConcurrentQueue::<i32>::bounded(2).with_name("jobs");
+    q.push(1).unwrap();
+
+    let events = record(|| {
+        assert!(q.close());
+        assert!(!q.close());
+    });
+    assert_eq!(
+        events,
+        [event(
+            Level::DEBUG,
+            "closed the queue",
+            Some("jobs"),
+            Some(2),
+            1
+        )]
+    );
+}
+
+#[test]
+fn rejected() {
+    for q in [ConcurrentQueue::bounded(1), ConcurrentQueue::bounded(2)] {
+        while q.push(0).is_ok() {}
+        let cap = q.capacity().map(|cap| cap as u64);
+        let len = cap.unwrap();
+
+        let events = record(|| {
+            let _ = q.push(1);
+            let _ = q.reserve();
+            q.close();
+            let _ = q.push(2);
+            let _ = q.force_push(3);
+        });
+        let full = || event(Level::DEBUG, "push rejected, queue is full", None, cap, len);
+        let closed = || {
+            event(
+                Level::DEBUG,
+                "push rejected, queue is closed",
+                None,
+                cap,
+                len,
+            )
+        };
+        let close = event(Level::DEBUG, "closed the queue", None, cap, len);
+        assert_eq!(events, [full(), full(), close, closed(), closed()]);
+    }
+}
+
+#[test]
+fn displaced() {
+    let q = ConcurrentQueue::bounded(2).with_name("latest");
+    let events = record(|| {
+        for i in 0..3 {
+            q.force_push(i).unwrap();
+        }
+    });
+    assert_eq!(
+        events,
+        [event(
+            Level::DEBUG,
+            "displaced an item, queue is full",
+            Some("latest"),
+            Some(2),
+            2
+        )]
+    );
+
+    // Dropping the newest item is reported as well.
+    let q = ConcurrentQueue::bounded_with_policy(1, OverflowPolicy::DropNewest);
+    let events = record(|| {
+        q.push(1).unwrap();
+        q.push(2).unwrap();
+    });
+    assert_eq!(
+        events,
+        [event(
+            Level::DEBUG,
+            "dropped the newest item, queue is full",
+            None,
+            Some(1),
+            1
+        )]
+    );
+}
+
+#[test]
+fn blocks() {
+    let q = ConcurrentQueue::unbounded().with_name("log");
+    let events = record(|| {
+        for i in 0..100 {
+            q.push(i).unwrap();
+        }
+    });
+
+    let messages: Vec<_> = events.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "allocated the first block",
+            "allocated a new block",
+            "allocated a new block",
+            "allocated a new block"
+        ]
+    );
+    assert!(events.iter().all(|e| e.level == Some(Level::TRACE)
+        && e.name.as_deref() == Some("log")
+        && e.capacity.is_none()));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn long_spin() {
+    let q = ConcurrentQueue::bounded(1).with_name("slow");
+    let slot = q.reserve().unwrap();
+
+    // Only the blocked consumer records events.
+    let (events, ()) = Parallel::new()
+        .add(|| record(|| assert_eq!(q.pop(), Ok(1))))
+        .finish(|| {
+            sleep(Duration::from_millis(100));
+            slot.write(1);
+        });
+
+    assert_eq!(
+        events.into_iter().next().unwrap(),
+        [event(
+            Level::WARN,
+            "busy-waiting for a long time",
+            Some("slow"),
+            Some(1),
+            1
+        )]
+    );
+}
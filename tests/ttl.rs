@@ -0,0 +1,129 @@
+#![cfg(feature = "std")]
+
+use concurrent_queue::ttl::TtlQueue;
+use concurrent_queue::{PopError, PushError};
+
+#[cfg(not(target_family = "wasm"))]
+use easy_parallel::Parallel;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_family = "wasm"))]
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[cfg(target_family = "wasm")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+#[test]
+fn smoke() {
+    let q = TtlQueue::bounded(2);
+    let past = Instant::now();
+
+    q.push_until(1, past).unwrap();
+    q.push_with_ttl(2, Duration::from_secs(60)).unwrap();
+    assert_eq!(q.push(3), Err(PushError::Full(3)));
+    assert_eq!(q.len(), 2);
+
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert_eq!(q.expired_count(), 1);
+
+    q.push(3).unwrap();
+    q.close();
+    assert_eq!(q.push_until(4, past), Err(PushError::Closed(4)));
+    assert_eq!(q.pop(), Ok(3));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn expiry_handler() {
+    let expired = Arc::new(Mutex::new(Vec::new()));
+    let q = TtlQueue::unbounded().with_expiry_handler({
+        let expired = expired.clone();
+        move |item| expired.lock().unwrap().push(item)
+    });
+
+    let past = Instant::now();
+    for i in 0..10 {
+        if i % 3 == 0 {
+            q.push(i).unwrap();
+        } else {
+            q.push_until(i, past).unwrap();
+        }
+    }
+
+    // Purging keeps the order of the live items.
+    assert_eq!(q.pop(), Ok(0));
+    assert_eq!(q.purge_expired(), 6);
+    assert_eq!(*expired.lock().unwrap(), [1, 2, 4, 5, 7, 8]);
+    assert_eq!(q.pop(), Ok(3));
+    assert_eq!(q.pop(), Ok(6));
+    assert_eq!(q.pop(), Ok(9));
+    assert_eq!(q.expired_count(), 6);
+}
+
+#[test]
+fn huge_ttl() {
+    let q = TtlQueue::unbounded();
+    q.push_with_ttl(1, Duration::MAX).unwrap();
+    assert_eq!(q.pop(), Ok(1));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn expires_while_queued() {
+    const TTL: Duration = Duration::from_millis(20);
+
+    let q = TtlQueue::unbounded();
+    q.push_with_ttl(1, TTL).unwrap();
+    q.push_with_ttl(2, TTL * 100).unwrap();
+    sleep(TTL);
+
+    assert_eq!(q.pop(), Ok(2));
+    assert_eq!(q.expired_count(), 1);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = TtlQueue::bounded(3);
+    let popped = Mutex::new(Vec::new());
+    let past = Instant::now();
+
+    Parallel::new()
+        .each(0..2, |_| {
+            let mut items = Vec::new();
+            loop {
+                match q.pop() {
+                    Ok(x) => items.push(x),
+                    Err(PopError::Closed) => break,
+                    Err(PopError::Empty) => {}
+                }
+            }
+            popped.lock().unwrap().extend(items);
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                let mut value = i;
+                loop {
+                    let result = if i % 2 == 0 {
+                        q.push(value)
+                    } else {
+                        q.push_until(value, past)
+                    };
+                    match result {
+                        Ok(()) => break,
+                        Err(err) => value = err.into_inner(),
+                    }
+                }
+            }
+            q.close();
+        })
+        .run();
+
+    let mut popped = popped.into_inner().unwrap();
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).step_by(2).collect::<Vec<_>>());
+    assert_eq!(q.expired_count(), (COUNT / 2) as u64);
+}
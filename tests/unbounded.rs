@@ -1,11 +1,11 @@
 #![allow(clippy::bool_assert_comparison)]
 
-use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+use concurrent_queue::{ConcurrentQueue, PopError, PushError, TryPopError, TryPushError};
 
 #[cfg(not(target_family = "wasm"))]
 use easy_parallel::Parallel;
 #[cfg(not(target_family = "wasm"))]
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 #[cfg(target_family = "wasm")]
 use wasm_bindgen_test::wasm_bindgen_test as test;
@@ -74,6 +74,626 @@ fn close() {
     assert_eq!(q.pop(), Err(PopError::Closed));
 }
 
+#[test]
+fn reserve() {
+    let q = ConcurrentQueue::unbounded();
+
+    for i in 0..100 {
+        let mut slot = q.reserve().unwrap();
+        slot.value_mut().write(i);
+        unsafe { slot.commit() };
+    }
+    for i in 0..100 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+
+    q.close();
+    assert_eq!(q.reserve().err(), Some(PushError::Closed(())));
+}
+
+#[test]
+fn reserve_abandon() {
+    let q = ConcurrentQueue::<String>::unbounded();
+
+    // Abandon slots across several blocks.
+    for i in 0..100 {
+        q.push(i.to_string()).unwrap();
+        drop(q.reserve().unwrap());
+    }
+    for i in 0..50 {
+        assert_eq!(q.pop(), Ok(i.to_string()));
+    }
+
+    // Abandoned slots are not dropped with the queue.
+    drop(q);
+}
+
+#[test]
+fn pop_ref() {
+    let q = ConcurrentQueue::unbounded();
+    for i in 0..100 {
+        q.push(i.to_string()).unwrap();
+    }
+
+    // Hold on to items across several blocks while the rest are popped.
+    let mut held = Vec::new();
+    for i in 0..100 {
+        if i % 7 == 0 {
+            let mut item = q.pop_ref().unwrap();
+            item.push('!');
+            held.push(item);
+        } else {
+            assert_eq!(q.pop(), Ok(i.to_string()));
+        }
+    }
+    assert_eq!(q.pop_ref().err(), Some(PopError::Empty));
+
+    for (i, item) in held.iter().enumerate() {
+        assert_eq!(**item, format!("{}!", i * 7));
+    }
+    drop(held);
+
+    q.push("hello".to_string()).unwrap();
+    assert_eq!(q.pop_ref().unwrap().into_inner(), "hello");
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn seq() {
+    let q = ConcurrentQueue::unbounded();
+
+    // Sequence numbers carry on across blocks.
+    let mut seq = 0;
+    for i in 0..100 {
+        assert_eq!(q.push_seq(i), Ok(seq));
+        seq += 1;
+        if i % 3 == 0 {
+            assert_eq!(q.reserve().unwrap().seq(), seq);
+            seq += 1;
+        }
+    }
+
+    let mut seq = 0;
+    for i in 0..100 {
+        assert_eq!(q.pop_seq(), Ok((seq, i)));
+        seq += if i % 3 == 0 { 2 } else { 1 };
+    }
+    assert_eq!(q.pop_seq(), Err(PopError::Empty));
+}
+
+#[test]
+fn nonblocking() {
+    let q = ConcurrentQueue::unbounded_nonblocking();
+
+    // Hold on to reservations across several blocks while other items are pushed and popped.
+    let mut held = Vec::new();
+    for i in 0..100 {
+        held.push(q.reserve().unwrap());
+        q.push(i).unwrap();
+        assert_eq!(q.pop(), Ok(i));
+    }
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    // The held items are pushed again at the tail.
+    for (i, slot) in held.into_iter().enumerate() {
+        slot.write(100 + i);
+    }
+    for i in 100..200 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+    assert_eq!(q.pop(), Err(PopError::Empty));
+}
+
+#[test]
+fn nonblocking_abandon() {
+    let q = ConcurrentQueue::<String>::unbounded_nonblocking();
+
+    // Abandon slots that consumers already skipped, across several blocks.
+    for i in 0..100 {
+        let slot = q.reserve().unwrap();
+        q.push(i.to_string()).unwrap();
+        assert_eq!(q.pop(), Ok(i.to_string()));
+        drop(slot);
+    }
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert!(q.is_empty());
+}
+
+#[cfg(feature = "seq")]
+#[test]
+fn nonblocking_seq() {
+    let q = ConcurrentQueue::unbounded_nonblocking();
+
+    let slot = q.reserve().unwrap();
+    assert_eq!(slot.seq(), 0);
+    assert_eq!(q.push_seq('a'), Ok(1));
+    assert_eq!(q.pop_seq(), Ok((1, 'a')));
+
+    // The skipped item moves to a new slot.
+    slot.write('b');
+    assert_eq!(q.pop_seq(), Ok((2, 'b')));
+}
+
+#[test]
+fn nonblocking_nowait() {
+    let q = ConcurrentQueue::unbounded_nonblocking();
+
+    // Items that are still being written are skipped.
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.try_push_nowait(2), Ok(()));
+    assert_eq!(q.try_pop_nowait(), Ok(2));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+    slot.write(1);
+    assert_eq!(q.try_pop_nowait(), Ok(1));
+}
+
+#[test]
+fn nonblocking_close() {
+    let q = ConcurrentQueue::unbounded_nonblocking();
+
+    let slot = q.reserve().unwrap();
+    assert!(q.close());
+
+    // The queue isn't drained while an item is still being pushed.
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert_eq!(q.pop(), Err(PopError::Empty));
+
+    // The item is still added to the closed queue.
+    slot.write(1);
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+
+    // The same goes for an item that was skipped before the queue was closed.
+    let q = ConcurrentQueue::unbounded_nonblocking();
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert!(q.close());
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    assert_eq!(q.push(2), Err(PushError::Closed(2)));
+
+    slot.write(1);
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.pop(), Ok(1));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+
+    // An abandoned item doesn't hold the queue open.
+    let q = ConcurrentQueue::<i32>::unbounded_nonblocking();
+    let slot = q.reserve().unwrap();
+    assert!(q.close());
+    assert_eq!(q.pop(), Err(PopError::Empty));
+    drop(slot);
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[test]
+fn nonblocking_inspect() {
+    let q = ConcurrentQueue::unbounded_nonblocking();
+    q.push(1).unwrap();
+
+    // Pops don't wait for an inspection to finish.
+    let dest = ConcurrentQueue::unbounded();
+    let removed = q.remove_if(|_| {
+        assert_eq!(q.pop(), Err(PopError::Empty));
+        assert_eq!(q.steal_into(&dest, 10), 0);
+        false
+    });
+    assert!(removed.is_empty());
+    assert_eq!(q.pop(), Ok(1));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn nonblocking_close_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 10_000 };
+
+    let q = ConcurrentQueue::unbounded_nonblocking();
+    let pushed = AtomicUsize::new(0);
+    let popped = AtomicUsize::new(0);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                if q.push(i).is_err() {
+                    break;
+                }
+                pushed.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .add(|| {
+            // Close the queue halfway through, while items may be pushed again.
+            while pushed.load(Ordering::SeqCst) < COUNT / 2 {
+                if q.pop().is_ok() {
+                    popped.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            q.close();
+
+            // Items that are still being pushed again show up before the queue is drained.
+            loop {
+                match q.pop() {
+                    Ok(_) => {
+                        popped.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err(PopError::Empty) => {}
+                    Err(PopError::Closed) => break,
+                }
+            }
+        })
+        .run();
+
+    assert_eq!(popped.load(Ordering::SeqCst), pushed.load(Ordering::SeqCst));
+    assert_eq!(q.pop(), Err(PopError::Closed));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn high_water_mark() {
+    let q = ConcurrentQueue::unbounded();
+    assert_eq!(q.high_water_mark(), 0);
+
+    // Span several blocks.
+    for i in 0..100 {
+        q.push(i).unwrap();
+        q.push(i).unwrap();
+        q.pop().unwrap();
+        assert_eq!(q.high_water_mark(), i + 2);
+    }
+
+    for _ in 0..50 {
+        q.pop().unwrap();
+    }
+    q.reset_high_water_mark();
+    assert_eq!(q.high_water_mark(), 50);
+
+    q.push(0).unwrap();
+    assert_eq!(q.high_water_mark(), 51);
+}
+
+#[test]
+fn debug_items() {
+    let q = ConcurrentQueue::unbounded().with_name("log");
+    assert_eq!(format!("{:?}", q.debug_items()), "[]");
+    assert_eq!(
+        format!("{:?}", q),
+        "ConcurrentQueue { name: Some(\"log\"), len: 0, capacity: None, is_closed: false }"
+    );
+
+    // Walk across several blocks.
+    for i in 0..100 {
+        q.push(i).unwrap();
+    }
+    for i in 0..40 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+    let expected = format!("{:?}", (40..100).collect::<Vec<_>>());
+    assert_eq!(format!("{:?}", q.debug_items()), expected);
+
+    // Abandoned and pending reservations are left out.
+    drop(q.reserve().unwrap());
+    let slot = q.reserve().unwrap();
+    assert_eq!(format!("{:?}", q.debug_items()), expected);
+    slot.write(100);
+    let expected = format!("{:?}", (40..=100).collect::<Vec<_>>());
+    assert_eq!(format!("{:?}", q.debug_items()), expected);
+
+    // The queue works as before.
+    for i in 40..=100 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+    assert!(q.is_empty());
+}
+
+#[test]
+fn snapshot() {
+    let q = ConcurrentQueue::unbounded();
+    assert_eq!(q.snapshot(), Vec::<Vec<i32>>::new());
+
+    // Items are cloned across several blocks.
+    for i in 0..100 {
+        q.push(vec![i]).unwrap();
+    }
+    for _ in 0..50 {
+        q.pop().unwrap();
+    }
+    let expected: Vec<_> = (50..100).map(|i| vec![i]).collect();
+    assert_eq!(q.snapshot(), expected);
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), expected);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn debug_items_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::unbounded();
+    let done = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                loop {
+                    match q.pop() {
+                        Ok(x) => {
+                            assert_eq!(x, i);
+                            break;
+                        }
+                        Err(_) => continue,
+                    }
+                }
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .add(|| {
+            while !done.load(Ordering::SeqCst) {
+                // The items are always in order.
+                let view = format!("{:?}", q.debug_items());
+                let items: Vec<usize> = view
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split(", ")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse().unwrap())
+                    .collect();
+                assert!(items.windows(2).all(|w| w[0] < w[1]));
+            }
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn snapshot_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::unbounded();
+    let popped = AtomicUsize::new(0);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                loop {
+                    if let Ok(x) = q.pop() {
+                        assert_eq!(x, i);
+                        popped.store(i + 1, Ordering::SeqCst);
+                        break;
+                    }
+                }
+            }
+        })
+        .add(|| {
+            while popped.load(Ordering::SeqCst) < COUNT {
+                let before = popped.load(Ordering::SeqCst);
+                let items = q.snapshot();
+                let after = popped.load(Ordering::SeqCst);
+
+                // With one producer, the snapshot is a run of consecutive items that were not
+                // popped before it was taken. The consumer may be holding one more item that it
+                // hasn't counted yet.
+                if let Some(&first) = items.first() {
+                    assert!(first >= before);
+                    assert!(first <= after + 1);
+                }
+                assert!(items.windows(2).all(|w| w[0] + 1 == w[1]));
+            }
+        })
+        .run();
+}
+
+#[test]
+fn retain() {
+    let q = ConcurrentQueue::unbounded();
+    for i in 0..100 {
+        q.push(i).unwrap();
+    }
+    for i in 0..10 {
+        assert_eq!(q.pop(), Ok(i));
+    }
+
+    // Remove items across several blocks.
+    q.retain(|&i| i % 10 != 0);
+    let removed = q.remove_if(|&i| i % 10 == 5);
+    assert_eq!(removed, (15..100).step_by(10).collect::<Vec<_>>());
+    q.push(100).unwrap();
+
+    let expected: Vec<_> = (11..=100).filter(|i| i % 5 != 0 || *i == 100).collect();
+    assert_eq!(q.snapshot(), expected);
+    assert_eq!(q.try_iter().collect::<Vec<_>>(), expected);
+    assert!(q.is_empty());
+}
+
+#[test]
+fn nowait() {
+    let q = ConcurrentQueue::unbounded();
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+
+    // Items that are still being written are left in place.
+    let slot = q.reserve().unwrap();
+    assert_eq!(q.try_push_nowait(2), Ok(()));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+    assert_eq!(q.len(), 2);
+    slot.write(1);
+    assert_eq!(q.try_pop_nowait(), Ok(1));
+    assert_eq!(q.try_pop_nowait(), Ok(2));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Empty));
+
+    // The same goes for the last slot of a block.
+    for i in 0..30 {
+        q.push(i).unwrap();
+    }
+    let slot = q.reserve().unwrap();
+    q.push(30).unwrap();
+    for i in 0..30 {
+        assert_eq!(q.try_pop_nowait(), Ok(i));
+    }
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+    slot.write(31);
+    assert_eq!(q.try_pop_nowait(), Ok(31));
+    assert_eq!(q.try_pop_nowait(), Ok(30));
+    q.push(1).unwrap();
+
+    // The head is frozen while the queue is being inspected.
+    q.retain(|_| {
+        assert_eq!(q.try_pop_nowait(), Err(TryPopError::Busy));
+        true
+    });
+    assert_eq!(q.try_pop_nowait(), Ok(1));
+
+    q.close();
+    assert_eq!(q.try_push_nowait(3), Err(TryPushError::Closed(3)));
+    assert_eq!(q.try_pop_nowait(), Err(TryPopError::Closed));
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn retain_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+
+    let q = ConcurrentQueue::unbounded();
+    let done = AtomicBool::new(false);
+
+    Parallel::new()
+        .add(|| {
+            for i in 0..COUNT {
+                while q.push(i).is_err() {}
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+        .add(|| {
+            // Odd items may or may not be removed before they are popped, but even items are
+            // all popped in order.
+            let mut last = None;
+            let mut even = 0;
+            while even < COUNT {
+                if let Ok(x) = q.pop() {
+                    assert!(last < Some(x));
+                    if x % 2 == 0 {
+                        assert_eq!(x, even);
+                        even += 2;
+                    }
+                    last = Some(x);
+                }
+            }
+        })
+        .add(|| {
+            while !done.load(Ordering::SeqCst) {
+                for x in q.remove_if(|x| x % 2 == 1) {
+                    assert_eq!(x % 2, 1);
+                }
+            }
+        })
+        .run();
+}
+
+#[test]
+fn steal_into() {
+    let src = ConcurrentQueue::unbounded();
+    let dest = ConcurrentQueue::unbounded();
+    for i in 0..100 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(src.pop(), Ok(0));
+
+    // Half of the items are moved across several blocks of both queues.
+    assert_eq!(src.steal_into(&dest, 10), 10);
+    assert_eq!(src.steal_into(&dest, 100), 45);
+    assert_eq!(src.len(), 44);
+    assert_eq!(dest.snapshot(), (1..56).collect::<Vec<_>>());
+
+    // Nothing is lost when the destination is closed.
+    dest.close();
+    assert_eq!(src.steal_into(&dest, 100), 0);
+    assert_eq!(
+        src.try_iter().collect::<Vec<_>>(),
+        (56..100).collect::<Vec<_>>()
+    );
+    assert_eq!(src.steal_into(&dest, 100), 0);
+    assert_eq!(dest.len(), 55);
+}
+
+#[test]
+fn steal_into_skipped() {
+    let src = ConcurrentQueue::unbounded();
+    let dest = ConcurrentQueue::bounded(3);
+
+    // Abandoned and removed slots are passed over without counting them.
+    src.push(0).unwrap();
+    drop(src.reserve().unwrap());
+    for i in 1..6 {
+        src.push(i).unwrap();
+    }
+    assert_eq!(src.remove_if(|&i| i == 1), [1]);
+    assert_eq!(src.steal_into(&dest, 2), 2);
+    assert_eq!(dest.snapshot(), [0, 2]);
+
+    // Nothing is lost when the destination fills up.
+    assert_eq!(src.steal_into(&dest, 10), 1);
+    assert_eq!(dest.try_iter().collect::<Vec<_>>(), [0, 2, 3]);
+
+    // An item that is still being written stops the batch, and leftover slots are skipped.
+    let slot = src.reserve().unwrap();
+    src.push(6).unwrap();
+    assert_eq!(src.steal_into(&dest, 10), 2);
+    slot.write(7);
+    assert_eq!(dest.try_iter().collect::<Vec<_>>(), [4, 5]);
+    assert_eq!(src.try_iter().collect::<Vec<_>>(), [7, 6]);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn steal_into_concurrent() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 1_000 };
+    const THIEVES: usize = 3;
+
+    let src = ConcurrentQueue::unbounded();
+    let producers = AtomicUsize::new(2);
+
+    let mut popped: Vec<usize> = Parallel::new()
+        .each(0..2, |p| {
+            for i in (p..COUNT).step_by(2) {
+                src.push(i).unwrap();
+            }
+            if producers.fetch_sub(1, Ordering::SeqCst) == 1 {
+                src.close();
+            }
+            Vec::new()
+        })
+        .each(0..THIEVES, |_| {
+            // Items from each producer arrive in the order they were pushed.
+            let local = ConcurrentQueue::unbounded();
+            let mut last = [None; 2];
+            let mut popped = Vec::new();
+            loop {
+                src.steal_into(&local, 40);
+                let mut got = false;
+                while let Ok(i) = local.pop() {
+                    assert!(last[i % 2] < Some(i));
+                    last[i % 2] = Some(i);
+                    popped.push(i);
+                    got = true;
+                }
+                if !got && src.is_closed() && src.is_empty() {
+                    break popped;
+                }
+            }
+        })
+        .run()
+        .into_iter()
+        .flatten()
+        .collect();
+
+    popped.sort_unstable();
+    assert_eq!(popped, (0..COUNT).collect::<Vec<_>>());
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[test]
 fn spsc() {
@@ -133,6 +753,99 @@ fn mpmc() {
     }
 }
 
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn nowait_mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 25_000 };
+    const THREADS: usize = 4;
+
+    let q = ConcurrentQueue::<usize>::unbounded();
+    let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            // Every item is popped exactly once, whether or not it was busy at first.
+            for _ in 0..COUNT {
+                let n = loop {
+                    if let Ok(x) = q.try_pop_nowait() {
+                        break x;
+                    }
+                };
+                v[n].fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), THREADS);
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn nonblocking_spsc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 100_000 };
+
+    let q = ConcurrentQueue::unbounded_nonblocking();
+
+    Parallel::new()
+        .add(|| {
+            // Items from a single producer stay in order.
+            for i in 0..COUNT {
+                loop {
+                    if let Ok(x) = q.pop() {
+                        assert_eq!(x, i);
+                        break;
+                    }
+                }
+            }
+            assert!(q.pop().is_err());
+        })
+        .add(|| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+        })
+        .run();
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn nonblocking_mpmc() {
+    const COUNT: usize = if cfg!(miri) { 100 } else { 25_000 };
+    const THREADS: usize = 4;
+
+    let q = ConcurrentQueue::<usize>::unbounded_nonblocking();
+    let v = (0..COUNT).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+
+    Parallel::new()
+        .each(0..THREADS, |_| {
+            for _ in 0..COUNT {
+                let n = loop {
+                    if let Ok(x) = q.pop() {
+                        break x;
+                    }
+                };
+                v[n].fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .each(0..THREADS, |_| {
+            for i in 0..COUNT {
+                q.push(i).unwrap();
+            }
+        })
+        .run();
+
+    for c in v {
+        assert_eq!(c.load(Ordering::SeqCst), THREADS);
+    }
+}
+
 #[cfg(not(target_family = "wasm"))]
 #[test]
 fn drops() {